@@ -0,0 +1,216 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+
+// NTSC output rates in CPU cycles
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/*
+ * Delta modulation channel, $4010-$4013
+ *
+ * IL-- RRRR  IRQ enable, loop, rate index
+ * -DDD DDDD  direct load of the output level
+ * AAAA AAAA  sample address, $C000 + A * 64
+ * LLLL LLLL  sample length, L * 16 + 1 bytes
+ *
+ * Sample bytes are fetched by DMA into a one byte buffer whenever it is empty,
+ * so the bus has to fetch them (see `Bus::dmc_dma`). The output unit shifts
+ * out one bit per timer period, each 1 raising the 7 bit output level by 2 and
+ * each 0 lowering it by 2.
+ */
+pub struct DMC {
+    irq_enabled: bool,
+    looping: bool,
+    timer: u16,
+    timer_period: u16,
+    output_level: u8,
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    irq: bool,
+}
+
+impl DMC {
+    pub fn new() -> Self {
+        DMC {
+            irq_enabled: false,
+            looping: false,
+            timer: 0,
+            timer_period: RATE_TABLE[0] - 1,
+            output_level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    pub fn write_control(&mut self, data: u8) {
+        self.irq_enabled = data & 0b1000_0000 != 0;
+        if !self.irq_enabled {
+            self.irq = false;
+        }
+        self.looping = data & 0b0100_0000 != 0;
+        self.timer_period = RATE_TABLE[(data & 0b1111) as usize] - 1;
+    }
+
+    pub fn write_output(&mut self, data: u8) {
+        self.output_level = data & 0b0111_1111;
+    }
+
+    pub fn write_address(&mut self, data: u8) {
+        self.sample_address = 0xC000 | ((data as u16) << 6);
+    }
+
+    pub fn write_length(&mut self, data: u8) {
+        self.sample_length = ((data as u16) << 4) | 1;
+    }
+
+    // any write to $4015 acknowledges the IRQ
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    // what the reset button does besides the $4015 write, the output level
+    // only keeps its lowest bit
+    pub fn reset(&mut self) {
+        self.output_level &= 1;
+    }
+
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // address of the next sample byte, if the buffer needs filling
+    pub fn dma_request(&self) -> Option<u16> {
+        match self.sample_buffer {
+            None if self.bytes_remaining > 0 => Some(self.current_address),
+            _ => None,
+        }
+    }
+
+    pub fn fill(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        self.current_address = match self.current_address {
+            0xFFFF => 0x8000,
+            addr => addr + 1,
+        };
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    // one CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period;
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(data) => {
+                    self.shift_register = data;
+                    self.silence = false;
+                },
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+impl Default for DMC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for DMC {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.looping);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+        w.write_u8(self.output_level);
+        w.write_u16(self.sample_address);
+        w.write_u16(self.sample_length);
+        w.write_u16(self.current_address);
+        w.write_u16(self.bytes_remaining);
+        w.write_bool(self.sample_buffer.is_some());
+        w.write_u8(self.sample_buffer.unwrap_or(0));
+        w.write_u8(self.shift_register);
+        w.write_u8(self.bits_remaining);
+        w.write_bool(self.silence);
+        w.write_bool(self.irq);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.irq_enabled = r.read_bool()?;
+        self.looping = r.read_bool()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        self.output_level = r.read_u8()?;
+        self.sample_address = r.read_u16()?;
+        self.sample_length = r.read_u16()?;
+        self.current_address = r.read_u16()?;
+        self.bytes_remaining = r.read_u16()?;
+        let buffered = r.read_bool()?;
+        let sample = r.read_u8()?;
+        self.sample_buffer = buffered.then_some(sample);
+        self.shift_register = r.read_u8()?;
+        self.bits_remaining = r.read_u8()?;
+        self.silence = r.read_bool()?;
+        self.irq = r.read_bool()?;
+        Ok(())
+    }
+}
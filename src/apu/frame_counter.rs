@@ -0,0 +1,153 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+
+// CPU cycles after a reset at which each step happens
+const STEP1: usize = 7457;
+const STEP2: usize = 14913;
+const STEP3: usize = 22371;
+const FOUR_STEP_LAST: usize = 29829;
+const FOUR_STEP_PERIOD: usize = 29830;
+const FIVE_STEP_LAST: usize = 37281;
+const FIVE_STEP_PERIOD: usize = 37282;
+
+pub enum FrameClock {
+    // envelopes and the triangle's linear counter
+    Quarter,
+    // the quarter frame units, plus length counters and sweeps
+    Half,
+}
+
+/*
+ * $4017
+ * 7  bit  0
+ * ---- ----
+ * MI.. ....
+ * ||
+ * |+-------- IRQ inhibit, also clears the frame IRQ flag
+ * +--------- Mode (0: 4-step, 1: 5-step)
+ *
+ * The 4-step sequence sets the frame IRQ flag on its last step (for 3 cycles
+ * in a row), the 5-step sequence never does. A write resets the sequence 3 or
+ * 4 CPU cycles later, depending on whether it lands on an APU cycle or between
+ * two, and selecting 5-step mode clocks everything straight away.
+ */
+pub struct FrameCounter {
+    five_step: bool,
+    irq_inhibit: bool,
+    irq: bool,
+    cycles: usize,
+    pending_reset: Option<(bool, u8)>,
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        FrameCounter {
+            five_step: false,
+            irq_inhibit: false,
+            irq: false,
+            cycles: 0,
+            pending_reset: None,
+        }
+    }
+
+    pub fn write(&mut self, data: u8, between_apu_cycles: bool) {
+        self.irq_inhibit = data & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+
+        let delay = match between_apu_cycles {
+            true => 4,
+            false => 3,
+        };
+        self.pending_reset = Some((data & 0b1000_0000 != 0, delay));
+    }
+
+    // The reset button restarts the sequence as though $4017 had been
+    // written again with what it last held, and clears the IRQ
+    pub fn reset(&mut self, between_apu_cycles: bool) {
+        self.irq = false;
+        let mode = (self.five_step as u8) << 7;
+        let inhibit = (self.irq_inhibit as u8) << 6;
+        self.write(mode | inhibit, between_apu_cycles);
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    // reading $4015 acknowledges the IRQ
+    pub fn acknowledge(&mut self) {
+        self.irq = false;
+    }
+
+    // one CPU cycle
+    pub fn tick(&mut self) -> Option<FrameClock> {
+        if let Some((five_step, delay)) = self.pending_reset {
+            if delay > 1 {
+                self.pending_reset = Some((five_step, delay - 1));
+            } else {
+                self.pending_reset = None;
+                self.five_step = five_step;
+                self.cycles = 0;
+                if five_step {
+                    return Some(FrameClock::Half);
+                }
+                return None;
+            }
+        }
+
+        self.cycles += 1;
+
+        if !self.five_step && self.cycles >= FOUR_STEP_LAST - 1 && !self.irq_inhibit {
+            self.irq = true;
+        }
+
+        let clock = match (self.five_step, self.cycles) {
+            (_, STEP1) | (_, STEP3) => Some(FrameClock::Quarter),
+            (_, STEP2) => Some(FrameClock::Half),
+            (false, FOUR_STEP_LAST) | (true, FIVE_STEP_LAST) => Some(FrameClock::Half),
+            _ => None,
+        };
+
+        let period = match self.five_step {
+            true => FIVE_STEP_PERIOD,
+            false => FOUR_STEP_PERIOD,
+        };
+        if self.cycles == period {
+            self.cycles = 0;
+        }
+
+        clock
+    }
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for FrameCounter {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.five_step);
+        w.write_bool(self.irq_inhibit);
+        w.write_bool(self.irq);
+        w.write_u64(self.cycles as u64);
+        let (five_step, delay) = self.pending_reset.unwrap_or((false, 0));
+        w.write_bool(self.pending_reset.is_some());
+        w.write_bool(five_step);
+        w.write_u8(delay);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.five_step = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.irq = r.read_bool()?;
+        self.cycles = r.read_u64()? as usize;
+        let pending = r.read_bool()?;
+        let five_step = r.read_bool()?;
+        let delay = r.read_u8()?;
+        self.pending_reset = pending.then_some((five_step, delay));
+        Ok(())
+    }
+}
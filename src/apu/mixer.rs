@@ -0,0 +1,45 @@
+/*
+ * The channels are mixed by resistor networks rather than added up, which
+ * compresses louder sounds. The usual approximation is two lookup tables, one
+ * for the pulses and one for triangle, noise and DMC:
+ *
+ * pulse_out = 95.52 / (8128.0 / (pulse1 + pulse2) + 100)
+ * tnd_out   = 163.67 / (24329.0 / (3 * triangle + 2 * noise + dmc) + 100)
+ *
+ * The output is in the range 0.0 to about 1.0.
+ */
+pub struct Mixer {
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        let mut pulse_table = [0.0; 31];
+        for (n, value) in pulse_table.iter_mut().enumerate().skip(1) {
+            *value = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+
+        let mut tnd_table = [0.0; 203];
+        for (n, value) in tnd_table.iter_mut().enumerate().skip(1) {
+            *value = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+
+        Mixer {
+            pulse_table,
+            tnd_table,
+        }
+    }
+
+    pub fn mix(&self, pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+        let pulse = self.pulse_table[(pulse1 + pulse2) as usize];
+        let tnd = self.tnd_table[3 * triangle as usize + 2 * noise as usize + dmc as usize];
+        pulse + tnd
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,270 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use self::dmc::DMC;
+use self::frame_counter::{FrameClock, FrameCounter};
+use self::mixer::Mixer;
+use self::noise::Noise;
+use self::pulse::Pulse;
+use self::resampler::Resampler;
+use self::triangle::Triangle;
+
+pub mod dmc;
+pub mod envelope;
+pub mod frame_counter;
+pub mod length;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod resampler;
+pub mod sweep;
+pub mod triangle;
+
+#[cfg(test)]
+mod tests;
+
+pub const CPU_CLOCK_NTSC: f64 = 1_789_773.0;
+
+/*
+ * The 2A03's audio processing unit, registers $4000-$4013, $4015 and $4017.
+ * It runs off the CPU clock, with most of the channels' timers ticking on
+ * every other CPU cycle (one APU cycle).
+ *
+ * Once a sample rate is set the mixed output is resampled down to that rate
+ * and collected for the frontend to pick up with `take_samples`.
+ */
+pub struct APU {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: DMC,
+    frame_counter: FrameCounter,
+    mixer: Mixer,
+    cycles: usize,
+    resampler: Option<Resampler>,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: DMC::new(),
+            frame_counter: FrameCounter::new(),
+            mixer: Mixer::new(),
+            cycles: 0,
+            resampler: None,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.resampler = Some(Resampler::new(CPU_CLOCK_NTSC, rate));
+    }
+
+    pub fn set_rate_adjust(&mut self, adjust: f64) {
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.set_rate_adjust(adjust);
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        match self.resampler.as_mut() {
+            Some(resampler) => resampler.take(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn output(&self) -> f32 {
+        self.mixer.mix(
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        )
+    }
+
+    /*
+     * The reset button. It silences every channel like a write of 0 to $4015,
+     * which stops the DMC and clears its IRQ, and restarts the frame counter
+     * in the mode it was in. The registers keep what was written to them.
+     */
+    pub fn reset(&mut self) {
+        self.write_register(0x4015, 0);
+        self.frame_counter.reset(self.cycles % 2 == 1);
+        self.triangle.reset();
+        self.dmc.reset();
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_irq_pending() || self.dmc_irq_pending()
+    }
+
+    pub fn frame_irq_pending(&self) -> bool {
+        self.frame_counter.irq_pending()
+    }
+
+    pub fn dmc_irq_pending(&self) -> bool {
+        self.dmc.irq_pending()
+    }
+
+    // The DMC can't read memory itself, the bus checks this every cycle and
+    // hands the byte back through `dmc_fill`.
+    pub fn dmc_dma_request(&self) -> Option<u16> {
+        self.dmc.dma_request()
+    }
+
+    pub fn dmc_fill(&mut self, data: u8) {
+        self.dmc.fill(data);
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000 => self.pulse1.write_control(data),
+            0x4001 => self.pulse1.write_sweep(data),
+            0x4002 => self.pulse1.write_timer_lo(data),
+            0x4003 => self.pulse1.write_timer_hi(data),
+
+            0x4004 => self.pulse2.write_control(data),
+            0x4005 => self.pulse2.write_sweep(data),
+            0x4006 => self.pulse2.write_timer_lo(data),
+            0x4007 => self.pulse2.write_timer_hi(data),
+
+            0x4008 => self.triangle.write_linear_counter(data),
+            0x400A => self.triangle.write_timer_lo(data),
+            0x400B => self.triangle.write_timer_hi(data),
+
+            0x400C => self.noise.write_control(data),
+            0x400E => self.noise.write_period(data),
+            0x400F => self.noise.write_length(data),
+
+            0x4010 => self.dmc.write_control(data),
+            0x4011 => self.dmc.write_output(data),
+            0x4012 => self.dmc.write_address(data),
+            0x4013 => self.dmc.write_length(data),
+
+            0x4015 => {
+                self.pulse1.set_enabled(data & 0b0000_0001 != 0);
+                self.pulse2.set_enabled(data & 0b0000_0010 != 0);
+                self.triangle.set_enabled(data & 0b0000_0100 != 0);
+                self.noise.set_enabled(data & 0b0000_1000 != 0);
+                self.dmc.set_enabled(data & 0b0001_0000 != 0);
+            },
+
+            0x4017 => self.frame_counter.write(data, self.cycles % 2 == 1),
+
+            _ => {},
+        }
+    }
+
+    // $4015, which channels still have a non-zero length counter and which
+    // IRQs are pending. Reading acknowledges the frame IRQ.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_counter.acknowledge();
+        status
+    }
+
+    // $4015 without acknowledging the frame IRQ
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.active() {
+            status |= 0b0000_0001;
+        }
+        if self.pulse2.active() {
+            status |= 0b0000_0010;
+        }
+        if self.triangle.active() {
+            status |= 0b0000_0100;
+        }
+        if self.noise.active() {
+            status |= 0b0000_1000;
+        }
+        if self.dmc.active() {
+            status |= 0b0001_0000;
+        }
+        if self.frame_counter.irq_pending() {
+            status |= 0b0100_0000;
+        }
+        if self.dmc.irq_pending() {
+            status |= 0b1000_0000;
+        }
+        status
+    }
+
+    // one CPU cycle
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles.is_multiple_of(2) {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        self.clock_frame_counter();
+        self.sample();
+    }
+
+    fn sample(&mut self) {
+        if self.resampler.is_none() {
+            return;
+        }
+
+        let output = self.output();
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.push(output);
+        }
+    }
+
+    fn clock_frame_counter(&mut self) {
+        let clock = match self.frame_counter.tick() {
+            Some(clock) => clock,
+            None => return,
+        };
+
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+
+        if let FrameClock::Half = clock {
+            self.pulse1.clock_half_frame();
+            self.pulse2.clock_half_frame();
+            self.triangle.clock_half_frame();
+            self.noise.clock_half_frame();
+        }
+    }
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The resampler is left alone, it belongs to the frontend's audio setup
+impl Snapshot for APU {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.pulse1.save_state(w);
+        self.pulse2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+        self.frame_counter.save_state(w);
+        w.write_u64(self.cycles as u64);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.pulse1.load_state(r)?;
+        self.pulse2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)?;
+        self.frame_counter.load_state(r)?;
+        self.cycles = r.read_u64()? as usize;
+        Ok(())
+    }
+}
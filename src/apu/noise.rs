@@ -0,0 +1,126 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+
+// NTSC timer periods in CPU cycles
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/*
+ * $400C-$400F
+ *
+ * --LC VVVV  length counter halt, envelope
+ * ---- ----  unused
+ * M--- PPPP  mode, period index
+ * LLLL L---  length counter load (also restarts the envelope)
+ *
+ * Each time the timer runs out the 15 bit shift register is shifted right,
+ * with bit 0 XOR bit 1 fed into bit 14. Mode 1 uses bit 6 instead of bit 1,
+ * which gives a much shorter, metallic sounding sequence. The channel is
+ * silent while bit 0 is set.
+ */
+pub struct Noise {
+    envelope: Envelope,
+    length: LengthCounter,
+    mode: bool,
+    shift_register: u16,
+    timer: u16,
+    timer_period: u16,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            envelope: Envelope::new(),
+            length: LengthCounter::new(),
+            mode: false,
+            shift_register: 1,
+            timer: 0,
+            timer_period: PERIOD_TABLE[0] - 1,
+        }
+    }
+
+    pub fn write_control(&mut self, data: u8) {
+        self.length.set_halt(data & 0b0010_0000 != 0);
+        self.envelope.write(data);
+    }
+
+    pub fn write_period(&mut self, data: u8) {
+        self.mode = data & 0b1000_0000 != 0;
+        self.timer_period = PERIOD_TABLE[(data & 0b1111) as usize] - 1;
+    }
+
+    pub fn write_length(&mut self, data: u8) {
+        self.length.load(data);
+        self.envelope.restart();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    // one CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period;
+
+        let tap = match self.mode {
+            true => 6,
+            false => 1,
+        };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 1 != 0 || !self.length.active() {
+            return 0;
+        }
+
+        self.envelope.output()
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Noise {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.envelope.save_state(w);
+        self.length.save_state(w);
+        w.write_bool(self.mode);
+        w.write_u16(self.shift_register);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.envelope.load_state(r)?;
+        self.length.load_state(r)?;
+        self.mode = r.read_bool()?;
+        self.shift_register = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        Ok(())
+    }
+}
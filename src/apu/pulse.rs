@@ -0,0 +1,129 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+use super::sweep::Sweep;
+
+// Waveforms for the 4 duty cycles (12.5%, 25%, 50%, 25% negated), in the order
+// the sequencer steps through them as it counts down.
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/*
+ * $4000-$4003 / $4004-$4007
+ *
+ * DDLC VVVV  duty, length counter halt, envelope
+ * EPPP NSSS  sweep
+ * TTTT TTTT  timer low
+ * LLLL LTTT  length counter load, timer high
+ *
+ * The timer is clocked every APU cycle (every other CPU cycle) and steps the
+ * 8 step duty sequencer each time it reaches 0.
+ */
+pub struct Pulse {
+    envelope: Envelope,
+    length: LengthCounter,
+    sweep: Sweep,
+    duty: u8,
+    step: u8,
+    timer: u16,
+    timer_period: u16,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            envelope: Envelope::new(),
+            length: LengthCounter::new(),
+            sweep: Sweep::new(ones_complement),
+            duty: 0,
+            step: 0,
+            timer: 0,
+            timer_period: 0,
+        }
+    }
+
+    pub fn write_control(&mut self, data: u8) {
+        self.duty = data >> 6;
+        self.length.set_halt(data & 0b0010_0000 != 0);
+        self.envelope.write(data);
+    }
+
+    pub fn write_sweep(&mut self, data: u8) {
+        self.sweep.write(data);
+    }
+
+    pub fn write_timer_lo(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | data as u16;
+    }
+
+    pub fn write_timer_hi(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+        self.length.load(data);
+        self.envelope.restart();
+        self.step = 0;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = self.step.wrapping_sub(1) & 0b111;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+        self.sweep.clock(&mut self.timer_period);
+    }
+
+    pub fn output(&self) -> u8 {
+        if DUTY_TABLE[self.duty as usize][self.step as usize] == 0
+            || !self.length.active()
+            || self.sweep.mutes(self.timer_period)
+        {
+            return 0;
+        }
+
+        self.envelope.output()
+    }
+}
+
+impl Snapshot for Pulse {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.envelope.save_state(w);
+        self.length.save_state(w);
+        self.sweep.save_state(w);
+        w.write_u8(self.duty);
+        w.write_u8(self.step);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.envelope.load_state(r)?;
+        self.length.load_state(r)?;
+        self.sweep.load_state(r)?;
+        self.duty = r.read_u8()?;
+        self.step = r.read_u8()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::f32::consts::PI;
+
+// the NES has a high-pass at about 90Hz on its output, which also takes the
+// DC offset out of the mixer's 0.0 to 1.0 range
+const HIGH_PASS_HZ: f32 = 90.0;
+
+// the furthest the rate control may stretch or squeeze the output, half a
+// percent is well below what anyone can hear as a pitch change
+pub const MAX_RATE_ADJUST: f64 = 0.005;
+
+/*
+ * Brings the mixer output from the CPU clock down to the frontend's sample
+ * rate. Every CPU cycle's output goes into a running average which is emitted
+ * once a sample period's worth has been collected, a box filter that is cheap
+ * and takes care of most of the aliasing at these ratios.
+ *
+ * The period can be nudged with `set_rate_adjust` so that the frontend can
+ * keep its audio buffer from slowly draining or filling up when the emulator
+ * and the sound card don't quite agree on how long a second is.
+ */
+pub struct Resampler {
+    // CPU cycles per output sample
+    period: f64,
+    adjust: f64,
+    clock: f64,
+    sum: f32,
+    count: u32,
+
+    high_pass: f32,
+    last_input: f32,
+    last_output: f32,
+
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * HIGH_PASS_HZ);
+        let dt = 1.0 / sample_rate as f32;
+
+        Resampler {
+            period: clock_rate / sample_rate as f64,
+            adjust: 1.0,
+            clock: 0.0,
+            sum: 0.0,
+            count: 0,
+            high_pass: rc / (rc + dt),
+            last_input: 0.0,
+            last_output: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    // > 1.0 makes more samples per second of emulation, < 1.0 fewer
+    pub fn set_rate_adjust(&mut self, adjust: f64) {
+        self.adjust = adjust.clamp(1.0 - MAX_RATE_ADJUST, 1.0 + MAX_RATE_ADJUST);
+    }
+
+    pub fn push(&mut self, input: f32) {
+        self.sum += input;
+        self.count += 1;
+        self.clock += 1.0;
+
+        let period = self.period / self.adjust;
+        if self.clock >= period {
+            self.clock -= period;
+            let average = self.sum / self.count as f32;
+            self.sum = 0.0;
+            self.count = 0;
+
+            let output = self.high_pass * (self.last_output + average - self.last_input);
+            self.last_input = average;
+            self.last_output = output;
+            self.samples.push(output);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+
+/*
+ * Pulse channel sweep unit, which moves the timer period up or down by a
+ * fraction of itself every few half frames.
+ *
+ * $4001 / $4005
+ * 7  bit  0
+ * ---- ----
+ * EPPP NSSS
+ * |||| ||||
+ * |||| |+++- Shift count
+ * |||| +---- Negate
+ * |+++------ Divider period is P + 1 half frames
+ * +--------- Enabled
+ *
+ * Pulse 1 negates with ones' complement (subtracts change + 1) while pulse 2
+ * uses two's complement, so the two sweep down at slightly different rates.
+ * The channel is muted whenever the target period would go past $7FF, even if
+ * the sweep is disabled.
+ */
+pub struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    divider: u8,
+    reload: bool,
+    ones_complement: bool,
+}
+
+impl Sweep {
+    pub fn new(ones_complement: bool) -> Self {
+        Sweep {
+            enabled: false,
+            period: 0,
+            negate: false,
+            shift: 0,
+            divider: 0,
+            reload: false,
+            ones_complement,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.enabled = data & 0b1000_0000 != 0;
+        self.period = (data >> 4) & 0b111;
+        self.negate = data & 0b1000 != 0;
+        self.shift = data & 0b111;
+        self.reload = true;
+    }
+
+    pub fn target(&self, period: u16) -> u16 {
+        let change = period >> self.shift;
+
+        match (self.negate, self.ones_complement) {
+            (false, _) => period + change,
+            (true, true) => period.saturating_sub(change + 1),
+            (true, false) => period.saturating_sub(change),
+        }
+    }
+
+    pub fn mutes(&self, period: u16) -> bool {
+        period < 8 || self.target(period) > 0x07FF
+    }
+
+    // half frame
+    pub fn clock(&mut self, period: &mut u16) {
+        if self.divider == 0 && self.enabled && self.shift != 0 && !self.mutes(*period) {
+            *period = self.target(*period);
+        }
+
+        if self.divider == 0 || self.reload {
+            self.divider = self.period;
+            self.reload = false;
+        } else {
+            self.divider -= 1;
+        }
+    }
+}
+
+// ones_complement is wiring, not state
+impl Snapshot for Sweep {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u8(self.period);
+        w.write_bool(self.negate);
+        w.write_u8(self.shift);
+        w.write_u8(self.divider);
+        w.write_bool(self.reload);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.enabled = r.read_bool()?;
+        self.period = r.read_u8()?;
+        self.negate = r.read_bool()?;
+        self.shift = r.read_u8()?;
+        self.divider = r.read_u8()?;
+        self.reload = r.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use super::*;
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+use super::resampler::Resampler;
+use super::sweep::Sweep;
+
+fn ticks(apu: &mut APU, cycles: usize) {
+    for _ in 0..cycles {
+        apu.tick();
+    }
+}
+
+#[test]
+fn test_envelope_decay() {
+    let mut envelope = Envelope::new();
+    envelope.write(0b0000_0001);
+    envelope.restart();
+
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+
+    // the divider period is volume + 1 clocks
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+    envelope.clock();
+    assert_eq!(envelope.output(), 14);
+
+    for _ in 0..40 {
+        envelope.clock();
+    }
+    assert_eq!(envelope.output(), 0);
+}
+
+#[test]
+fn test_envelope_loop() {
+    let mut envelope = Envelope::new();
+    envelope.write(0b0010_0000);
+    envelope.restart();
+
+    for _ in 0..16 {
+        envelope.clock();
+    }
+    assert_eq!(envelope.output(), 0);
+
+    envelope.clock();
+    assert_eq!(envelope.output(), 15);
+}
+
+#[test]
+fn test_envelope_constant_volume() {
+    let mut envelope = Envelope::new();
+    envelope.write(0b0001_0111);
+    envelope.restart();
+    envelope.clock();
+    envelope.clock();
+
+    assert_eq!(envelope.output(), 7);
+}
+
+#[test]
+fn test_length_counter() {
+    let mut length = LengthCounter::new();
+    length.load(0b0001_1000);
+    assert!(!length.active());
+
+    length.set_enabled(true);
+    length.load(0b0001_1000);
+    length.clock();
+    assert!(length.active());
+    length.clock();
+    assert!(!length.active());
+
+    length.load(0);
+    length.set_halt(true);
+    for _ in 0..20 {
+        length.clock();
+    }
+    assert!(length.active());
+
+    length.set_enabled(false);
+    assert!(!length.active());
+}
+
+#[test]
+fn test_sweep_negate_differs_between_pulses() {
+    let mut pulse1 = Sweep::new(true);
+    let mut pulse2 = Sweep::new(false);
+    pulse1.write(0b0000_1001);
+    pulse2.write(0b0000_1001);
+
+    assert_eq!(pulse1.target(0x100), 0x100 - 0x80 - 1);
+    assert_eq!(pulse2.target(0x100), 0x100 - 0x80);
+}
+
+#[test]
+fn test_sweep_updates_period() {
+    let mut sweep = Sweep::new(false);
+    sweep.write(0b1001_0001);
+    let mut period = 0x100;
+
+    // reload the divider, then wait for it to count down
+    sweep.clock(&mut period);
+    assert_eq!(period, 0x180);
+    sweep.clock(&mut period);
+    assert_eq!(period, 0x180);
+    sweep.clock(&mut period);
+    assert_eq!(period, 0x240);
+}
+
+#[test]
+fn test_sweep_mutes() {
+    let sweep = Sweep::new(false);
+    assert!(sweep.mutes(7));
+    assert!(!sweep.mutes(8));
+    assert!(sweep.mutes(0x0400));
+    assert!(!sweep.mutes(0x03FF));
+}
+
+#[test]
+fn test_pulse_duty_sequence() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b01);
+    apu.write_register(0x4000, 0b1001_1010);
+    apu.write_register(0x4002, 8);
+    apu.write_register(0x4003, 0);
+
+    // 50% duty, each step lasts period + 1 APU cycles and the sequencer
+    // counts down from 0
+    let mut wave = Vec::new();
+    for _ in 0..8 {
+        ticks(&mut apu, 18);
+        wave.push(apu.pulse1.output());
+    }
+    assert_eq!(wave, vec![0, 0, 0, 10, 10, 10, 10, 0]);
+}
+
+#[test]
+fn test_pulse_silenced_by_low_period() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b10);
+    apu.write_register(0x4004, 0b0011_1111);
+    apu.write_register(0x4006, 7);
+    apu.write_register(0x4007, 0);
+
+    for _ in 0..16 {
+        ticks(&mut apu, 2);
+        assert_eq!(apu.pulse2.output(), 0);
+    }
+}
+
+#[test]
+fn test_status_and_length_clocks() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b11);
+    // length index 3 loads 2
+    apu.write_register(0x4003, 0b0001_1000);
+    apu.write_register(0x4007, 0b0001_1000);
+    assert_eq!(apu.read_status(), 0b11);
+
+    // two half frames in one 4-step sequence
+    ticks(&mut apu, 29830);
+    assert_eq!(apu.read_status() & 0b1111, 0);
+
+    apu.write_register(0x4007, 0b0001_1000);
+    apu.write_register(0x4015, 0b01);
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn test_triangle_sequence() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b0100);
+    apu.write_register(0x4008, 0b0111_1111);
+    apu.write_register(0x400A, 2);
+    apu.write_register(0x400B, 0);
+    assert_eq!(apu.read_status(), 0b0100);
+
+    // nothing moves until a quarter frame loads the linear counter
+    ticks(&mut apu, 100);
+    assert_eq!(apu.triangle.output(), 15);
+
+    ticks(&mut apu, 7457 - 100);
+    let mut wave = Vec::new();
+    for _ in 0..32 {
+        ticks(&mut apu, 3);
+        wave.push(apu.triangle.output());
+    }
+    assert_eq!(&wave[..4], &[14, 13, 12, 11]);
+    assert_eq!(&wave[14..18], &[0, 0, 1, 2]);
+}
+
+#[test]
+fn test_triangle_linear_counter_stops_sequence() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b0100);
+    apu.write_register(0x4008, 0b0000_0001);
+    apu.write_register(0x400A, 2);
+    apu.write_register(0x400B, 0);
+
+    // loaded with 1 on the first quarter frame, 0 on the second
+    ticks(&mut apu, 14913);
+    let output = apu.triangle.output();
+    ticks(&mut apu, 300);
+    assert_eq!(apu.triangle.output(), output);
+}
+
+#[test]
+fn test_triangle_ultrasonic_is_silenced() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b0100);
+    apu.write_register(0x4008, 0b0111_1111);
+    apu.write_register(0x400A, 1);
+    apu.write_register(0x400B, 0);
+
+    ticks(&mut apu, 8000);
+    assert_eq!(apu.triangle.output(), 15);
+}
+
+#[test]
+fn test_noise_shift_register() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b1000);
+    apu.write_register(0x400C, 0b0011_1111);
+    apu.write_register(0x400E, 0);
+    apu.write_register(0x400F, 0);
+
+    // the register starts at 1, so the first shift feeds a 1 into bit 14
+    assert_eq!(apu.noise.output(), 0);
+    ticks(&mut apu, 1);
+    assert_eq!(apu.noise.output(), 15);
+
+    // the long sequence repeats every 32767 shifts
+    let mut outputs = Vec::new();
+    for _ in 0..32767 * 2 {
+        ticks(&mut apu, 4);
+        outputs.push(apu.noise.output());
+    }
+    assert_eq!(outputs[..32767], outputs[32767..]);
+}
+
+#[test]
+fn test_noise_short_mode() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b1000);
+    apu.write_register(0x400C, 0b0011_1111);
+    apu.write_register(0x400E, 0b1000_0000);
+    apu.write_register(0x400F, 0);
+
+    ticks(&mut apu, 1);
+    let mut outputs = Vec::new();
+    for _ in 0..93 * 2 {
+        ticks(&mut apu, 4);
+        outputs.push(apu.noise.output());
+    }
+    assert_eq!(outputs[..93], outputs[93..]);
+}
+
+#[test]
+fn test_mixer() {
+    let mixer = Mixer::new();
+    assert_eq!(mixer.mix(0, 0, 0, 0, 0), 0.0);
+
+    let pulses = mixer.mix(15, 15, 0, 0, 0);
+    assert!((pulses - 0.2585).abs() < 0.001);
+
+    let all = mixer.mix(15, 15, 15, 15, 127);
+    assert!(all > 0.99 && all < 1.01);
+
+    // non-linear, two channels are less than twice as loud as one
+    assert!(mixer.mix(15, 15, 0, 0, 0) < 2.0 * mixer.mix(15, 0, 0, 0, 0));
+}
+
+#[test]
+fn test_sample_rate() {
+    let mut apu = APU::new();
+    ticks(&mut apu, 1000);
+    assert!(apu.take_samples().is_empty());
+
+    apu.set_sample_rate(44100);
+    ticks(&mut apu, (CPU_CLOCK_NTSC / 100.0).ceil() as usize);
+    assert_eq!(apu.take_samples().len(), 441);
+    assert!(apu.take_samples().is_empty());
+}
+
+#[test]
+fn test_dmc_output() {
+    let mut apu = APU::new();
+    apu.write_register(0x4010, 0x0F);
+    apu.write_register(0x4011, 64);
+    apu.write_register(0x4013, 0);
+    apu.write_register(0x4015, 0b0001_0000);
+
+    assert_eq!(apu.dmc_dma_request(), Some(0xC000));
+    apu.dmc_fill(0b0000_1111);
+    assert_eq!(apu.dmc_dma_request(), None);
+    assert_eq!(apu.read_status() & 0b0001_0000, 0);
+
+    // the byte is picked up at the end of the current (silent) 8 bit cycle,
+    // then four 1s and four 0s
+    ticks(&mut apu, 54 * 8);
+    assert_eq!(apu.dmc.output(), 64);
+
+    let mut levels = Vec::new();
+    for _ in 0..8 {
+        ticks(&mut apu, 54);
+        levels.push(apu.dmc.output());
+    }
+    assert_eq!(levels, vec![66, 68, 70, 72, 70, 68, 66, 64]);
+}
+
+#[test]
+fn test_dmc_address_wraps() {
+    let mut apu = APU::new();
+    apu.write_register(0x4012, 0xFF);
+    apu.write_register(0x4013, 0x04);
+    apu.write_register(0x4015, 0b0001_0000);
+
+    for _ in 0..0x40 {
+        let addr = apu.dmc_dma_request().unwrap();
+        apu.dmc_fill(0);
+        ticks(&mut apu, 428 * 8);
+        assert!(addr >= 0xFFC0);
+    }
+    assert_eq!(apu.dmc_dma_request(), Some(0x8000));
+}
+
+#[test]
+fn test_frame_irq() {
+    let mut apu = APU::new();
+    ticks(&mut apu, 29827);
+    assert!(!apu.irq_pending());
+    ticks(&mut apu, 1);
+    assert!(apu.irq_pending());
+
+    // reading $4015 reports and acknowledges it
+    assert_eq!(apu.read_status() & 0b0100_0000, 0b0100_0000);
+    assert!(!apu.irq_pending());
+
+    // still being set on the next two cycles
+    ticks(&mut apu, 2);
+    assert!(apu.irq_pending());
+    apu.read_status();
+    ticks(&mut apu, 29830);
+    assert!(apu.irq_pending());
+}
+
+#[test]
+fn test_frame_irq_inhibit() {
+    let mut apu = APU::new();
+    ticks(&mut apu, 29830);
+    assert!(apu.irq_pending());
+
+    apu.write_register(0x4017, 0b0100_0000);
+    assert!(!apu.irq_pending());
+    ticks(&mut apu, 29830 * 2);
+    assert!(!apu.irq_pending());
+}
+
+#[test]
+fn test_five_step_mode() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b0001);
+    // length index 1 loads 254, the 5-step sequence has no IRQ
+    apu.write_register(0x4003, 0b0000_1000);
+    apu.write_register(0x4017, 0b1000_0000);
+    ticks(&mut apu, 37282 * 2);
+    assert!(!apu.irq_pending());
+
+    // the write clocks a half frame straight away
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0b0001);
+    apu.write_register(0x4003, 0b0001_1000);
+    apu.write_register(0x4017, 0b1000_0000);
+    ticks(&mut apu, 4);
+    assert_eq!(apu.read_status() & 1, 1);
+    ticks(&mut apu, 14913);
+    assert_eq!(apu.read_status() & 1, 0);
+}
+
+#[test]
+fn test_frame_counter_write_delay() {
+    // A length counter of 2 runs out on the second half frame after the
+    // reset. On an APU cycle the reset is 3 cycles after the write, between
+    // two it's 4.
+    for (offset, delay) in [(2, 3), (1, 4)] {
+        let mut apu = APU::new();
+        ticks(&mut apu, offset);
+        apu.write_register(0x4015, 0b0001);
+        apu.write_register(0x4003, 0b0001_1000);
+        apu.write_register(0x4017, 0);
+
+        ticks(&mut apu, delay + 29829 - 1);
+        assert_eq!(apu.read_status() & 1, 1);
+        ticks(&mut apu, 1);
+        assert_eq!(apu.read_status() & 1, 0);
+    }
+}
+
+#[test]
+fn test_reset() {
+    let mut apu = APU::new();
+    apu.write_register(0x4011, 0x45);
+    apu.write_register(0x4015, 0b0001_0001);
+    apu.write_register(0x4003, 0b0000_1000);
+    ticks(&mut apu, 29830);
+    assert_eq!(apu.peek_status(), 0b0101_0001);
+
+    // like a write of 0 to $4015, with the frame IRQ cleared too
+    apu.reset();
+    assert!(!apu.irq_pending());
+    assert_eq!(apu.peek_status(), 0);
+    assert_eq!(apu.dmc.output(), 1);
+
+    // and the frame counter starts its sequence again
+    ticks(&mut apu, 29830);
+    assert!(!apu.irq_pending());
+    ticks(&mut apu, 4);
+    assert!(apu.irq_pending());
+}
+
+#[test]
+fn test_resampler_rate_adjust() {
+    let cycles = (CPU_CLOCK_NTSC / 10.0).ceil() as usize;
+
+    let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 44100);
+    (0..cycles).for_each(|_| resampler.push(0.5));
+    assert_eq!(resampler.take().len(), 4410);
+
+    let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 44100);
+    resampler.set_rate_adjust(1.002);
+    (0..cycles).for_each(|_| resampler.push(0.5));
+    assert_eq!(resampler.take().len(), 4418);
+
+    // clamped to half a percent
+    let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 44100);
+    resampler.set_rate_adjust(0.5);
+    (0..cycles).for_each(|_| resampler.push(0.5));
+    assert_eq!(resampler.take().len(), 4387);
+}
+
+#[test]
+fn test_resampler_removes_dc() {
+    let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 44100);
+    (0..CPU_CLOCK_NTSC as usize).for_each(|_| resampler.push(1.0));
+
+    let samples = resampler.take();
+    assert!(samples[0] > 0.9);
+    assert!(samples.last().unwrap().abs() < 0.001);
+}
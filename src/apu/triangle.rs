@@ -0,0 +1,140 @@
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::length::LengthCounter;
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/*
+ * $4008-$400B
+ *
+ * CRRR RRRR  length counter halt / linear counter control, linear counter reload
+ * ---- ----  unused
+ * TTTT TTTT  timer low
+ * LLLL LTTT  length counter load, timer high (also sets the linear counter reload flag)
+ *
+ * The timer runs at the CPU clock and steps the 32 step sequence, as long as
+ * both the linear counter and the length counter are non-zero. There is no
+ * volume control, stopping the sequencer holds the output at its last level.
+ */
+pub struct Triangle {
+    length: LengthCounter,
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    step: u8,
+    timer: u16,
+    timer_period: u16,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle {
+            length: LengthCounter::new(),
+            control: false,
+            linear_reload_value: 0,
+            linear_counter: 0,
+            linear_reload: false,
+            step: 0,
+            timer: 0,
+            timer_period: 0,
+        }
+    }
+
+    pub fn write_linear_counter(&mut self, data: u8) {
+        self.control = data & 0b1000_0000 != 0;
+        self.length.set_halt(self.control);
+        self.linear_reload_value = data & 0b0111_1111;
+    }
+
+    pub fn write_timer_lo(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | data as u16;
+    }
+
+    pub fn write_timer_hi(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+        self.length.load(data);
+        self.linear_reload = true;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    // the reset button puts the sequence back at its start
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period;
+
+        // Periods of 0 and 1 would play an ultrasonic tone that just comes
+        // out as a pop, so the sequencer is left where it is instead.
+        if self.linear_counter > 0 && self.length.active() && self.timer_period >= 2 {
+            self.step = (self.step + 1) & 0b1_1111;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+}
+
+impl Default for Triangle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Triangle {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.length.save_state(w);
+        w.write_bool(self.control);
+        w.write_u8(self.linear_reload_value);
+        w.write_u8(self.linear_counter);
+        w.write_bool(self.linear_reload);
+        w.write_u8(self.step);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.length.load_state(r)?;
+        self.control = r.read_bool()?;
+        self.linear_reload_value = r.read_u8()?;
+        self.linear_counter = r.read_u8()?;
+        self.linear_reload = r.read_bool()?;
+        self.step = r.read_u8()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,433 @@
+use bitflags::bitflags;
+
+use crate::apu::APU;
+use crate::error::EmulatorError;
+use crate::joypad::Joypad;
+use crate::mapper::{self, SharedMapper};
+use crate::mem::Mem;
+use crate::ppu::PPU;
+use crate::ppu::frame::Frame;
+use crate::rom::Rom;
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+
+#[cfg(test)]
+mod tests;
+
+const RAM_START: u16 = 0x0000;
+const RAM_END: u16 = 0x1FFF;
+const RAM_MASK: u16 = 0b0000_0111_1111_1111;
+
+const PPU_START: u16 = 0x2000;
+const PPU_END: u16 = 0x3FFF;
+const PPU_MASK: u16 = 0b0010_0000_0000_0111;
+
+const CART_START: u16 = 0x4020;
+const ROM_START: u16 = 0x8000;
+const ROM_END: u16 = 0xFFFF;
+
+/*
+ * Everything that can hold the CPU's IRQ line low. The line is level
+ * triggered, it stays asserted until every source has been acknowledged in
+ * its own way, and the CPU keeps taking the interrupt for as long as it is and
+ * the I flag is clear.
+ */
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct IrqSource: u8 {
+        const FRAME_COUNTER = 0b0000_0001;
+        const DMC           = 0b0000_0010;
+        const MAPPER        = 0b0000_0100;
+    }
+}
+
+// the last thing the CPU did on the bus, which decides how a DMC fetch stalls it
+#[derive(Clone, Copy)]
+enum Access {
+    Read(u16),
+    Write,
+}
+
+pub struct Bus {
+    cpu_vram: [u8; 2048],
+    cycles: usize,
+    mapper: SharedMapper,
+    ppu: PPU,
+    apu: APU,
+    frame_complete: bool,
+    oam_dma_page: Option<u8>,
+    oam_dma_active: bool,
+    last_access: Access,
+    open_bus: u8,
+    error: Option<EmulatorError>,
+    pub joypad1: Joypad,
+    pub joypad2: Joypad,
+    pub allow_rom_writes: bool,
+}
+
+impl Mem for Bus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            RAM_START ..= RAM_END => {
+                let mask_apply = addr & RAM_MASK;
+                self.cpu_vram[mask_apply as usize]
+            },
+
+            // there's nothing to drive the data bus
+            0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => {
+                self.fault(EmulatorError::WriteOnlyRead { addr });
+                self.open_bus
+            },
+
+            0x2002 => self.ppu.read_status(),
+
+            0x2004 => self.ppu.read_oam_data(),
+
+            0x2007 => self.ppu.read_data(),
+
+            0x2008 ..= PPU_END => {
+                let mask_apply = addr & PPU_MASK;
+                self.mem_read(mask_apply)
+            },
+
+            0x4015 => self.apu.read_status(),
+
+            // only the low bits are driven by the controllers, the rest is
+            // whatever was last on the data bus
+            0x4016 => (self.open_bus & 0xE0) | self.joypad1.read(),
+            0x4017 => (self.open_bus & 0xE0) | self.joypad2.read(),
+
+            // the APU's write-only registers and the unused test registers,
+            // nothing answers so it's whatever was last on the bus
+            0x4000 ..= 0x401F => self.open_bus,
+
+            CART_START ..= ROM_END => {
+                self.mapper.borrow_mut().cpu_read(addr).unwrap_or(self.open_bus)
+            },
+        };
+
+        self.open_bus = data;
+        self.last_access = Access::Read(addr);
+        data
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        self.last_access = Access::Write;
+
+        match addr {
+            RAM_START ..= RAM_END => {
+                let mask_apply = addr & RAM_MASK;
+                self.cpu_vram[mask_apply as usize] = data;
+            },
+
+            0x2000 => self.ppu.write_to_ppu_ctrl(data),
+            0x2001 => self.ppu.write_to_ppu_mask(data),
+
+            0x2003 => self.ppu.oam_addr = data,
+            0x2004 => self.ppu.write_oam_data(data),
+
+            0x2005 => self.ppu.write_to_ppu_scroll(data),
+            0x2006 => self.ppu.write_to_ppu_addr(data),
+            0x2007 => self.ppu.write_data(data),
+
+            0x2008 ..= PPU_END => {
+                let mask_apply = addr & PPU_MASK;
+                self.mem_write(mask_apply, data);
+            },
+
+            0x4000 ..= 0x4013 | 0x4015 => self.apu.write_register(addr, data),
+
+            // the transfer starts once the writing instruction has finished
+            0x4014 => self.oam_dma_page = Some(data),
+
+            0x4016 => {
+                self.joypad1.write(data);
+                self.joypad2.write(data);
+            },
+
+            0x4017 => self.apu.write_register(addr, data),
+
+            ROM_START ..= ROM_END if self.allow_rom_writes => {
+                if !self.mapper.borrow_mut().patch_prg_rom(addr, data) {
+                    self.fault(EmulatorError::RomWrite { addr, data });
+                }
+            },
+
+            ROM_START ..= ROM_END if !self.mapper.borrow().has_prg_registers() => {
+                self.fault(EmulatorError::RomWrite { addr, data });
+            },
+
+            CART_START ..= ROM_END => self.mapper.borrow_mut().cpu_write(addr, data),
+
+            _ => {
+                println!("Ignoring mem write at {}", addr);
+            },
+        }
+    }
+}
+
+impl Bus {
+    pub fn new(rom: Rom) -> Result<Self, String> {
+        let mapper = mapper::from_rom(rom)?;
+        let ppu = PPU::new(mapper.clone());
+
+        Ok(Bus {
+            cpu_vram: [0; 2048],
+            cycles: 0,
+            mapper,
+            ppu,
+            apu: APU::new(),
+            frame_complete: false,
+            oam_dma_page: None,
+            oam_dma_active: false,
+            last_access: Access::Read(0),
+            open_bus: 0,
+            error: None,
+            joypad1: Joypad::new(),
+            joypad2: Joypad::new(),
+            allow_rom_writes: false,
+        })
+    }
+
+    // what the reset button reaches on the bus, the cartridge and the
+    // controllers don't see it
+    pub fn reset(&mut self) {
+        self.ppu.reset();
+        self.apu.reset();
+    }
+
+    pub fn poll_nmi_status(&mut self) -> bool {
+        self.ppu.poll_nmi_interrupt()
+    }
+
+    // whether there's an NMI waiting, without taking it
+    pub fn nmi_pending(&self) -> bool {
+        self.ppu.nmi_pending()
+    }
+
+    pub fn poll_irq_status(&self) -> bool {
+        !self.irq_line().is_empty()
+    }
+
+    // which sources are asserting IRQ right now
+    pub fn irq_line(&self) -> IrqSource {
+        let mut line = IrqSource::empty();
+        line.set(IrqSource::FRAME_COUNTER, self.apu.frame_irq_pending());
+        line.set(IrqSource::DMC, self.apu.dmc_irq_pending());
+        line.set(IrqSource::MAPPER, self.mapper.borrow().irq_pending());
+        line
+    }
+
+    // Problems with the program's accesses since the last call, only the
+    // first is kept. The CPU takes these after every step.
+    pub fn take_error(&mut self) -> Option<EmulatorError> {
+        self.error.take()
+    }
+
+    /*
+     * What a read of addr would return, for debuggers and the trace. Nothing
+     * on the bus notices: the PPU, APU and controllers show what they would
+     * put on the bus without acting on the read, the registers that can't be
+     * read give open bus, and the open bus itself is left alone.
+     */
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            RAM_START ..= RAM_END => self.cpu_vram[(addr & RAM_MASK) as usize],
+
+            0x2002 => self.ppu.peek_status(),
+            0x2004 => self.ppu.read_oam_data(),
+            0x2007 => self.ppu.peek_data(),
+            0x2008 ..= PPU_END => self.peek(addr & PPU_MASK),
+            PPU_START ..= PPU_END => self.open_bus,
+
+            0x4015 => self.apu.peek_status(),
+            0x4016 => (self.open_bus & 0xE0) | self.joypad1.peek(),
+            0x4017 => (self.open_bus & 0xE0) | self.joypad2.peek(),
+            0x4000 ..= 0x401F => self.open_bus,
+
+            // none of the boards do anything on a read
+            CART_START ..= ROM_END => {
+                self.mapper.borrow_mut().cpu_read(addr).unwrap_or(self.open_bus)
+            },
+        }
+    }
+
+    pub fn peek_u16(&self, addr: u16) -> u16 {
+        let lo = self.peek(addr);
+        let hi = self.peek(addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    // A read the CPU throws away. The registers still see it, but it isn't
+    // something the program asked for, so it's never reported.
+    pub fn dummy_read(&mut self, addr: u16) {
+        let error = self.error.take();
+        self.mem_read(addr);
+        self.error = error;
+    }
+
+    fn fault(&mut self, error: EmulatorError) {
+        self.error.get_or_insert(error);
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    pub fn poll_frame_complete(&mut self) -> bool {
+        let complete = self.frame_complete;
+        self.frame_complete = false;
+        complete
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.ppu.frame
+    }
+
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.apu.set_sample_rate(rate);
+    }
+
+    pub fn set_rate_adjust(&mut self, adjust: f64) {
+        self.apu.set_rate_adjust(adjust);
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.apu.take_samples()
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.clock();
+
+            if let Some(addr) = self.apu.dmc_dma_request() {
+                self.dmc_dma(addr);
+            }
+        }
+
+        if let Some(page) = self.oam_dma_page.take() {
+            self.oam_dma(page);
+        }
+    }
+
+    // One CPU cycle at a time, so the cartridge sees PPU fetches in step with
+    // the CPU clock
+    fn clock(&mut self) {
+        self.cycles += 1;
+        self.mapper.borrow_mut().cpu_tick(1);
+        if self.ppu.tick(3) {
+            self.frame_complete = true;
+        }
+        self.apu.tick();
+    }
+
+    /*
+     * Fetch a sample byte for the DMC. The CPU is halted on its next read and
+     * repeats that read while it waits, which is how samples end up eating
+     * controller bits and $2007 reads. The repeats land on back to back cycles
+     * and registers with side effects only notice one extra read. The stall is
+     * 4 cycles, 3 if the CPU was writing and it has to wait for the next read,
+     * and 2 when it lands in the middle of an OAM DMA which already has the
+     * CPU halted.
+     */
+    fn dmc_dma(&mut self, addr: u16) {
+        let stall = match (self.oam_dma_active, self.last_access) {
+            (true, _) => 2,
+            (false, Access::Write) => 3,
+            (false, Access::Read(read)) => {
+                let side_effects = match read {
+                    PPU_START ..= PPU_END => read & 0b111 == 0b111,
+                    0x4016 | 0x4017 => true,
+                    _ => false,
+                };
+                if side_effects {
+                    self.mem_read(read);
+                }
+                4
+            },
+        };
+
+        for _ in 1..stall {
+            self.clock();
+        }
+        let data = self.mem_read(addr);
+        self.clock();
+        self.apu.dmc_fill(data);
+    }
+
+    /*
+     * Copy a page to OAM through $2004, one byte every two cycles while the
+     * CPU is halted. There is one cycle to halt the CPU, plus one more to get
+     * back in step if that landed on an odd cycle, so 513 or 514 in total.
+     */
+    fn oam_dma(&mut self, page: u8) {
+        self.tick(1);
+        if self.cycles % 2 == 1 {
+            self.tick(1);
+        }
+
+        self.oam_dma_active = true;
+        let base = (page as u16) << 8;
+        for offset in 0..=0xFF {
+            let value = self.mem_read(base | offset);
+            self.tick(1);
+            self.ppu.write_oam_data(value);
+            self.tick(1);
+        }
+        self.oam_dma_active = false;
+    }
+}
+
+/*
+ * Everything on the bus goes in with it: the PPU, APU, controllers and the
+ * cartridge. The OAM DMA flag isn't saved, states are only taken between
+ * instructions and it's never set there.
+ */
+impl Snapshot for Bus {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.cpu_vram);
+        w.write_u64(self.cycles as u64);
+        w.write_bool(self.frame_complete);
+        w.write_bool(self.oam_dma_page.is_some());
+        w.write_u8(self.oam_dma_page.unwrap_or(0));
+        match self.last_access {
+            Access::Read(addr) => {
+                w.write_u8(0);
+                w.write_u16(addr);
+            },
+            Access::Write => {
+                w.write_u8(1);
+                w.write_u16(0);
+            },
+        }
+        w.write_u8(self.open_bus);
+        self.joypad1.save_state(w);
+        self.joypad2.save_state(w);
+
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.mapper.borrow().save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.cpu_vram)?;
+        self.cycles = r.read_u64()? as usize;
+        self.frame_complete = r.read_bool()?;
+        let dma = r.read_bool()?;
+        let page = r.read_u8()?;
+        self.oam_dma_page = dma.then_some(page);
+        let access = r.read_u8()?;
+        let addr = r.read_u16()?;
+        self.last_access = match access {
+            0 => Access::Read(addr),
+            _ => Access::Write,
+        };
+        self.open_bus = r.read_u8()?;
+        self.joypad1.load_state(r)?;
+        self.joypad2.load_state(r)?;
+
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.mapper.borrow_mut().load_state(r)
+    }
+}
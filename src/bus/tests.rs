@@ -0,0 +1,326 @@
+use super::*;
+use crate::joypad::JoypadButton;
+use crate::rom::tests::{nop_rom, test_rom};
+
+fn fill_page(bus: &mut Bus, page: u16) {
+    for i in 0..256u16 {
+        bus.mem_write((page << 8) | i, i as u8);
+    }
+}
+
+#[test]
+fn test_oam_dma_copies_page() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    fill_page(&mut bus, 0x02);
+
+    bus.mem_write(0x4014, 0x02);
+    bus.tick(4);
+
+    for i in 0..256 {
+        assert_eq!(bus.ppu.oam_data[i], i as u8);
+    }
+}
+
+#[test]
+fn test_oam_dma_starts_at_oam_addr() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    fill_page(&mut bus, 0x03);
+
+    bus.mem_write(0x2003, 0x10);
+    bus.mem_write(0x4014, 0x03);
+    bus.tick(4);
+
+    assert_eq!(bus.ppu.oam_data[0x10], 0x00);
+    assert_eq!(bus.ppu.oam_data[0x0F], 0xFF);
+    assert_eq!(bus.ppu.oam_addr, 0x10);
+}
+
+#[test]
+fn test_oam_dma_reads_through_bus() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x4014, 0x80);
+    bus.tick(4);
+
+    // test_rom() PRG is filled with 1s
+    assert!(bus.ppu.oam_data.iter().all(|&b| b == 1));
+}
+
+#[test]
+fn test_oam_dma_cycles() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x4014, 0x02);
+    bus.tick(4);
+    assert_eq!(bus.cycles, 4 + 514);
+
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x4014, 0x02);
+    bus.tick(3);
+    assert_eq!(bus.cycles, 3 + 513);
+}
+
+#[test]
+fn test_oam_dma_clocks_ppu() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    let mut frames = 0;
+
+    // 60 transfers are about one frame's worth of PPU time on their own
+    for _ in 0..60 {
+        bus.mem_write(0x4014, 0x02);
+        bus.tick(1);
+        if bus.poll_frame_complete() {
+            frames += 1;
+        }
+    }
+
+    assert_eq!(frames, 1);
+}
+
+#[test]
+fn test_joypad_reads() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.joypad1.set_button_pressed_status(JoypadButton::BUTTON_B, true);
+    bus.joypad2.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+
+    assert_eq!(bus.mem_read(0x4016) & 1, 0);
+    assert_eq!(bus.mem_read(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4017) & 1, 1);
+    assert_eq!(bus.mem_read(0x4017) & 1, 0);
+}
+
+#[test]
+fn test_joypad_open_bus_bits() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+
+    // LDA $4016 leaves the high byte of the address on the bus
+    bus.mem_write(0x0000, 0x40);
+    bus.mem_read(0x0000);
+    assert_eq!(bus.mem_read(0x4016), 0x40);
+
+    bus.mem_write(0x0000, 0xFF);
+    bus.mem_read(0x0000);
+    assert_eq!(bus.mem_read(0x4017), 0xE0);
+}
+
+#[test]
+fn test_apu_registers_read_open_bus() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x0000, 0x5A);
+    bus.mem_read(0x0000);
+
+    assert_eq!(bus.mem_read(0x4000), 0x5A);
+    assert_eq!(bus.mem_read(0x4013), 0x5A);
+    assert_eq!(bus.mem_read(0x401F), 0x5A);
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_mmc3_counts_rendered_scanlines() {
+    let mut rom = test_rom();
+    rom.mapper = 4;
+    let mut bus = Bus::new(rom).unwrap();
+
+    bus.mem_write(0xC000, 10);
+    bus.mem_write(0xC001, 0);
+    bus.mem_write(0xE001, 0);
+    // background from $0000, sprites from $1000
+    bus.mem_write(0x2000, 0b0000_1000);
+    bus.mem_write(0x2001, 0b0001_1000);
+
+    // the first rise is on the pre-render line and reloads the counter, the
+    // IRQ comes at the end of the 10th visible line
+    let mut cycles = 0;
+    while !bus.poll_irq_status() {
+        bus.tick(1);
+        cycles += 1;
+    }
+
+    assert_eq!(cycles * 3 / 341, 10);
+}
+
+// One byte sample from $C000, with the DMC fetching it as soon as it's enabled
+fn start_dmc(bus: &mut Bus, control: u8) {
+    bus.mem_write(0x4010, control);
+    bus.mem_write(0x4012, 0x00);
+    bus.mem_write(0x4013, 0x00);
+    bus.mem_write(0x4015, 0b0001_0000);
+}
+
+#[test]
+fn test_dmc_dma_stall() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0);
+    bus.tick(1);
+    assert_eq!(bus.cycles, 1 + 3);
+
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0);
+    bus.mem_read(0x0000);
+    bus.tick(1);
+    assert_eq!(bus.cycles, 1 + 4);
+
+    // nothing left to fetch
+    bus.tick(1);
+    assert_eq!(bus.cycles, 1 + 4 + 1);
+}
+
+#[test]
+fn test_dmc_dma_during_oam_dma() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0);
+    bus.mem_write(0x4014, 0x02);
+    bus.tick(1);
+
+    // the DMC fetch goes first, then 514 cycles of OAM DMA
+    assert_eq!(bus.cycles, 1 + 3 + 514);
+}
+
+#[test]
+fn test_dmc_irq() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0b1000_0000);
+    assert_eq!(bus.mem_read(0x4015) & 0b1001_0000, 0b0001_0000);
+
+    bus.tick(1);
+    assert!(bus.poll_irq_status());
+    assert_eq!(bus.mem_read(0x4015) & 0b1001_0000, 0b1000_0000);
+
+    bus.mem_write(0x4015, 0);
+    assert!(!bus.poll_irq_status());
+}
+
+#[test]
+fn test_dmc_loop() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0b1100_0000);
+    bus.tick(1);
+
+    assert!(!bus.poll_irq_status());
+    assert_eq!(bus.mem_read(0x4015) & 0b0001_0000, 0b0001_0000);
+}
+
+#[test]
+fn test_dmc_dma_eats_controller_bit() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.joypad1.set_button_pressed_status(JoypadButton::SELECT, true);
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+    start_dmc(&mut bus, 0);
+
+    // A, then the DMC halts the CPU on the read and B is lost
+    assert_eq!(bus.mem_read(0x4016) & 1, 0);
+    bus.tick(4);
+    assert_eq!(bus.mem_read(0x4016) & 1, 1);
+}
+
+#[test]
+fn test_frame_irq_line() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    for _ in 0..29830 {
+        bus.tick(1);
+    }
+    assert!(bus.poll_irq_status());
+
+    bus.mem_read(0x4015);
+    assert!(!bus.poll_irq_status());
+
+    bus.mem_write(0x4017, 0b0100_0000);
+    for _ in 0..29830 {
+        bus.tick(1);
+    }
+    assert!(!bus.poll_irq_status());
+}
+
+#[test]
+fn test_irq_line() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    start_dmc(&mut bus, 0b1000_0000);
+    for _ in 0..29830 {
+        bus.tick(1);
+    }
+    assert_eq!(bus.irq_line(), IrqSource::FRAME_COUNTER | IrqSource::DMC);
+
+    bus.mem_read(0x4015);
+    assert_eq!(bus.irq_line(), IrqSource::DMC);
+    bus.mem_write(0x4015, 0);
+    assert_eq!(bus.irq_line(), IrqSource::empty());
+}
+
+#[test]
+fn test_rom_write_error() {
+    let mut bus = Bus::new(nop_rom()).unwrap();
+    bus.mem_write(0x8000, 0x42);
+    assert_eq!(bus.take_error(), Some(EmulatorError::RomWrite { addr: 0x8000, data: 0x42 }));
+    assert_eq!(bus.mem_read(0x8000), 0xEA);
+    assert_eq!(bus.take_error(), None);
+
+    // nothing there to write to, but nothing to go wrong either
+    bus.mem_write(0x6000, 0x42);
+    assert_eq!(bus.take_error(), None);
+
+    // a board with registers up there takes it
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x8000, 0x42);
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_write_only_read_error() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(0x0000, 0x5A);
+    assert_eq!(bus.mem_read(0x2000), 0x5A);
+    assert_eq!(bus.mem_read(0x4014), 0x5A);
+
+    // only the first is kept
+    assert_eq!(bus.take_error(), Some(EmulatorError::WriteOnlyRead { addr: 0x2000 }));
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_peek_has_no_side_effects() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+
+    // vblank stays set and the write latch stays where it was
+    bus.ppu.status.set_vblank_status(true);
+    bus.mem_write(0x2006, 0x20);
+    assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+    bus.mem_write(0x2006, 0x00);
+    bus.mem_write(0x2007, 0x55);
+    assert_eq!(bus.mem_read(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.peek(0x2002) & 0x80, 0);
+
+    // v doesn't move and the read buffer isn't refilled
+    bus.mem_write(0x2006, 0x20);
+    bus.mem_write(0x2006, 0x00);
+    bus.mem_read(0x2007);
+    assert_eq!(bus.peek(0x2007), 0x55);
+    assert_eq!(bus.peek(0x2007), 0x55);
+    assert_eq!(bus.mem_read(0x2007), 0x55);
+
+    // the controller doesn't shift
+    bus.joypad1.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+    assert_eq!(bus.peek(0x4016) & 1, 1);
+    assert_eq!(bus.peek(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4016) & 1, 0);
+
+    // the frame IRQ isn't acknowledged
+    for _ in 0..29830 {
+        bus.tick(1);
+    }
+    assert_eq!(bus.peek(0x4015) & 0x40, 0x40);
+    assert!(bus.poll_irq_status());
+
+    // the open bus is left alone, and nothing is the program's fault
+    bus.mem_write(0x0000, 0x5A);
+    bus.mem_read(0x0000);
+    bus.peek(0x8000);
+    assert_eq!(bus.peek(0x2000), 0x5A);
+    assert_eq!(bus.mem_read(0x4000), 0x5A);
+    assert_eq!(bus.take_error(), None);
+}
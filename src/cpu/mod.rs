@@ -2,9 +2,10 @@ use bitflags::bitflags;
 use std::collections::HashMap;
 
 use crate::bus::Bus;
+use crate::error::EmulatorError;
 use crate::mem::Mem;
 use crate::opcode;
-use crate::opcode::CycleBehavior;
+use crate::savestate::{Snapshot, StateReader, StateWriter};
 
 #[cfg(test)]
 mod tests;
@@ -27,20 +28,57 @@ const STACK: u16 = 0x0100;
 const STACK_RESET: u8 = 0xFD;
 const STATUS_RESET: StatusFlags = StatusFlags::from_bits_truncate(0b0010_0100);
 
-enum InstructionResult {
-    Complete,
-    PageCross(bool),
-    BranchFailed,
-    BranchSuccess,
-    BranchSuccessPageCrossed,
+/*
+ * Which 6502 this is. The NES's Ricoh 2A03 is an NMOS 6502 with the decimal
+ * mode cut out, D is still a flag but ADC and SBC ignore it. The WDC 65C02
+ * is the CMOS redesign: it has decimal mode with N and Z set properly from the
+ * result, clears D on interrupts, fixes the indirect JMP and swaps the
+ * undocumented opcodes for instructions of its own, WDC's RMB, SMB, BBR, BBS,
+ * WAI and STP included.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuVariant {
+    Ricoh2A03,
+    NMOS6502,
+    WDC65C02,
 }
 
-impl From<()> for InstructionResult {
-    fn from(_: ()) -> Self {
-        InstructionResult::Complete
+impl CpuVariant {
+    pub fn opcodes(self) -> &'static HashMap<u8, &'static opcode::OpCode> {
+        match self {
+            CpuVariant::WDC65C02 => &opcode::OPCODES_65C02_MAP,
+            _ => &opcode::OPCODES_MAP,
+        }
+    }
+
+    pub fn has_decimal(self) -> bool {
+        self != CpuVariant::Ricoh2A03
+    }
+
+    pub fn is_cmos(self) -> bool {
+        self == CpuVariant::WDC65C02
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interrupt {
+    NMI,
+    IRQ,
+}
+
+/*
+ * What one call to `step` did: either an interrupt sequence, with no opcode,
+ * or an instruction. The cycles include any DMA that stalled the CPU on the
+ * way.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepResult {
+    pub opcode: Option<u8>,
+    pub cycles: usize,
+    pub interrupt: Option<Interrupt>,
+    pub fault: Option<EmulatorError>,
+}
+
 pub struct CPU {
     pub register_a: u8,
     pub register_x: u8,
@@ -49,8 +87,19 @@ pub struct CPU {
     pub status: StatusFlags,
     pub program_counter: u16,
     pub bus: Bus,
-    pub enable_decimal: bool,
+    pub variant: CpuVariant,
     pause: bool,
+    stop: bool,
+    // the HLT that locked the CPU up, which only a reset gets it out of
+    jammed: Option<u8>,
+    // stopped by a WAI until an interrupt comes along
+    waiting: bool,
+    // the first diagnostic the run loops carried on past
+    diagnostic: Option<EmulatorError>,
+    nmi_sampled: bool,
+    irq_sampled: bool,
+    nmi_polled: bool,
+    irq_polled: bool,
 }
 
 #[derive(Debug)]
@@ -66,6 +115,7 @@ pub enum AddressingMode {
     Indirect,
     Indirect_X,
     Indirect_Y,
+    ZeroPage_Indirect,
     Implied,
     None,
 }
@@ -102,8 +152,16 @@ impl CPU {
             status: STATUS_RESET,
             program_counter: 0,
             bus,
-            enable_decimal: false,
+            variant: CpuVariant::Ricoh2A03,
             pause: false,
+            stop: false,
+            jammed: None,
+            waiting: false,
+            diagnostic: None,
+            nmi_sampled: false,
+            irq_sampled: false,
+            nmi_polled: false,
+            irq_polled: false,
         }
     }
 
@@ -111,10 +169,24 @@ impl CPU {
         self.pause = !self.pause;
     }
 
-    pub fn load_and_run(&mut self, program: Vec<u8>) {
-        self.load(program);
-        self.reset();
-        self.run()
+    pub fn is_paused(&self) -> bool {
+        self.pause
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.jammed.is_some()
+    }
+
+    // The first error the run loops didn't stop for since the last call
+    pub fn take_diagnostic(&mut self) -> Option<EmulatorError> {
+        self.diagnostic.take()
+    }
+
+    // Ask the run loops to return before the next instruction, for callbacks
+    // that decide the program is done. If nothing is running the next run
+    // returns straight away.
+    pub fn stop(&mut self) {
+        self.stop = true;
     }
 
     pub fn load(&mut self, program: Vec<u8>) {
@@ -123,7 +195,7 @@ impl CPU {
 
     pub fn load_at(&mut self, program: Vec<u8>, addr: u16) {
         for i in 0..program.len() as u16 {
-            self.mem_write(addr + i, program[i as usize]);
+            self.mem_write(addr.wrapping_add(i), program[i as usize]);
         }
         self.mem_write_u16(0xFFFC, addr);
     }
@@ -134,31 +206,39 @@ impl CPU {
         self.register_y = 0;
         self.stack_pointer = STACK_RESET;
         self.status = STATUS_RESET;
+        self.jammed = None;
+        self.waiting = false;
+        self.nmi_sampled = false;
+        self.irq_sampled = false;
+        self.nmi_polled = false;
+        self.irq_polled = false;
 
         self.program_counter = self.mem_read_u16(0xFFFC);
     }
 
-    pub fn resolve_address(&mut self, mode: &AddressingMode, base: u16) -> (u16, bool) {
-        match mode {
-            AddressingMode::ZeroPage => (self.mem_read(base) as u16, false),
-            AddressingMode::Absolute => (self.mem_read_u16(base), false),
+    // Where an operand is without taking any cycles or disturbing the bus, for
+    // the trace to peek at
+    pub fn resolve_address(&self, mode: &AddressingMode, base: u16) -> Option<(u16, bool)> {
+        let resolved = match mode {
+            AddressingMode::ZeroPage => (self.bus.peek(base) as u16, false),
+            AddressingMode::Absolute => (self.bus.peek_u16(base), false),
             AddressingMode::ZeroPage_X => {
-                let pos = self.mem_read(base);
+                let pos = self.bus.peek(base);
                 let addr = pos.wrapping_add(self.register_x) as u16;
                 (addr, false) 
             },
             AddressingMode::ZeroPage_Y => {
-                let pos = self.mem_read(base);
+                let pos = self.bus.peek(base);
                 let addr = pos.wrapping_add(self.register_y) as u16;
                 (addr, false) 
             },
             AddressingMode::Absolute_X => {
-                let base = self.mem_read_u16(base);
+                let base = self.bus.peek_u16(base);
                 let addr = base.wrapping_add(self.register_x as u16);
                 (addr, page_cross(base, addr))
             },
             AddressingMode::Absolute_Y => {
-                let base = self.mem_read_u16(base);
+                let base = self.bus.peek_u16(base);
                 let addr = base.wrapping_add(self.register_y as u16);
                 (addr, page_cross(base, addr))
             },
@@ -176,63 +256,211 @@ impl CPU {
                 // so for compatibility always ensure the indirect vector is not
                 // at the end of the page.
 
-                let addr = self.mem_read_u16(base);
-                (self.mem_read_u16(addr), false)
+                let addr = self.bus.peek_u16(base);
+                (self.bus.peek_u16(addr), false)
             },
             AddressingMode::Indirect_X => {
-                let base_addr = self.mem_read(base);
+                let base_addr = self.bus.peek(base);
                 let ptr = base_addr.wrapping_add(self.register_x);
-                let lo = self.mem_read(ptr as u16);
-                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
+                let lo = self.bus.peek(ptr as u16);
+                let hi = self.bus.peek(ptr.wrapping_add(1) as u16);
                 ((hi as u16) << 8 | (lo as u16), false)
             },
             AddressingMode::Indirect_Y => {
-                let base_addr = self.mem_read(base);
-                let lo = self.mem_read(base_addr as u16);
-                let hi = self.mem_read(base_addr.wrapping_add(1) as u16);
+                let base_addr = self.bus.peek(base);
+                let lo = self.bus.peek(base_addr as u16);
+                let hi = self.bus.peek(base_addr.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.register_y as u16);
                 (deref, page_cross(deref_base, deref))
             },
+            AddressingMode::ZeroPage_Indirect => {
+                let base_addr = self.bus.peek(base);
+                let lo = self.bus.peek(base_addr as u16);
+                let hi = self.bus.peek(base_addr.wrapping_add(1) as u16);
+                ((hi as u16) << 8 | (lo as u16), false)
+            },
+            // there's no address in memory to go to
+            AddressingMode::Immediate | AddressingMode::Implied | AddressingMode::None => return None,
+        };
+        Some(resolved)
+    }
+
+    /*
+     * One bus cycle: every cycle of an instruction is a read or a write,
+     * and the rest of the console runs for that cycle straight after it. The
+     * `Mem` impl above doesn't take any time, it's for loading programs and
+     * peeking from outside.
+     */
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = self.bus.mem_read(addr);
+        self.end_cycle();
+        data
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.bus.mem_write(addr, data);
+        self.end_cycle();
+    }
+
+    fn dummy_read(&mut self, addr: u16) {
+        self.bus.dummy_read(addr);
+        self.end_cycle();
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.read(addr);
+        let hi = self.read(addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    // The 6502 decides whether to take an interrupt on the cycle before an
+    // instruction's last, so the lines are sampled every cycle and it's what
+    // they were one cycle back that counts.
+    fn end_cycle(&mut self) {
+        self.bus.tick(1);
+
+        self.nmi_polled = self.nmi_sampled;
+        self.irq_polled = self.irq_sampled;
+        self.nmi_sampled = self.bus.nmi_pending();
+        self.irq_sampled = self.bus.poll_irq_status()
+            && !self.status.contains(StatusFlags::INTERRUPT_DISABLE);
+    }
+
+    /*
+     * The operand's address, reading the operand bytes and any pointer on
+     * the way. Indexing reads from the address before its high byte is fixed
+     * up: a read that didn't cross a page is done by then, anything else
+     * throws that read away and goes again. Stores and read-modify-writes
+     * can't take back a write, so they always take the extra cycle.
+     */
+    fn operand_address(&mut self, mode: &AddressingMode, always_fix: bool) -> u16 {
+        let pc = self.program_counter;
+        match mode {
+            AddressingMode::Immediate => pc,
+            AddressingMode::ZeroPage => self.read(pc) as u16,
+            AddressingMode::Absolute => self.read_u16(pc),
+            AddressingMode::ZeroPage_X => {
+                let pos = self.read(pc);
+                self.dummy_read(pos as u16);
+                pos.wrapping_add(self.register_x) as u16
+            },
+            AddressingMode::ZeroPage_Y => {
+                let pos = self.read(pc);
+                self.dummy_read(pos as u16);
+                pos.wrapping_add(self.register_y) as u16
+            },
+            AddressingMode::Absolute_X => {
+                let base = self.read_u16(pc);
+                self.index(base, self.register_x, always_fix)
+            },
+            AddressingMode::Absolute_Y => {
+                let base = self.read_u16(pc);
+                self.index(base, self.register_y, always_fix)
+            },
+            AddressingMode::Indirect_X => {
+                let base_addr = self.read(pc);
+                self.dummy_read(base_addr as u16);
+                let ptr = base_addr.wrapping_add(self.register_x);
+                let lo = self.read(ptr as u16);
+                let hi = self.read(ptr.wrapping_add(1) as u16);
+                u16::from_le_bytes([lo, hi])
+            },
+            AddressingMode::Indirect_Y => {
+                let base_addr = self.read(pc);
+                let lo = self.read(base_addr as u16);
+                let hi = self.read(base_addr.wrapping_add(1) as u16);
+                self.index(u16::from_le_bytes([lo, hi]), self.register_y, always_fix)
+            },
+            AddressingMode::ZeroPage_Indirect => {
+                let base_addr = self.read(pc);
+                let lo = self.read(base_addr as u16);
+                let hi = self.read(base_addr.wrapping_add(1) as u16);
+                u16::from_le_bytes([lo, hi])
+            },
             _ => {
                 panic!("mode {:?} is not supported", mode);
             },
         }
     }
 
-    fn get_operand_address(&mut self, mode: &AddressingMode) -> (u16, bool) {
-        match mode {
-            AddressingMode::Immediate => (self.program_counter, false),
-            _ => self.resolve_address(mode, self.program_counter),
+    fn index(&mut self, base: u16, index: u8, always_fix: bool) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        if always_fix || page_cross(base, addr) {
+            self.dummy_read((base & 0xFF00) | (addr & 0x00FF));
+        }
+        addr
+    }
+
+    fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
+        self.operand_address(mode, false)
+    }
+
+    fn get_write_address(&mut self, mode: &AddressingMode) -> u16 {
+        self.operand_address(mode, true)
+    }
+
+    fn read_operand(&mut self, mode: &AddressingMode) -> u8 {
+        let addr = self.get_operand_address(mode);
+        self.read(addr)
+    }
+
+    /*
+     * Read-modify-write. The NMOS 6502 writes the old value back while it
+     * works out the new one, so a register with side effects sees both
+     * writes. The 65C02 reads it again instead.
+     */
+    fn modify<F>(&mut self, mode: &AddressingMode, op: F) -> u8 where F: FnOnce(&mut CPU, u8) -> u8 {
+        let addr = self.get_write_address(mode);
+        let value = self.read(addr);
+
+        if self.variant.is_cmos() {
+            self.dummy_read(addr);
+        } else {
+            self.write(addr, value);
         }
+
+        let result = op(self, value);
+        self.write(addr, result);
+        result
     }
 
-    fn adc(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let mem_value = self.mem_read(addr);
+    fn adc(&mut self, mode: &AddressingMode) {
+        let mem_value = self.read_operand(mode);
+        self.adc_value(mem_value);
+        self.decimal_cycle();
+    }
 
-        if self.enable_decimal && self.status.contains(StatusFlags::DECIMAL_MODE) {
+    fn adc_value(&mut self, mem_value: u8) {
+        if self.decimal_mode() {
             self.bcd_add(mem_value);
         } else {
             self.binary_add(mem_value);
+            self.update_zero_and_negative_flags(self.register_a);
         }
+    }
 
-        self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
+    fn sbc(&mut self, mode: &AddressingMode) {
+        let mem_value = self.read_operand(mode);
+        self.sbc_value(mem_value);
+        self.decimal_cycle();
     }
 
-    fn sbc(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let mem_value = self.mem_read(addr);
+    // the 65C02 spends a cycle more on decimal ADC and SBC, to get the N and
+    // Z flags right
+    fn decimal_cycle(&mut self) {
+        if self.variant.is_cmos() && self.decimal_mode() {
+            self.dummy_read(self.program_counter);
+        }
+    }
 
-        if self.enable_decimal && self.status.contains(StatusFlags::DECIMAL_MODE) {
+    fn sbc_value(&mut self, mem_value: u8) {
+        if self.decimal_mode() {
             self.bcd_sub(mem_value);
         } else {
             self.binary_add(!mem_value);
+            self.update_zero_and_negative_flags(self.register_a);
         }
-
-        self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
     }
 
     fn binary_add(&mut self, arg: u8) {
@@ -245,6 +473,10 @@ impl CPU {
         self.register_a = tmp;
     }
 
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal() && self.status.contains(StatusFlags::DECIMAL_MODE)
+    }
+
     fn bcd_add(&mut self, arg: u8) {
         let carry_bit = StatusFlags::CARRY.bits() & self.status.bits();
         // abandon hope all ye who enter here
@@ -261,120 +493,148 @@ impl CPU {
         }
 
         // add in high nibbles
-        tmp += (a & 0xF0).wrapping_add(v & 0xF0);
+        tmp += (a & 0xF0) + (v & 0xF0);
         
         // overflow is calculated before the upper nibble is corrected
         let o = (!(a ^ v) & (a ^ tmp)) & 0x80 != 0;
         self.status.set(StatusFlags::OVERFLOW, o);
+        let negative = tmp & 0x80 != 0;
 
         // correct high nibble if out of BDC range
-        if tmp > 0x90 {
+        if tmp > 0x9F {
             tmp += 0x60;
         }
 
-        self.status.set(StatusFlags::CARRY, tmp > 99);
+        self.status.set(StatusFlags::CARRY, tmp > 0xFF);
         self.register_a = (tmp & 0xFF) as u8;
+
+        // the NMOS chip takes N from before the high nibble is corrected and Z
+        // from the plain binary sum, the 65C02 takes both from the result
+        if self.variant.is_cmos() {
+            self.update_zero_and_negative_flags(self.register_a);
+        } else {
+            self.status.set(StatusFlags::ZERO, (a + v + carry_bit as u16) & 0xFF == 0);
+            self.status.set(StatusFlags::NEGATIVE, negative);
+        }
     }
 
     fn bcd_sub(&mut self, arg: u8) {
-        let carry_bit = self.status.contains(StatusFlags::CARRY) as u16;
-        let n_carry_bit = !carry_bit & 0x01;
+        let borrow = !self.status.contains(StatusFlags::CARRY) as i16;
+        let a = self.register_a as i16;
+        let v = arg as i16;
 
-        let a = self.register_a as u16;
-        let v = arg as u16;
+        // carry and overflow come out the same as a binary subtract, and on
+        // the NMOS chip so do N and Z
+        self.binary_add(!arg);
+        let binary = self.register_a;
 
         // calculate lower nibble
-        let mut lo = (a & 0x0F).wrapping_sub(v & 0x0F).wrapping_sub(n_carry_bit);
+        let mut lo = (a & 0x0F) - (v & 0x0F) - borrow;
 
-        // correct lower nibble if outside bcd range1
-        if lo & 0x10 == 0x10 {
-            lo -= 0x06;
-        }
+        let result = if self.variant.is_cmos() {
+            let mut tmp = a - v - borrow;
+            if tmp < 0 {
+                tmp -= 0x60;
+            }
+            if lo < 0 {
+                tmp -= 0x06;
+            }
+            tmp
+        } else {
+            // correct lower nibble if it borrowed
+            if lo < 0 {
+                lo = ((lo - 0x06) & 0x0F) - 0x10;
+            }
+            let mut tmp = (a & 0xF0) - (v & 0xF0) + lo;
+            if tmp < 0 {
+                tmp -= 0x60;
+            }
+            tmp
+        };
 
-        let mut hi = (a >> 4).wrapping_sub(v >> 4).wrapping_sub((lo & 0x10 == 0x10) as u16);
+        self.register_a = (result & 0xFF) as u8;
 
-        if hi & 0x10 == 0x10 {
-            hi -= 0x06;
+        if self.variant.is_cmos() {
+            self.update_zero_and_negative_flags(self.register_a);
+        } else {
+            self.update_zero_and_negative_flags(binary);
         }
-
-        self.status.set(StatusFlags::CARRY, a.wrapping_sub(v).wrapping_sub(n_carry_bit) & 0x0F00 != 0);
-        self.status.set(StatusFlags::OVERFLOW, (a.wrapping_sub(v).wrapping_sub(n_carry_bit) ^ v) & 0x80 == 0x80 && (a ^ v) & 0x80 == 0x80);
-
-        self.register_a = ((hi << 4) | (lo & 0xF)) as u8;
     }
 
-    fn and(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-
-        self.register_a &= self.mem_read(addr);
+    fn and(&mut self, mode: &AddressingMode) {
+        self.register_a &= self.read_operand(mode);
         self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
     }
 
-    fn asl(&mut self, mode: &AddressingMode) -> InstructionResult {
+    fn asl(&mut self, mode: &AddressingMode) {
         match mode {
-            AddressingMode::None => {
-                let carry = 0b1000_0000 & self.register_a == 0b1000_0000;
-                self.register_a <<= 1;
-                self.update_zero_and_negative_flags(self.register_a);
-                self.status.set(StatusFlags::CARRY, carry);
-            },
-
+            AddressingMode::None => self.register_a = self.asl_value(self.register_a),
             _ => {
-                let (addr, _) = self.get_operand_address(mode);
-                let value = self.mem_read(addr);
-                let carry = 0b1000_0000 & value == 0b1000_0000;
-                self.mem_write(addr, value << 1);
-                let mem_read = self.mem_read(addr);
-                self.update_zero_and_negative_flags(mem_read);
-                self.status.set(StatusFlags::CARRY, carry);
+                self.modify(mode, CPU::asl_value);
             },
         }
-        InstructionResult::Complete
+    }
+
+    fn asl_value(&mut self, value: u8) -> u8 {
+        let carry = 0b1000_0000 & value == 0b1000_0000;
+        let result = value << 1;
+        self.update_zero_and_negative_flags(result);
+        self.status.set(StatusFlags::CARRY, carry);
+        result
     }
 
     fn lsr(&mut self, mode: &AddressingMode) {
         match mode {
-            AddressingMode::None => {
-                let carry = 1 & self.register_a == 1;
-                self.register_a >>= 1;
-                self.update_zero_and_negative_flags(self.register_a);
-                self.status.set(StatusFlags::CARRY, carry);
-            },
-
+            AddressingMode::None => self.register_a = self.lsr_value(self.register_a),
             _ => {
-                let (addr, _) = self.get_operand_address(mode);
-                let value = self.mem_read(addr);
-                let carry = 1 & value == 1;
-                self.mem_write(addr, value >> 1);
-                let mem_read = self.mem_read(addr);
-                self.update_zero_and_negative_flags(mem_read);
-                self.status.set(StatusFlags::CARRY, carry);
+                self.modify(mode, CPU::lsr_value);
             },
         }
     }
 
-    fn branch(&mut self, condition: bool) -> InstructionResult {
+    fn lsr_value(&mut self, value: u8) -> u8 {
+        let carry = 1 & value == 1;
+        let result = value >> 1;
+        self.update_zero_and_negative_flags(result);
+        self.status.set(StatusFlags::CARRY, carry);
+        result
+    }
+
+    fn branch(&mut self, condition: bool) {
+        let jump: i8 = self.read(self.program_counter) as i8;
+
         if condition {
-            let jump: i8 = self.mem_read(self.program_counter) as i8;
-            let jump_addr = self.program_counter
-                .wrapping_add(1)
-                .wrapping_add(jump as u16);
+            let next = self.program_counter.wrapping_add(1);
+            let jump_addr = next.wrapping_add(jump as u16);
+
+            // a cycle to add the offset, and one more to fix the high byte if
+            // that crossed a page
+            self.dummy_read(next);
+            if page_cross(next, jump_addr) {
+                self.dummy_read((next & 0xFF00) | (jump_addr & 0x00FF));
+            }
 
-            let page_crossed = page_cross(self.program_counter, jump_addr);
             self.program_counter = jump_addr;
+        }
+    }
 
-            if page_crossed {
-                return InstructionResult::BranchSuccessPageCrossed;
-            }
-            return InstructionResult::BranchSuccess;
+    // BBR and BBS test a zero page byte, reading it twice like the 65C02's
+    // read-modify-writes, before branching past the offset like any branch
+    fn branch_on_bit(&mut self, bit: u8, set: bool) {
+        let addr = self.read(self.program_counter) as u16;
+        let value = self.read(addr);
+        self.dummy_read(addr);
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        let condition = (value >> bit) & 1 == set as u8;
+        self.branch(condition);
+        if !condition {
+            self.program_counter = self.program_counter.wrapping_add(1);
         }
-        InstructionResult::BranchFailed
     }
 
-    fn bit(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, _) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn bit(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
 
         let b6 = value & 0b0100_0000 == 0b0100_0000;
         let b7 = value & 0b1000_0000 == 0b1000_0000;
@@ -382,23 +642,28 @@ impl CPU {
         let zero = value & self.register_a == 0;
 
         self.status.set(StatusFlags::ZERO, zero);
-        self.status.set(StatusFlags::OVERFLOW, b6);
-        self.status.set(StatusFlags::NEGATIVE, b7);
-        InstructionResult::Complete
+
+        // the 65C02's BIT #imm only tests, N and V are left alone
+        if !matches!(mode, AddressingMode::Immediate) {
+            self.status.set(StatusFlags::OVERFLOW, b6);
+            self.status.set(StatusFlags::NEGATIVE, b7);
+        }
+    }
+
+    fn compare(&mut self, register: u8, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.compare_value(register, value);
     }
 
-    fn compare(&mut self, register: u8, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn compare_value(&mut self, register: u8, value: u8) {
         let result = register.wrapping_sub(value); 
         self.status.set(StatusFlags::CARRY, register >= value);
         self.status.set(StatusFlags::ZERO, value == register);
         self.status.set(StatusFlags::NEGATIVE, result & 0b1000_0000 == 0b1000_0000);
-        InstructionResult::PageCross(page_crossed)
     }
 
-    fn cmp(&mut self, mode: &AddressingMode) -> InstructionResult {
-        self.compare(self.register_a, mode)
+    fn cmp(&mut self, mode: &AddressingMode) {
+        self.compare(self.register_a, mode);
     }
 
     fn cpx(&mut self, mode: &AddressingMode) {
@@ -410,11 +675,11 @@ impl CPU {
     }
 
     fn dec(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let value = self.mem_read(addr).wrapping_sub(1);
-
-        self.mem_write(addr, value);
-        self.update_zero_and_negative_flags(value);
+        self.modify(mode, |cpu, value| {
+            let value = value.wrapping_sub(1);
+            cpu.update_zero_and_negative_flags(value);
+            value
+        });
     }
 
     fn dex(&mut self) {
@@ -427,25 +692,33 @@ impl CPU {
         self.update_zero_and_negative_flags(self.register_y);
     }
     
-    fn eor(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn eor(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
 
         self.register_a = self.register_a ^ value;
         self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
     }
 
+    // The return address goes on the stack between reading the two halves of
+    // the target, with a cycle before that where the stack is only read
     fn jsr(&mut self) {
-       self.stack_push_u16(self.program_counter + 2 - 1);
-       self.program_counter = self.mem_read_u16(self.program_counter);
+       let lo = self.read(self.program_counter);
+       self.dummy_read(STACK + self.stack_pointer as u16);
+       self.stack_push_u16(self.program_counter.wrapping_add(1));
+       let hi = self.read(self.program_counter.wrapping_add(1));
+       self.program_counter = u16::from_le_bytes([lo, hi]);
     }
 
     fn rts(&mut self) {
-        self.program_counter = self.stack_pop_u16() + 1;
+        self.stack_increment_cycle();
+        let addr = self.stack_pop_u16();
+        // and one more to step past the JSR's last byte
+        self.dummy_read(addr);
+        self.program_counter = addr.wrapping_add(1);
     }
 
     fn rti(&mut self) {
+        self.stack_increment_cycle();
         self.status = StatusFlags::from_bits_truncate(self.stack_pop());
         self.status.remove(StatusFlags::BREAK);
         self.status.insert(StatusFlags::BREAK2);
@@ -454,11 +727,11 @@ impl CPU {
     }
 
     fn inc(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let value = self.mem_read(addr).wrapping_add(1);
-
-        self.mem_write(addr, value);
-        self.update_zero_and_negative_flags(value);
+        self.modify(mode, |cpu, value| {
+            let value = value.wrapping_add(1);
+            cpu.update_zero_and_negative_flags(value);
+            value
+        });
     }
 
     fn inx(&mut self) {
@@ -471,111 +744,83 @@ impl CPU {
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    fn lda(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn lda(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
 
         self.register_a = value;
         self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
     }
 
-    fn ldx(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn ldx(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
 
         self.register_x = value;
         self.update_zero_and_negative_flags(self.register_x);
-        InstructionResult::PageCross(page_crossed)
     }
 
-    fn ldy(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed)= self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn ldy(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
 
         self.register_y = value;
         self.update_zero_and_negative_flags(self.register_y);
-        InstructionResult::PageCross(page_crossed)
     }
 
-    fn ora(&mut self, mode: &AddressingMode) -> InstructionResult {
-        let (addr, page_crossed) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    fn ora(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.ora_value(value);
+    }
 
+    fn ora_value(&mut self, value: u8) {
         self.register_a = self.register_a | value;
         self.update_zero_and_negative_flags(self.register_a);
-        InstructionResult::PageCross(page_crossed)
     }
 
     fn rol(&mut self, mode: &AddressingMode) {
-        let old_carry: bool = self.status.contains(StatusFlags::CARRY);
-        let carry: bool;
-
         match mode {
-            AddressingMode::None => {
-                carry = self.register_a & 0b1000_0000 == 0b1000_0000;
-
-                self.register_a <<= 1;
-
-                if old_carry {
-                    self.register_a |= 1;
-                }
-                self.update_zero_and_negative_flags(self.register_a);
-            },
-
+            AddressingMode::None => self.register_a = self.rol_value(self.register_a),
             _ => {
-                let (addr, _) = self.get_operand_address(mode);
-                let mut value = self.mem_read(addr);
-                carry = value & 0b1000_0000 == 0b1000_0000;
+                self.modify(mode, CPU::rol_value);
+            },
+        }
+    }
 
-                value <<= 1;
+    fn rol_value(&mut self, mut value: u8) -> u8 {
+        let old_carry: bool = self.status.contains(StatusFlags::CARRY);
+        let carry = value & 0b1000_0000 == 0b1000_0000;
 
-                if old_carry {
-                    value |= 1;
-                }
+        value <<= 1;
 
-                self.mem_write(addr, value);
-                self.update_zero_and_negative_flags(value);
-            },
+        if old_carry {
+            value |= 1;
         }
 
+        self.update_zero_and_negative_flags(value);
         self.status.set(StatusFlags::CARRY, carry);
+        value
     }
 
     fn ror(&mut self, mode: &AddressingMode) {
-        let old_carry: bool = self.status.contains(StatusFlags::CARRY);
-        let carry: bool;
-
         match mode {
-            AddressingMode::None => {
-                carry = self.register_a & 1 == 1;
-
-                self.register_a >>= 1;
-                
-                if old_carry {
-                    self.register_a |= 0b1000_0000;
-                }
-
-                self.update_zero_and_negative_flags(self.register_a);
+            AddressingMode::None => self.register_a = self.ror_value(self.register_a),
+            _ => {
+                self.modify(mode, CPU::ror_value);
             },
+        }
+    }
 
-            _ => {
-                let (addr, _) = self.get_operand_address(mode);
-                let mut value = self.mem_read(addr);
-                carry = value & 1 == 1;
+    fn ror_value(&mut self, mut value: u8) -> u8 {
+        let old_carry: bool = self.status.contains(StatusFlags::CARRY);
+        let carry = value & 1 == 1;
 
-                value >>= 1;
+        value >>= 1;
 
-                if old_carry {
-                    value |= 0b1000_0000;
-                }
-                
-                self.mem_write(addr, value);
-                self.update_zero_and_negative_flags(value);
-            }
+        if old_carry {
+            value |= 0b1000_0000;
         }
 
+        self.update_zero_and_negative_flags(value);
         self.status.set(StatusFlags::CARRY, carry);
+        value
     }
 
     fn tax(&mut self) {
@@ -584,18 +829,39 @@ impl CPU {
     }
 
     fn sta(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_a);
+        let addr = self.get_write_address(mode);
+        self.write(addr, self.register_a);
     }
 
     fn sty(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_y);
+        let addr = self.get_write_address(mode);
+        self.write(addr, self.register_y);
     }
 
     fn stx(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_x);
+        let addr = self.get_write_address(mode);
+        self.write(addr, self.register_x);
+    }
+
+    fn stz(&mut self, mode: &AddressingMode) {
+        let addr = self.get_write_address(mode);
+        self.write(addr, 0);
+    }
+
+    // TRB and TSB clear or set the accumulator's bits in memory, with Z from
+    // the same test BIT does
+    fn trb(&mut self, mode: &AddressingMode) {
+        self.modify(mode, |cpu, value| {
+            cpu.status.set(StatusFlags::ZERO, value & cpu.register_a == 0);
+            value & !cpu.register_a
+        });
+    }
+
+    fn tsb(&mut self, mode: &AddressingMode) {
+        self.modify(mode, |cpu, value| {
+            cpu.status.set(StatusFlags::ZERO, value & cpu.register_a == 0);
+            value | cpu.register_a
+        });
     }
 
     fn tay(&mut self) {
@@ -623,7 +889,7 @@ impl CPU {
     }
 
     fn stack_push(&mut self, data: u8) {
-        self.mem_write(STACK + self.stack_pointer as u16, data);
+        self.write(STACK + self.stack_pointer as u16, data);
         self.stack_pointer = self.stack_pointer.wrapping_sub(1);
     }
 
@@ -636,7 +902,7 @@ impl CPU {
 
     fn stack_pop(&mut self) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
-        self.mem_read(STACK.wrapping_add(self.stack_pointer as u16))
+        self.read(STACK.wrapping_add(self.stack_pointer as u16))
     }
 
     fn stack_pop_u16(&mut self) -> u16 {
@@ -646,6 +912,12 @@ impl CPU {
         u16::from_le_bytes([lo, hi])
     }
 
+    // pulling takes a cycle to move the stack pointer first, which reads
+    // whatever it points at now
+    fn stack_increment_cycle(&mut self) {
+        self.dummy_read(STACK + self.stack_pointer as u16);
+    }
+
     fn php(&mut self) {
         let mut flags = self.status.clone();
         flags.insert(StatusFlags::BREAK);
@@ -654,11 +926,25 @@ impl CPU {
     }
 
     fn pla(&mut self) {
+        self.stack_increment_cycle();
         self.register_a = self.stack_pop();
         self.update_zero_and_negative_flags(self.register_a);
     }
 
+    fn plx(&mut self) {
+        self.stack_increment_cycle();
+        self.register_x = self.stack_pop();
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    fn ply(&mut self) {
+        self.stack_increment_cycle();
+        self.register_y = self.stack_pop();
+        self.update_zero_and_negative_flags(self.register_y);
+    }
+
     fn plp(&mut self) {
+        self.stack_increment_cycle();
         self.status = StatusFlags::from_bits_truncate(self.stack_pop());
         self.status.remove(StatusFlags::BREAK);
         self.status.insert(StatusFlags::BREAK2);
@@ -666,17 +952,19 @@ impl CPU {
 
     fn jmp_indirect(&mut self) {
         // JMP indirect
-        let mem_addr = self.mem_read_u16(self.program_counter);
+        let mem_addr = self.read_u16(self.program_counter);
         // 6502 bug mode with with page boundary:
         // if address $3000 contains $40, $30FF contains $80, and $3100 contains $50,
         // the result of JMP ($30FF) will be a transfer of control to $4080 rather than $5080 as you intended
         // i.e. the 6502 took the low byte of the address from $30FF and the high byte from $3000
-        let indirect_ref = if mem_addr & 0x00FF == 0x00FF {
-            let lo = self.mem_read(mem_addr);
-            let hi = self.mem_read(mem_addr & 0xFF00);
-            u16::from_le_bytes([lo, hi])
+        // The 65C02 fixed this, and spends a cycle on it whether it's needed or not.
+        let indirect_ref = if self.variant.is_cmos() {
+            self.dummy_read(self.program_counter.wrapping_add(1));
+            self.read_u16(mem_addr)
         } else {
-            self.mem_read_u16(mem_addr)
+            let lo = self.read(mem_addr);
+            let hi = self.read((mem_addr & 0xFF00) | (mem_addr.wrapping_add(1) & 0x00FF));
+            u16::from_le_bytes([lo, hi])
         };
 
         self.program_counter = indirect_ref;
@@ -684,7 +972,15 @@ impl CPU {
 
     fn jmp_absolute(&mut self) {
         // JMP absolute
-        self.program_counter = self.mem_read_u16(self.program_counter);
+        self.program_counter = self.read_u16(self.program_counter);
+    }
+
+    fn jmp_indexed_indirect(&mut self) {
+        // JMP (abs,X), 65C02 only, for jump tables
+        let base = self.read_u16(self.program_counter);
+        self.dummy_read(self.program_counter.wrapping_add(1));
+        let mem_addr = base.wrapping_add(self.register_x as u16);
+        self.program_counter = self.read_u16(mem_addr);
     }
 
     fn update_zero_and_negative_flags(&mut self, result: u8) {
@@ -692,344 +988,621 @@ impl CPU {
         self.status.set(StatusFlags::NEGATIVE, result & 0b1000_0000 != 0);
     }
 
-    fn interrupt_nmi(&mut self) {
-        self.stack_push_u16(self.program_counter);
+    // B is only set in the pushed status for a BRK, which is the only way an
+    // IRQ handler can tell the two apart
+    fn push_interrupt(&mut self, return_addr: u16, brk: bool) {
+        self.stack_push_u16(return_addr);
         let mut status = self.status.clone();
-        status.set(StatusFlags::BREAK, false);
+        status.set(StatusFlags::BREAK, brk);
         status.set(StatusFlags::BREAK2, true);
         self.stack_push(status.bits());
         self.status.set(StatusFlags::INTERRUPT_DISABLE, true);
-        self.bus.tick(2);
-        self.program_counter = self.mem_read_u16(0xFFFA);
+
+        // so a handler can't be thrown off by wherever the program left D
+        if self.variant.is_cmos() {
+            self.status.set(StatusFlags::DECIMAL_MODE, false);
+        }
     }
 
-    pub fn run(&mut self) {
-        self.run_with_callback(|_| {});
+    // An interrupt runs through the same seven cycles as BRK, with the
+    // opcode and padding fetches thrown away
+    fn interrupt(&mut self, vector: u16) {
+        self.dummy_read(self.program_counter);
+        self.dummy_read(self.program_counter);
+        self.push_interrupt(self.program_counter, false);
+        self.program_counter = self.read_u16(vector);
     }
 
-    pub fn run_with_callback<F>(&mut self, mut callback: F) where F: FnMut(&mut CPU) {
-        let ref opcodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
+    fn interrupt_nmi(&mut self) {
+        self.interrupt(0xFFFA);
+    }
 
-        loop {
-            if self.bus.poll_nmi_status() {
-                self.interrupt_nmi();
-            }
+    fn interrupt_irq(&mut self) {
+        self.interrupt(0xFFFE);
+    }
+
+    // BRK goes through the IRQ vector too. The byte after it is padding, so
+    // the handler returns past it.
+    fn brk(&mut self) {
+        self.push_interrupt(self.program_counter.wrapping_add(1), true);
+        self.program_counter = self.read_u16(0xFFFE);
+    }
+
+    pub fn run(&mut self) -> Result<(), EmulatorError> {
+        self.run_with_callback(|_| {})
+    }
 
+    // Runs until `stop` is called, or a step goes wrong in a way it can't carry
+    // on from. The callback comes before every step.
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<(), EmulatorError> where F: FnMut(&mut CPU) {
+        loop {
             callback(self);
 
+            if self.take_stop() {
+                return Ok(());
+            }
+
             if self.pause {
                 continue;
             }
 
-            let code = self.mem_read(self.program_counter);
-            self.program_counter += 1;
-            let program_counter_state = self.program_counter;
+            self.run_step()?;
+        }
+    }
+
+    /*
+     * Run until the PPU has finished drawing a frame, so the frontend can
+     * present it and pace itself once per frame. Returns false if `stop` was
+     * called before getting there, and the error if a step went fatally wrong,
+     * in which case calling it again carries on with the same frame. Nothing
+     * runs while paused.
+     */
+    pub fn run_frame(&mut self) -> Result<bool, EmulatorError> {
+        self.run_frame_with_callback(|_| {})
+    }
+
+    pub fn run_frame_with_callback<F>(&mut self, mut callback: F) -> Result<bool, EmulatorError> where F: FnMut(&mut CPU) {
+        if self.pause {
+            return Ok(!self.take_stop());
+        }
+
+        loop {
+            callback(self);
+
+            if self.take_stop() {
+                return Ok(false);
+            }
+
+            self.run_step()?;
+
+            if self.bus.poll_frame_complete() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /*
+     * One instruction, or the interrupt sequence instead if one is pending.
+     * This runs even while paused, for stepping through a paused program. A
+     * jammed CPU does nothing at all and says so every time, and one waiting
+     * on a WAI idles a cycle at a time.
+     */
+    pub fn step(&mut self) -> StepResult {
+        if let Some(opcode) = self.jammed {
+            return StepResult {
+                opcode: None,
+                cycles: 0,
+                interrupt: None,
+                fault: Some(EmulatorError::Jammed { opcode, pc: self.program_counter }),
+            };
+        }
+
+        let start = self.bus.cycles();
+
+        if self.waiting && !self.wake() {
+            self.dummy_read(self.program_counter);
+            return StepResult {
+                opcode: None,
+                cycles: self.bus.cycles() - start,
+                interrupt: None,
+                fault: self.bus.take_error(),
+            };
+        }
+        self.waiting = false;
+
+        let mut result = StepResult {
+            opcode: None,
+            cycles: 0,
+            interrupt: self.poll_interrupts(),
+            fault: None,
+        };
+
+        if result.interrupt.is_none() {
+            let code = self.read(self.program_counter);
+            result.opcode = Some(code);
+            result.fault = self.execute(code).err();
+        }
+
+        // the bus's errors are taken either way, so they never carry over
+        let bus_error = self.bus.take_error();
+        result.fault = result.fault.or(bus_error);
+        result.cycles = self.bus.cycles() - start;
+        result
+    }
+
+    // A WAI ends once there's an interrupt to take. An IRQ while I is set
+    // ends it too, and the program carries on without taking it.
+    fn wake(&self) -> bool {
+        self.nmi_polled || self.irq_polled
+            || (self.status.contains(StatusFlags::INTERRUPT_DISABLE) && self.bus.poll_irq_status())
+    }
+
+    fn run_step(&mut self) -> Result<(), EmulatorError> {
+        match self.step().fault {
+            Some(error) if error.is_fatal() => Err(error),
+            Some(error) => {
+                self.diagnostic.get_or_insert(error);
+                Ok(())
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn take_stop(&mut self) -> bool {
+        std::mem::take(&mut self.stop)
+    }
+
+    /*
+     * Go by what the lines were on the previous instruction's second to last
+     * cycle. An NMI that arrived later than that waits for the end of the
+     * next instruction, and one that a $2002 read took back in the meantime
+     * never happens at all.
+     */
+    fn poll_interrupts(&mut self) -> Option<Interrupt> {
+        if self.nmi_polled && self.bus.poll_nmi_status() {
+            self.interrupt_nmi();
+            Some(Interrupt::NMI)
+        } else if self.irq_polled {
+            self.interrupt_irq();
+            Some(Interrupt::IRQ)
+        } else {
+            None
+        }
+    }
+
+    // run the instruction whose opcode has just been fetched from the program
+    // counter
+    fn execute(&mut self, code: u8) -> Result<(), EmulatorError> {
+        let opcodes = self.variant.opcodes();
+
+        let opcode = match opcodes.get(&code) {
+            Some(opcode) => opcode,
+            None => {
+                return Err(EmulatorError::UnknownOpcode { opcode: code, pc: self.program_counter });
+            },
+        };
+
+        self.program_counter = self.program_counter.wrapping_add(1);
+        let program_counter_state = self.program_counter;
 
-            let opcode = opcodes.get(&code).expect(&format!("OpCode {:x} is not recognized", code));
+        // Nothing takes less than two cycles, bar the 65C02's one-cycle NOPs.
+        // The instructions without an operand spend the second reading the
+        // byte after the opcode anyway.
+        if opcode.len == 1 && !matches!(opcode.cycles, opcode::CycleBehavior::Constant(1)) {
+            self.dummy_read(self.program_counter);
+        }
+
+        if self.variant.is_cmos() {
+            self.execute_65c02(code, opcode)?;
+        } else {
+            self.execute_nmos(code, opcode)?;
+        }
+
+        if program_counter_state == self.program_counter {
+            self.program_counter = self.program_counter.wrapping_add((opcode.len - 1) as u16);
+        }
+
+        Ok(())
+    }
+
+    // The NMOS instruction set, undocumented opcodes and all. The 65C02
+    // shares the documented part of it.
+    fn execute_nmos(&mut self, code: u8, opcode: &opcode::OpCode) -> Result<(), EmulatorError> {
+        match code {
+            // OFFICIAL OPCODES
+
+            0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => self.adc(&opcode.mode),
+            0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => self.and(&opcode.mode),
+            0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(&opcode.mode),
 
-            let instr_result: InstructionResult = match code {
-                // OFFICIAL OPCODES
+            0x90 => self.branch(!self.status.contains(StatusFlags::CARRY)),     // BCC
+            0xB0 => self.branch(self.status.contains(StatusFlags::CARRY)),      // BCS
+            0xF0 => self.branch(self.status.contains(StatusFlags::ZERO)),       // BEQ
+            0x30 => self.branch(self.status.contains(StatusFlags::NEGATIVE)),   // BMI
+            0xD0 => self.branch(!self.status.contains(StatusFlags::ZERO)),      // BNE
+            0x10 => self.branch(!self.status.contains(StatusFlags::NEGATIVE)),  // BPL
+            0x50 => self.branch(!self.status.contains(StatusFlags::OVERFLOW)),  // BVC
+            0x70 => self.branch(self.status.contains(StatusFlags::OVERFLOW)),   // BVS
 
-                0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => self.adc(&opcode.mode),
-                0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => self.and(&opcode.mode),
-                0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(&opcode.mode),
+            0x24 | 0x2C => self.bit(&opcode.mode),
 
-                0x90 => self.branch(!self.status.contains(StatusFlags::CARRY)),     // BCC
-                0xB0 => self.branch(self.status.contains(StatusFlags::CARRY)),      // BCS
-                0xF0 => self.branch(self.status.contains(StatusFlags::ZERO)),       // BEQ
-                0x30 => self.branch(self.status.contains(StatusFlags::NEGATIVE)),   // BMI
-                0xD0 => self.branch(!self.status.contains(StatusFlags::ZERO)),      // BNE
-                0x10 => self.branch(!self.status.contains(StatusFlags::NEGATIVE)),  // BPL
-                0x50 => self.branch(!self.status.contains(StatusFlags::OVERFLOW)),  // BVC
-                0x70 => self.branch(self.status.contains(StatusFlags::OVERFLOW)),   // BVS
+            0x00 => self.brk(),
 
-                0x24 | 0x2C => self.bit(&opcode.mode),
+            0x18 => self.status.set(StatusFlags::CARRY, false),             // CLC
+            0xD8 => self.status.set(StatusFlags::DECIMAL_MODE, false),      // CLD
+            0x58 => self.status.set(StatusFlags::INTERRUPT_DISABLE, false), // CLI
+            0xB8 => self.status.set(StatusFlags::OVERFLOW, false),          // CLV
 
-                0x18 => self.status.set(StatusFlags::CARRY, false).into(),              // CLC
-                0xD8 => self.status.set(StatusFlags::DECIMAL_MODE, false).into(),       // CLD
-                0x58 => self.status.set(StatusFlags::INTERRUPT_DISABLE, false).into(),  // CLI
-                0xB8 => self.status.set(StatusFlags::OVERFLOW, false).into(),           // CLV
+            0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => self.cmp(&opcode.mode),
+            0xE0 | 0xE4 | 0xEC => self.cpx(&opcode.mode),
+            0xC0 | 0xC4 | 0xCC => self.cpy(&opcode.mode),
 
-                0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => self.cmp(&opcode.mode),
-                0xE0 | 0xE4 | 0xEC => self.cpx(&opcode.mode).into(),
-                0xC0 | 0xC4 | 0xCC => self.cpy(&opcode.mode).into(),
+            0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(&opcode.mode),
 
-                0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(&opcode.mode).into(),
+            0xCA => self.dex(),
+            0x88 => self.dey(),
 
-                0xCA => self.dex().into(),
-                0x88 => self.dey().into(),
+            0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => self.eor(&opcode.mode),
 
-                0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => self.eor(&opcode.mode),
+            0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(&opcode.mode),
 
-                0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(&opcode.mode).into(),
+            0xE8 => self.inx(),
+            0xC8 => self.iny(),
 
-                0xE8 => self.inx().into(),
-                0xC8 => self.iny().into(),
+            0x6C => self.jmp_indirect(),
+            0x4C => self.jmp_absolute(),
 
-                0x6C => self.jmp_indirect().into(),
-                0x4C => self.jmp_absolute().into(),
+            0x20 => self.jsr(),
+            0x60 => self.rts(),
 
-                0x20 => self.jsr().into(),
-                0x60 => self.rts().into(),
+            0x40 => self.rti(),
 
-                0x40 => self.rti().into(),
+            0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => self.lda(&opcode.mode),
+            0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(&opcode.mode),
+            0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(&opcode.mode),
 
-                0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => self.lda(&opcode.mode),
-                0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(&opcode.mode),
-                0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(&opcode.mode),
+            0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(&opcode.mode),
 
-                0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(&opcode.mode).into(),
+            0xEA => {}, // NOP
 
-                0xEA => InstructionResult::Complete, // NOP
+            0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => self.ora(&opcode.mode),
 
-                0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => self.ora(&opcode.mode),
+            0x48 => self.stack_push(self.register_a), // PHA
+            0x08 => self.php(), 
+            0x68 => self.pla(),
+            0x28 => self.plp(),
+            
+            0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(&opcode.mode),
+            0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(&opcode.mode),
 
-                0x48 => self.stack_push(self.register_a).into(), // PHA
-                0x08 => self.php().into(), 
-                0x68 => self.pla().into(),
-                0x28 => self.plp().into(),
+            0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => self.sbc(&opcode.mode),
+
+            0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => self.sta(&opcode.mode),
+            0x84 | 0x94 | 0x8C => self.sty(&opcode.mode),
+            0x86 | 0x96 | 0x8E => self.stx(&opcode.mode),
+
+            0x38 => self.status.set(StatusFlags::CARRY, true),
+            0xF8 => self.status.set(StatusFlags::DECIMAL_MODE, true),
+            0x78 => self.status.set(StatusFlags::INTERRUPT_DISABLE, true),
+
+            0xAA => self.tax(),
+            0xA8 => self.tay(),
+            0xBA => self.tsx(),
+            0x8A => self.txa(),
+            0x9A => self.txs(),
+            0x98 => self.tya(),
+
+            // UN-OFFICIAL OPCODES
+
+            0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => {
+                // NOP
+            },
+
+            0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 |
+            0x0C | 0x1C | 0x3C | 0x5C | 0x7C |
+            0xDC | 0xFC | 0x04 | 0x44 | 0x64 |
+            0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => {
+                // NOP, which still reads its operand
+                self.read_operand(&opcode.mode);
+            },
+
+            0xA3 | 0xA7 | 0xAF |
+            0xB3 | 0xB7 | 0xBF => {
+                // LAX
+                self.lda(&opcode.mode);
+                self.tax();
+            },
+
+            0x83 | 0x87 | 0x8F | 0x97 => {
+                // SAX
+                let value = self.register_a & self.register_x;
+                let addr = self.get_write_address(&opcode.mode);
+                self.write(addr, value);
+            },
+
+            // Duplicated SBC 
+            0xEB => self.sbc(&opcode.mode),
+
+            // DCP
+            0xC3 | 0xC7 | 0xCF | 0xD3 |
+            0xD7 | 0xDB | 0xDF => {
+                let value = self.modify(&opcode.mode, |_, value| value.wrapping_sub(1));
+                self.compare_value(self.register_a, value);
+            },
+
+            // ISC (ISB)
+            0xE3 | 0xE7 | 0xEF | 0xF3 |
+            0xF7 | 0xFB | 0xFF => {
+                let value = self.modify(&opcode.mode, |_, value| value.wrapping_add(1));
+                self.sbc_value(value);
+            },
+
+            // SLO
+            0x03 | 0x07 | 0x0F | 0x13 |
+            0x17 | 0x1B | 0x1F => {
+                let value = self.modify(&opcode.mode, CPU::asl_value);
+                self.ora_value(value);
+            },
+
+            // RLA
+            0x23 | 0x27 | 0x2F | 0x33 |
+            0x37 | 0x3B | 0x3F => {
+                let value = self.modify(&opcode.mode, CPU::rol_value);
+                self.register_a &= value;
+                self.update_zero_and_negative_flags(self.register_a);
+            },
+
+            // SRE
+            0x43 | 0x47 | 0x4F | 0x53 |
+            0x57 | 0x5B | 0x5F => {
+                let value = self.modify(&opcode.mode, CPU::lsr_value);
+                self.register_a ^= value;
+                self.update_zero_and_negative_flags(self.register_a);
+            },
+
+            // RRA
+            0x63 | 0x67 | 0x6F | 0x73 |
+            0x77 | 0x7B | 0x7F => {
+                let value = self.modify(&opcode.mode, CPU::ror_value);
+                self.adc_value(value);
+            },
+
+            // ALR
+            0x4B => {
+                self.and(&opcode.mode);
+                self.lsr(&AddressingMode::None);
+            },
+
+            // ANC
+            0x0B | 0x2B => {
+                self.and(&opcode.mode);
+                self.status.set(StatusFlags::CARRY, self.status.contains(StatusFlags::NEGATIVE));
+            },
+
+            // ARR
+            0x6B => {
+                self.and(&opcode.mode);
+                self.ror(&AddressingMode::None);
+
+                let (five, six) = (
+                    self.register_a & 0b0010_0000 == 0b0010_0000,
+                    self.register_a & 0b0100_0000 == 0b0100_0000,
+                );
+
+                self.status.set(StatusFlags::OVERFLOW, five != six);
+                self.status.set(StatusFlags::CARRY, six);
+            },
+
+            // AXS
+            0xCB => {
+                let data = self.read_operand(&opcode.mode);
+                let bitwise_and = self.register_a & self.register_x;
+
+                if data <= bitwise_and {
+                    self.status.insert(StatusFlags::CARRY);
+                }
                 
-                0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(&opcode.mode).into(),
-                0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(&opcode.mode).into(),
-
-                0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => self.sbc(&opcode.mode),
-
-                0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => self.sta(&opcode.mode).into(),
-                0x84 | 0x94 | 0x8C => self.sty(&opcode.mode).into(),
-                0x86 | 0x96 | 0x8E => self.stx(&opcode.mode).into(),
-
-                0x38 => self.status.set(StatusFlags::CARRY, true).into(),
-                0xF8 => self.status.set(StatusFlags::DECIMAL_MODE, true).into(),
-                0x78 => self.status.set(StatusFlags::INTERRUPT_DISABLE, true).into(),
-
-                0xAA => self.tax().into(),
-                0xA8 => self.tay().into(),
-                0xBA => self.tsx().into(),
-                0x8A => self.txa().into(),
-                0x9A => self.txs().into(),
-                0x98 => self.tya().into(),
-
-                // UN-OFFICIAL OPCODES
-
-                0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => {
-                    // NOP
-                    InstructionResult::Complete
-                },
-
-                0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => {
-                    // NOP
-                    InstructionResult::Complete
-                },
-
-                0x0C | 0x1C | 0x3C | 0x5C | 0x7C |
-                0xDC | 0xFC | 0x04 | 0x44 | 0x64 |
-                0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => {
-                    // NOP
-                    let (_, page_crossed) = self.get_operand_address(&opcode.mode);
-                    InstructionResult::PageCross(page_crossed)
-                },
-
-                0xA3 | 0xA7 | 0xAF |
-                0xB3 | 0xB7 | 0xBF => {
-                    // LAX
-                    self.lda(&opcode.mode);
-                    self.tax();
-                    InstructionResult::Complete
-                },
-
-                0x83 | 0x87 | 0x8F | 0x97 => {
-                    // SAX
-                    let value = self.register_a & self.register_x;
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    self.mem_write(addr, value);
-                    InstructionResult::Complete
-                },
-
-                // Duplicated SBC 
-                0xEB => self.sbc(&opcode.mode),
-
-                // DCP
-                0xC3 | 0xC7 | 0xCF | 0xD3 |
-                0xD7 | 0xDB | 0xDF => {
-                    self.dec(&opcode.mode);
-                    self.cmp(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // ISC (ISB)
-                0xE3 | 0xE7 | 0xEF | 0xF3 |
-                0xF7 | 0xFB | 0xFF => {
-                    self.inc(&opcode.mode);
-                    self.sbc(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // SLO
-                0x03 | 0x07 | 0x0F | 0x13 |
-                0x17 | 0x1B | 0x1F => {
-                    self.asl(&opcode.mode);
-                    self.ora(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // RLA
-                0x23 | 0x27 | 0x2F | 0x33 |
-                0x37 | 0x3B | 0x3F => {
-                    self.rol(&opcode.mode);
-                    self.and(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // SRE
-                0x43 | 0x47 | 0x4F | 0x53 |
-                0x57 | 0x5B | 0x5F => {
-                    self.lsr(&opcode.mode);
-                    self.eor(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // RRA
-                0x63 | 0x67 | 0x6F | 0x73 |
-                0x77 | 0x7B | 0x7F => {
-                    self.ror(&opcode.mode);
-                    self.adc(&opcode.mode);
-                    InstructionResult::Complete
-                },
-
-                // ALR
-                0x4B => {
-                    self.and(&opcode.mode);
-                    self.lsr(&AddressingMode::None);
-                    InstructionResult::Complete
-                },
-
-                // ANC
-                0x0B | 0x2B => {
-                    self.and(&opcode.mode);
-                    self.status.set(StatusFlags::CARRY, self.status.contains(StatusFlags::NEGATIVE));
-                    InstructionResult::Complete
-                },
-
-                // ARR
-                0x6B => {
-                    self.and(&opcode.mode);
-                    self.ror(&AddressingMode::None);
-
-                    let (five, six) = (
-                        self.register_a & 0b0010_0000 == 0b0010_0000,
-                        self.register_a & 0b0100_0000 == 0b0100_0000,
-                    );
-
-                    self.status.set(StatusFlags::OVERFLOW, five != six);
-                    self.status.set(StatusFlags::CARRY, six);
-                    InstructionResult::Complete
-                },
-
-                // AXS
-                0xCB => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    let data = self.mem_read(addr);
-                    let bitwise_and = self.register_a & self.register_x;
-
-                    if data <= bitwise_and {
-                        self.status.insert(StatusFlags::CARRY);
-                    }
-                    
-                    let result = bitwise_and.wrapping_sub(data);
-                    self.update_zero_and_negative_flags(result);
-
-                    self.register_x = result;
-                    InstructionResult::Complete
-                },
-
-                // ATX
-                0xAB => {
-                    self.and(&opcode.mode);
-                    self.register_x = self.register_a;
-                    InstructionResult::Complete
-                },
-
-                // AXA
-                0x9F | 0x93 => {
-                    // Conflicting info on what this one does
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    let value = self.register_a & self.register_x & (addr >> 8) as u8;
-                    self.mem_write(addr, value);
-                    InstructionResult::Complete
-                },
-
-                // SXA
-                0x9E => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    let value = self.register_x & (addr >> 8) as u8 + 1;
-                    self.mem_write(addr, value);
-                    InstructionResult::Complete
-                },
-
-                // SYA
-                0x9C => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    let value = self.register_y & (addr >> 8) as u8 + 1;
-                    self.mem_write(addr, value);
-                    InstructionResult::Complete
-                },
-
-                // XAS
-                0x9B => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    self.stack_pointer = self.register_a & self.register_x;
-                    let value = self.stack_pointer & (addr >> 8) as u8 + 1;
-                    self.mem_write(addr, value);
-                    InstructionResult::Complete
-                },
-
-                // HLT
-                0x02 | 0x12 | 0x22 | 0x32 | 0x42 |
-                0x52 | 0x62 | 0x72 | 0x92 | 0xB2 |
-                0xD2 | 0xF2 => {
-                    panic!("Illegal HLT instruction received.");
-                },
-
-                // LAR
-                0xBB => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    let data = self.mem_read(addr) & self.stack_pointer;
-                    self.register_a = data;
-                    self.register_x = data;
-                    self.stack_pointer = data;
-                    self.update_zero_and_negative_flags(data);
-                    InstructionResult::Complete
-                },
-
-                // XAA
-                0x8B => {
-                    let (addr, _) = self.get_operand_address(&opcode.mode);
-                    self.register_a = self.register_x;
-                    self.register_a &= self.mem_read(addr);
-                    InstructionResult::Complete
-                },
-
-                0x00 => return, // BRK
-            };
+                let result = bitwise_and.wrapping_sub(data);
+                self.update_zero_and_negative_flags(result);
 
-            let ticks: u8 = match (&opcode.cycles, instr_result) {
-                (&CycleBehavior::Constant(i), _) => i,
-                (&CycleBehavior::PageCross(i), InstructionResult::PageCross(false)) => i,
-                (&CycleBehavior::PageCross(i), InstructionResult::PageCross(true)) => i + 1,
-                (&CycleBehavior::Branch(i), br) => match br {
-                    InstructionResult::BranchSuccess => i + 1,
-                    InstructionResult::BranchSuccessPageCrossed => i + 2,
-                    _ => i,
-                },
-                _ => panic!("Invalid cycle behavior instruction result!"),
-            };
+                self.register_x = result;
+            },
 
-            self.bus.tick(ticks);
+            // ATX
+            0xAB => {
+                self.and(&opcode.mode);
+                self.register_x = self.register_a;
+            },
 
-            if program_counter_state == self.program_counter {
-                self.program_counter += (opcode.len - 1) as u16;
-            }
+            // AXA
+            0x9F | 0x93 => {
+                // Conflicting info on what this one does
+                let addr = self.get_write_address(&opcode.mode);
+                let value = self.register_a & self.register_x & (addr >> 8) as u8;
+                self.write(addr, value);
+            },
+
+            // SXA
+            0x9E => {
+                let addr = self.get_write_address(&opcode.mode);
+                let value = self.register_x & ((addr >> 8) as u8).wrapping_add(1);
+                self.write(addr, value);
+            },
+
+            // SYA
+            0x9C => {
+                let addr = self.get_write_address(&opcode.mode);
+                let value = self.register_y & ((addr >> 8) as u8).wrapping_add(1);
+                self.write(addr, value);
+            },
+
+            // XAS
+            0x9B => {
+                let addr = self.get_write_address(&opcode.mode);
+                self.stack_pointer = self.register_a & self.register_x;
+                let value = self.stack_pointer & ((addr >> 8) as u8).wrapping_add(1);
+                self.write(addr, value);
+            },
+
+            // HLT
+            0x02 | 0x12 | 0x22 | 0x32 | 0x42 |
+            0x52 | 0x62 | 0x72 | 0x92 | 0xB2 |
+            0xD2 | 0xF2 => {
+                self.program_counter = self.program_counter.wrapping_sub(1);
+                self.jammed = Some(code);
+                return Err(EmulatorError::Jammed { opcode: code, pc: self.program_counter });
+            },
+
+            // LAR
+            0xBB => {
+                let data = self.read_operand(&opcode.mode) & self.stack_pointer;
+                self.register_a = data;
+                self.register_x = data;
+                self.stack_pointer = data;
+                self.update_zero_and_negative_flags(data);
+            },
+
+            // XAA
+            0x8B => {
+                self.register_a = self.register_x;
+                self.register_a &= self.read_operand(&opcode.mode);
+            },
         }
+
+        Ok(())
+    }
+
+    fn execute_65c02(&mut self, code: u8, opcode: &opcode::OpCode) -> Result<(), EmulatorError> {
+        match code {
+            0x80 => self.branch(true), // BRA
+
+            0x89 | 0x34 | 0x3C => self.bit(&opcode.mode),
+
+            0x1A => {
+                // INC A
+                self.register_a = self.register_a.wrapping_add(1);
+                self.update_zero_and_negative_flags(self.register_a);
+            },
+            0x3A => {
+                // DEC A
+                self.register_a = self.register_a.wrapping_sub(1);
+                self.update_zero_and_negative_flags(self.register_a);
+            },
+
+            0x7C => self.jmp_indexed_indirect(),
+
+            0xDA => self.stack_push(self.register_x), // PHX
+            0x5A => self.stack_push(self.register_y), // PHY
+            0xFA => self.plx(),
+            0x7A => self.ply(),
+
+            0x64 | 0x74 | 0x9C | 0x9E => self.stz(&opcode.mode),
+
+            0x14 | 0x1C => self.trb(&opcode.mode),
+            0x04 | 0x0C => self.tsb(&opcode.mode),
+
+            // (zp)
+            0x12 => self.ora(&opcode.mode),
+            0x32 => self.and(&opcode.mode),
+            0x52 => self.eor(&opcode.mode),
+            0x72 => self.adc(&opcode.mode),
+            0x92 => self.sta(&opcode.mode),
+            0xB2 => self.lda(&opcode.mode),
+            0xD2 => self.cmp(&opcode.mode),
+            0xF2 => self.sbc(&opcode.mode),
+
+            // RMB and SMB
+            0x07 | 0x17 | 0x27 | 0x37 | 0x47 | 0x57 | 0x67 | 0x77 => {
+                let mask = !(1 << (code >> 4));
+                self.modify(&opcode.mode, |_, value| value & mask);
+            },
+            0x87 | 0x97 | 0xA7 | 0xB7 | 0xC7 | 0xD7 | 0xE7 | 0xF7 => {
+                let bit = 1 << ((code >> 4) & 0b111);
+                self.modify(&opcode.mode, |_, value| value | bit);
+            },
+
+            // BBR and BBS
+            _ if code & 0x0F == 0x0F => self.branch_on_bit((code >> 4) & 0b111, code & 0x80 != 0),
+
+            0xCB => {
+                // WAI
+                self.dummy_read(self.program_counter);
+                self.waiting = true;
+            },
+            0xDB => {
+                // STP, which only a reset gets it out of, like a jam
+                self.dummy_read(self.program_counter);
+                self.jammed = Some(code);
+                self.program_counter = self.program_counter.wrapping_sub(1);
+                return Err(EmulatorError::Jammed { opcode: code, pc: self.program_counter });
+            },
+
+            // the rest of the unused opcodes are all NOPs
+            _ if code & 0x03 == 0x03 => {},
+
+            0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 |
+            0x44 | 0x54 | 0xD4 | 0xF4 | 0xDC | 0xFC => {
+                self.read_operand(&opcode.mode);
+            },
+
+            0x5C => {
+                let addr = self.read_u16(self.program_counter);
+                for _ in 0..5 {
+                    self.dummy_read(0xFF00 | (addr & 0x00FF));
+                }
+            },
+
+            // everything else is as it was on the NMOS chip, which the
+            // opcode table has already kept to the documented opcodes
+            _ => return self.execute_nmos(code, opcode),
+        }
+
+        Ok(())
     }
 }
 
+impl Snapshot for CPU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.register_a);
+        w.write_u8(self.register_x);
+        w.write_u8(self.register_y);
+        w.write_u8(self.stack_pointer);
+        w.write_u8(self.status.bits());
+        w.write_u16(self.program_counter);
+        w.write_bool(self.pause);
+        w.write_bool(self.jammed.is_some());
+        w.write_u8(self.jammed.unwrap_or(0));
+        w.write_bool(self.waiting);
+        w.write_bool(self.nmi_sampled);
+        w.write_bool(self.irq_sampled);
+        w.write_bool(self.nmi_polled);
+        w.write_bool(self.irq_polled);
+        w.write_u8(self.variant as u8);
+        self.bus.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.register_a = r.read_u8()?;
+        self.register_x = r.read_u8()?;
+        self.register_y = r.read_u8()?;
+        self.stack_pointer = r.read_u8()?;
+        self.status = StatusFlags::from_bits_truncate(r.read_u8()?);
+        self.program_counter = r.read_u16()?;
+        self.pause = r.read_bool()?;
+        let jammed = r.read_bool()?;
+        let opcode = r.read_u8()?;
+        self.jammed = jammed.then_some(opcode);
+        self.waiting = r.read_bool()?;
+        self.nmi_sampled = r.read_bool()?;
+        self.irq_sampled = r.read_bool()?;
+        self.nmi_polled = r.read_bool()?;
+        self.irq_polled = r.read_bool()?;
+        self.variant = match r.read_u8()? {
+            0 => CpuVariant::Ricoh2A03,
+            1 => CpuVariant::NMOS6502,
+            2 => CpuVariant::WDC65C02,
+            n => return Err(format!("Save state has {} where a CPU variant was expected", n)),
+        };
+        self.bus.load_state(r)
+    }
+}
@@ -1,23 +1,44 @@
 use super::*;
 
-use crate::bus::Bus;
+use crate::bus::{Bus, IrqSource};
 use crate::cpu::{STACK_RESET, STATUS_RESET};
+use crate::error::EmulatorError;
+use crate::joypad::JoypadButton;
+use crate::opcode::CycleBehavior;
 use crate::rom::Rom;
 
 fn new_cpu() -> CPU {
     let rom = Rom::blank();
-    let mut bus = Bus::new(rom);
+    let mut bus = Bus::new(rom).unwrap();
     bus.allow_rom_writes = true;
 
     CPU::new(bus)
 }
 
+// The test programs end on a BRK, or run into the blank ROM's zeros, and stop
+// there before it's taken
+fn stop_on_brk(cpu: &mut CPU) {
+    if cpu.mem_read(cpu.program_counter) == 0x00 {
+        cpu.stop();
+    }
+}
+
+fn run(cpu: &mut CPU) {
+    cpu.run_with_callback(stop_on_brk).unwrap();
+}
+
+fn load_and_run(cpu: &mut CPU, program: Vec<u8>) {
+    cpu.load(program);
+    cpu.reset();
+    run(cpu);
+}
+
 #[test]
 fn test_0xa9_lda_immidiate_load_data() {
     let mut cpu = new_cpu();
     cpu.load(vec![0xa9, 0x05, 0x00]);
     cpu.reset();
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x05);
     assert!(!cpu.status.contains(StatusFlags::ZERO));
@@ -28,7 +49,7 @@ fn test_0xa9_lda_immidiate_load_data() {
 fn test_lda_from_memory() {
     let mut cpu = new_cpu();
     cpu.mem_write(0x10, 0x55);
-    cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
+    load_and_run(&mut cpu, vec![0xa5, 0x10, 0x00]);
 
     assert_eq!(cpu.register_a, 0x55);
 }
@@ -39,7 +60,7 @@ fn test_sta() {
     cpu.load(vec![0x85, 0x10, 0x00]);
     cpu.reset();
     cpu.register_a = 4;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.mem_read(0x10), 4);
 }
@@ -47,7 +68,7 @@ fn test_sta() {
 #[test]
 fn test_0xa9_lda_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
+    load_and_run(&mut cpu, vec![0xa9, 0x00, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -59,7 +80,7 @@ fn test_0xaa_tax_move_a_to_x() {
     cpu.load(vec![0xaa, 0x00]);
     cpu.reset();
     cpu.register_a = 10;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 10);
 }
@@ -67,7 +88,7 @@ fn test_0xaa_tax_move_a_to_x() {
 #[test]
 fn test_5_ops_working_together() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+    load_and_run(&mut cpu, vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
 
     assert_eq!(cpu.register_x, 0xc1)
 }
@@ -95,7 +116,7 @@ fn test_tya_move_y_to_a() {
     cpu.load(vec![0x98, 0x00]);
     cpu.reset();
     cpu.register_y = 15;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 15);
 }
@@ -106,7 +127,7 @@ fn test_txs_move_x_to_s() {
     cpu.load(vec![0x9A, 0x00]);
     cpu.reset();
     cpu.register_x = 69;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.stack_pointer, 69);
 }
@@ -117,7 +138,7 @@ fn test_txa_move_x_to_a() {
     cpu.load(vec![0x8A, 0x00]);
     cpu.reset();
     cpu.register_x = 37;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 37);
 }
@@ -128,7 +149,7 @@ fn test_txa_zero_flag() {
     cpu.load(vec![0x8A, 0x00]);
     cpu.reset();
     cpu.register_x = 0;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -139,7 +160,7 @@ fn test_txa_negative_flag() {
     cpu.load(vec![0x8A, 0x00]);
     cpu.reset();
     cpu.register_x = 0b1000_0010;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -149,7 +170,7 @@ fn test_tsx_move_s_to_x() {
     let mut cpu = new_cpu();
     cpu.load(vec![0xBA, 0x00]);
     cpu.reset();
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, STACK_RESET);
 }
@@ -160,7 +181,7 @@ fn test_tsx_zero_flag() {
     cpu.load(vec![0xBA, 0x00]);
     cpu.reset();
     cpu.stack_pointer = 0;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -171,7 +192,7 @@ fn test_tsx_negative_flag() {
     cpu.load(vec![0xBA, 0x00]);
     cpu.reset();
     cpu.stack_pointer = 0b1000_0010;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -179,7 +200,7 @@ fn test_tsx_negative_flag() {
 #[test]
 fn test_tay_move_a_to_y() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0x13, 0xA8, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0x13, 0xA8, 0x00]);
 
     assert_eq!(cpu.register_y, 0x13);
 }
@@ -187,7 +208,7 @@ fn test_tay_move_a_to_y() {
 #[test]
 fn test_tay_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0x00, 0xA8, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0x00, 0xA8, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -195,7 +216,7 @@ fn test_tay_zero_flag() {
 #[test]
 fn test_tay_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b1000_0010, 0xA8, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0b1000_0010, 0xA8, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -203,7 +224,7 @@ fn test_tay_negative_flag() {
 #[test]
 fn test_ldx_load_immediate() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0xCC, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0xCC, 0x00]);
 
     assert_eq!(cpu.register_x, 0xCC);
 }
@@ -214,7 +235,7 @@ fn test_ldx_load_zero_page() {
     cpu.load(vec![0xA6, 0x10, 0x00]);
     cpu.reset();
     cpu.mem_write(0x10, 0x33);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 0x33);
 }
@@ -226,7 +247,7 @@ fn test_ldx_load_zero_page_y() {
     cpu.reset();
     cpu.register_y = 1;
     cpu.mem_write(0x12, 0x44);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 0x44);
 }
@@ -237,7 +258,7 @@ fn test_ldx_load_absolute() {
     cpu.load(vec![0xAE, 0x10, 0x02, 0x00]);
     cpu.reset();
     cpu.mem_write(0x0210, 0x99);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 0x99);
 
@@ -250,7 +271,7 @@ fn test_ldx_load_absolute_y() {
     cpu.reset();
     cpu.register_y = 4;
     cpu.mem_write(0x0214, 0x88);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 0x88);
 
@@ -259,7 +280,7 @@ fn test_ldx_load_absolute_y() {
 #[test]
 fn test_ldx_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xa2, 0x00, 0x00]);
+    load_and_run(&mut cpu, vec![0xa2, 0x00, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -267,7 +288,7 @@ fn test_ldx_zero_flag() {
 #[test]
 fn test_ldx_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0b1011_0010, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0b1011_0010, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -275,7 +296,7 @@ fn test_ldx_negative_flag() {
 #[test]
 fn test_ldy_load_immediate() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA0, 0xDE, 0x00]);
+    load_and_run(&mut cpu, vec![0xA0, 0xDE, 0x00]);
 
     assert_eq!(cpu.register_y, 0xDE);
 }
@@ -286,7 +307,7 @@ fn test_ldy_load_zero_page() {
     cpu.load(vec![0xA4, 0x69, 0x00]);
     cpu.reset();
     cpu.mem_write(0x69, 0xBB);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_y, 0xBB);
 }
@@ -298,7 +319,7 @@ fn test_ldy_load_zero_page_x() {
     cpu.reset();
     cpu.register_x = 2;
     cpu.mem_write(0x13, 0x47);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_y, 0x47);
 }
@@ -309,7 +330,7 @@ fn test_ldy_load_absolute() {
     cpu.load(vec![0xAC, 0x9A, 0x04, 0x00]);
     cpu.reset();
     cpu.mem_write(0x049A, 0x12);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_y, 0x12);
 
@@ -322,7 +343,7 @@ fn test_ldy_load_absolute_x() {
     cpu.reset();
     cpu.register_y = 0xA2;
     cpu.mem_write(0x0235, 0x87);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_y, 0x87);
 
@@ -331,7 +352,7 @@ fn test_ldy_load_absolute_x() {
 #[test]
 fn test_ldy_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xa0, 0x00, 0x00]);
+    load_and_run(&mut cpu, vec![0xa0, 0x00, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -339,7 +360,7 @@ fn test_ldy_zero_flag() {
 #[test]
 fn test_ldy_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0b1001_0111, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0b1001_0111, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -347,7 +368,7 @@ fn test_ldy_negative_flag() {
 #[test]
 fn test_sty_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA0, 0x11, 0x84, 0xBD, 0x00]);
+    load_and_run(&mut cpu, vec![0xA0, 0x11, 0x84, 0xBD, 0x00]);
 
     assert_eq!(cpu.mem_read(0xBD), 0x11);
 }
@@ -355,7 +376,7 @@ fn test_sty_zero_page() {
 #[test]
 fn test_sty_zero_page_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0x0A, 0xA0, 0x77, 0x94, 0x30, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0x0A, 0xA0, 0x77, 0x94, 0x30, 0x00]);
 
     assert_eq!(cpu.mem_read(0x3A), 0x77);
 }
@@ -363,7 +384,7 @@ fn test_sty_zero_page_x() {
 #[test]
 fn test_sty_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA0, 0xCD, 0x8C, 0x34, 0x12, 0x00]);
+    load_and_run(&mut cpu, vec![0xA0, 0xCD, 0x8C, 0x34, 0x12, 0x00]);
 
     assert_eq!(cpu.mem_read(0x1234), 0xCD);
 }
@@ -372,7 +393,7 @@ fn test_sty_absolute() {
 #[test]
 fn test_stx_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0xDD, 0x86, 0xB0, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0xDD, 0x86, 0xB0, 0x00]);
 
     assert_eq!(cpu.mem_read(0xB0), 0xDD);
 }
@@ -380,7 +401,7 @@ fn test_stx_zero_page() {
 #[test]
 fn test_stx_zero_page_y() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA0, 0x0B, 0xA2, 0x78, 0x96, 0x01, 0x00]);
+    load_and_run(&mut cpu, vec![0xA0, 0x0B, 0xA2, 0x78, 0x96, 0x01, 0x00]);
 
     assert_eq!(cpu.mem_read(0x000C), 0x78);
 }
@@ -388,7 +409,7 @@ fn test_stx_zero_page_y() {
 #[test]
 fn test_stx_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA2, 0xCE, 0x8E, 0x35, 0x13, 0x00]);
+    load_and_run(&mut cpu, vec![0xA2, 0xCE, 0x8E, 0x35, 0x13, 0x00]);
 
     assert_eq!(cpu.mem_read(0x1335), 0xCE);
 }
@@ -396,7 +417,7 @@ fn test_stx_absolute() {
 #[test]
 fn test_sei_interrupt_disable() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0x78, 0x00]);
+    load_and_run(&mut cpu, vec![0x78, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
 }
@@ -404,7 +425,7 @@ fn test_sei_interrupt_disable() {
 #[test]
 fn test_sed_decimal_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xF8, 0x00]);
+    load_and_run(&mut cpu, vec![0xF8, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::DECIMAL_MODE));
 }
@@ -412,7 +433,7 @@ fn test_sed_decimal_flag() {
 #[test]
 fn test_sec_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0x38, 0x00]);
+    load_and_run(&mut cpu, vec![0x38, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::CARRY));
 }
@@ -420,7 +441,7 @@ fn test_sec_carry_flag() {
 #[test]
 fn test_and_immediate() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b0111_1111, 0x29, 0b1010_0101, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0b0111_1111, 0x29, 0b1010_0101, 0x00]);
 
     assert_eq!(cpu.register_a, 0b0010_0101);
 }
@@ -428,7 +449,7 @@ fn test_and_immediate() {
 #[test]
 fn test_and_indirect_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0010, // LDA #$02
         0x85, 0xFF, // STA $FF
         0xA9, 0xFF, // LDA #$FF
@@ -444,7 +465,7 @@ fn test_and_indirect_x() {
 #[test]
 fn test_and_indirect_y() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0100, // LDA #$04
         0x85, 0xFF, // STA $FF
         0xA9, 0xF0, // LDA #$F0
@@ -462,7 +483,7 @@ fn test_and_indirect_y() {
 #[test]
 fn test_asl_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1010_0000,
         0x0A,
     ]);
@@ -473,7 +494,7 @@ fn test_asl_carry_flag() {
 #[test]
 fn test_asl_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1000_0000,
         0x0A,
     ]);
@@ -484,7 +505,7 @@ fn test_asl_zero_flag() {
 #[test]
 fn test_asl_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0100_0000,
         0x0A,
     ]);
@@ -495,7 +516,7 @@ fn test_asl_negative_flag() {
 #[test]
 fn test_asl_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_1011,
         0x0A,
     ]);
@@ -506,7 +527,7 @@ fn test_asl_implied() {
 #[test]
 fn test_asl_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_1010,
         0x85, 0x10,
         0x06, 0x10,
@@ -518,7 +539,7 @@ fn test_asl_zero_page() {
 #[test]
 fn test_asl_zero_page_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0010_1101,
         0x85, 0x06,
         0xA2, 0x04,
@@ -531,7 +552,7 @@ fn test_asl_zero_page_x() {
 #[test]
 fn test_asl_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0011_0001,
         0x8D, 0xAB, 0xCD,
         0x0E, 0xAB, 0xCD,
@@ -543,7 +564,7 @@ fn test_asl_absolute() {
 #[test]
 fn test_asl_absolute_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0011_0001,
         0x8D, 0x34, 0x10,
         0xA2, 0x04,
@@ -556,7 +577,7 @@ fn test_asl_absolute_x() {
 #[test]
 fn test_and_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b1101_1010, 0x29, 0b1101_1010, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0b1101_1010, 0x29, 0b1101_1010, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -564,7 +585,7 @@ fn test_and_negative_flag() {
 #[test]
 fn test_and_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b0101_1010, 0x29, 0b1010_0101, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0b0101_1010, 0x29, 0b1010_0101, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -572,7 +593,7 @@ fn test_and_zero_flag() {
 #[test]
 fn test_bit_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1010_1010,
         0x85, 0x10,
         0xA9, 0b0101_0101,
@@ -581,7 +602,7 @@ fn test_bit_zero_flag() {
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1011_1010,
         0x85, 0x10,
         0xA9, 0b0101_0101,
@@ -594,7 +615,7 @@ fn test_bit_zero_flag() {
 #[test]
 fn test_bit_overflow_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1010_1010,
         0x85, 0x10,
         0xA9, 0b0101_0101,
@@ -612,7 +633,7 @@ fn test_bit_overflow_negative_flag() {
 #[test]
 fn test_clc_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1000_0000,  // LDA
         0x0A,               // ASL
         0x18,               // CLC
@@ -624,7 +645,7 @@ fn test_clc_carry_flag() {
 #[test]
 fn test_cld_decimal_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xF8,   // SED
         0xD8,   // CLD
     ]);
@@ -635,7 +656,7 @@ fn test_cld_decimal_flag() {
 #[test]
 fn test_cli_interrupt_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x78,   // SEI
         0x58,   // CLI
     ]);
@@ -646,7 +667,7 @@ fn test_cli_interrupt_flag() {
 #[test]
 fn test_clv_overflow_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1111_0000,
         0x85, 0x10,
         0xA9, 0b0000_0000,
@@ -660,7 +681,7 @@ fn test_clv_overflow_flag() {
 #[test]
 fn test_cmp_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1111_1111,
         0xC9, 0b0100_0000,
     ]);
@@ -671,7 +692,7 @@ fn test_cmp_negative_flag() {
 #[test]
 fn test_cmp_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0101,
         0x85, 0x09,
         0xA9, 0b0111_0101,
@@ -684,7 +705,7 @@ fn test_cmp_carry_flag() {
 #[test]
 fn test_cmp_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0101,
         0xC9, 0b0101_0101,
     ]);
@@ -696,7 +717,7 @@ fn test_cmp_zero_flag() {
 #[test]
 fn test_cpx_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x45,
         0xE0, 0x45,
     ]);
@@ -708,7 +729,7 @@ fn test_cpx_zero_flag() {
 #[test]
 fn test_cpx_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x68,
         0x85, 0x04,
         0xA2, 0x69,
@@ -722,7 +743,7 @@ fn test_cpx_carry_flag() {
 #[test]
 fn test_cpx_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0100_0000,
         0x8D, 0x12, 0x03,
         0xA2, 0b1100_0000,
@@ -735,7 +756,7 @@ fn test_cpx_negative_flag() {
 #[test]
 fn test_cpy_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x45,
         0xC0, 0x45,
     ]);
@@ -747,7 +768,7 @@ fn test_cpy_zero_flag() {
 #[test]
 fn test_cpy_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x68,
         0x85, 0x04,
         0xA0, 0x69,
@@ -761,7 +782,7 @@ fn test_cpy_carry_flag() {
 #[test]
 fn test_cpy_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0100_0000,
         0x8D, 0x12, 0x04,
         0xA0, 0b1100_0000,
@@ -774,7 +795,7 @@ fn test_cpy_negative_flag() {
 #[test]
 fn test_lsr_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1010_0001,
         0x4A,
     ]);
@@ -785,7 +806,7 @@ fn test_lsr_carry_flag() {
 #[test]
 fn test_lsr_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0001,
         0x4A,
     ]);
@@ -796,7 +817,7 @@ fn test_lsr_zero_flag() {
 #[test]
 fn test_lsr_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0100_0000,
         0x4A,
     ]);
@@ -808,7 +829,7 @@ fn test_lsr_negative_flag() {
 #[test]
 fn test_lsr_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_1011,
         0x4A,
     ]);
@@ -819,7 +840,7 @@ fn test_lsr_implied() {
 #[test]
 fn test_lsr_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_1010,
         0x85, 0x10,
         0x46, 0x10,
@@ -831,7 +852,7 @@ fn test_lsr_zero_page() {
 #[test]
 fn test_lsr_zero_page_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0010_1101,
         0x85, 0x06,
         0xA2, 0x04,
@@ -844,7 +865,7 @@ fn test_lsr_zero_page_x() {
 #[test]
 fn test_lsr_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0011_0001,
         0x8D, 0xAB, 0xCD,
         0x4E, 0xAB, 0xCD,
@@ -856,7 +877,7 @@ fn test_lsr_absolute() {
 #[test]
 fn test_lsr_absolute_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0011_0001,
         0x8D, 0x34, 0x03,
         0xA2, 0x04,
@@ -869,7 +890,7 @@ fn test_lsr_absolute_x() {
 #[test]
 fn test_dec_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x43,
         0x85, 0x22,
         0xC6, 0x22,
@@ -881,7 +902,7 @@ fn test_dec_zero_page() {
 #[test]
 fn test_dec_zero_page_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x43,
         0x85, 0x35,
         0xA2, 0x02,
@@ -894,7 +915,7 @@ fn test_dec_zero_page_x() {
 #[test]
 fn test_dec_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x43,
         0x8D, 0x00, 0x03,
         0xCE, 0x00, 0x03,
@@ -906,7 +927,7 @@ fn test_dec_absolute() {
 #[test]
 fn test_dec_absolute_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x43,
         0x8D, 0x45, 0x03,
         0xA2, 0x05,
@@ -919,7 +940,7 @@ fn test_dec_absolute_x() {
 #[test]
 fn test_dec_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x00,
         0x85, 0x69,
         0xC6, 0x69,
@@ -931,7 +952,7 @@ fn test_dec_negative_flag() {
 #[test]
 fn test_dec_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x01,
         0x85, 0x69,
         0xC6, 0x69,
@@ -943,7 +964,7 @@ fn test_dec_zero_flag() {
 #[test]
 fn test_dex_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x70,
         0xCA,
     ]);
@@ -954,7 +975,7 @@ fn test_dex_implied() {
 #[test]
 fn test_dex_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x01,
         0xCA,
     ]);
@@ -965,7 +986,7 @@ fn test_dex_zero_flag() {
 #[test]
 fn test_dex_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x01,
         0xCA,
         0xCA,
@@ -977,7 +998,7 @@ fn test_dex_negative_flag() {
 #[test]
 fn test_dey_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x70,
         0x88,
     ]);
@@ -988,7 +1009,7 @@ fn test_dey_implied() {
 #[test]
 fn test_dey_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x01,
         0x88,
     ]);
@@ -999,7 +1020,7 @@ fn test_dey_zero_flag() {
 #[test]
 fn test_dey_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x01,
         0x88,
         0x88,
@@ -1011,7 +1032,7 @@ fn test_dey_negative_flag() {
 #[test]
 fn test_eor_immediate() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0110_1001,
         0x49, 0b1111_0000,
     ]);
@@ -1022,7 +1043,7 @@ fn test_eor_immediate() {
 #[test]
 fn test_eor_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1111_1111,
         0x49, 0b1111_1111,
     ]);
@@ -1033,7 +1054,7 @@ fn test_eor_zero_flag() {
 #[test]
 fn test_eor_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1111_1111,
         0x49, 0b0101_1111,
     ]);
@@ -1044,7 +1065,7 @@ fn test_eor_negative_flag() {
 #[test]
 fn test_inc_zero_page() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x41,
         0x85, 0x22,
         0xE6, 0x22,
@@ -1056,7 +1077,7 @@ fn test_inc_zero_page() {
 #[test]
 fn test_inc_zero_page_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x41,
         0x85, 0x35,
         0xA2, 0x02,
@@ -1069,7 +1090,7 @@ fn test_inc_zero_page_x() {
 #[test]
 fn test_inc_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x41,
         0x8D, 0x69, 0x03,
         0xEE, 0x69, 0x03,
@@ -1081,7 +1102,7 @@ fn test_inc_absolute() {
 #[test]
 fn test_inc_absolute_x() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x41,
         0x8D, 0x45, 0x03,
         0xA2, 0x05,
@@ -1094,7 +1115,7 @@ fn test_inc_absolute_x() {
 #[test]
 fn test_inc_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x7F,
         0x85, 0x69,
         0xE6, 0x69,
@@ -1106,7 +1127,7 @@ fn test_inc_negative_flag() {
 #[test]
 fn test_inc_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0xFF,
         0x85, 0x69,
         0xE6, 0x69,
@@ -1118,7 +1139,7 @@ fn test_inc_zero_flag() {
 #[test]
 fn test_inx_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x70,
         0xE8,
     ]);
@@ -1129,7 +1150,7 @@ fn test_inx_implied() {
 #[test]
 fn test_inx_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0xFF,
         0xE8,
     ]);
@@ -1140,7 +1161,7 @@ fn test_inx_zero_flag() {
 #[test]
 fn test_inx_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA2, 0x7F,
         0xE8,
     ]);
@@ -1155,7 +1176,7 @@ fn test_inx_overflow() {
     cpu.load(vec![0xe8, 0xe8, 0x00]);
     cpu.reset();
     cpu.register_x = 0xff;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_x, 1)
 }
@@ -1163,7 +1184,7 @@ fn test_inx_overflow() {
 #[test]
 fn test_iny_implied() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x70,
         0xC8,
     ]);
@@ -1174,7 +1195,7 @@ fn test_iny_implied() {
 #[test]
 fn test_iny_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0xFF,
         0xC8,
     ]);
@@ -1185,7 +1206,7 @@ fn test_iny_zero_flag() {
 #[test]
 fn test_iny_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA0, 0x7F,
         0xC8,
     ]);
@@ -1196,7 +1217,7 @@ fn test_iny_negative_flag() {
 #[test]
 fn test_ora_immediate() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_1010,
         0x09, 0b0010_0101,
     ]);
@@ -1207,7 +1228,7 @@ fn test_ora_immediate() {
 #[test]
 fn test_ora_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0000,
         0x09, 0b0000_0000,
     ]);
@@ -1219,7 +1240,7 @@ fn test_ora_zero_flag() {
 #[test]
 fn test_ora_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0000,
         0x09, 0b1000_1010,
     ]);
@@ -1230,7 +1251,7 @@ fn test_ora_negative_flag() {
 #[test]
 fn test_rol_accumulator() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0111,
         0x2A,
     ]);
@@ -1241,7 +1262,7 @@ fn test_rol_accumulator() {
 #[test]
 fn test_rol_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0111,
         0x8D, 0x38, 0x0D,
         0xA9, 0b0101_0111,
@@ -1254,7 +1275,7 @@ fn test_rol_absolute() {
 #[test]
 fn test_rol_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1100_1100,
         0x2A,
         0x2A,
@@ -1267,7 +1288,7 @@ fn test_rol_carry_flag() {
 #[test]
 fn test_rol_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1000_0000,
         0x2A,
     ]);
@@ -1278,7 +1299,7 @@ fn test_rol_zero_flag() {
 #[test]
 fn test_rol_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b0100_0000, 0x2A]);
+    load_and_run(&mut cpu, vec![0xA9, 0b0100_0000, 0x2A]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -1289,7 +1310,7 @@ fn test_pha_after_reset() {
     cpu.load(vec![0x48, 0x00]);
     cpu.reset();
     cpu.register_a = 0xDE;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.stack_pointer, STACK_RESET - 1);
 
@@ -1304,7 +1325,7 @@ fn test_pha_stack_overflow() {
     cpu.reset();
     cpu.register_a = 0xAD;
     cpu.stack_pointer = 0;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.stack_pointer, 0xFF);
     assert_eq!(cpu.mem_read(0x0100), 0xAD);
@@ -1316,7 +1337,7 @@ fn test_php_push_stack() {
     cpu.load(vec![0x08, 0x00]);
     cpu.reset();
     cpu.status = StatusFlags::from_bits_truncate(0b0101_1010);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.mem_read(0x01fd), 0b0111_1010);
 }
@@ -1328,7 +1349,7 @@ fn test_pla_negative_flag() {
     cpu.reset();
     cpu.mem_write(0x1ff, 0b1010_1111);
     cpu.stack_pointer = 0xfe;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0b1010_1111);
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
@@ -1341,7 +1362,7 @@ fn test_pla_zero_flag() {
     cpu.reset();
     cpu.register_a = 0b1111_1111;
     cpu.mem_write(0x1ff, 0b0000_0000);
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0);
     assert!(cpu.status.contains(StatusFlags::ZERO));
@@ -1355,7 +1376,7 @@ fn test_plp_pull_flags() {
     cpu.status = StatusFlags::empty();
     cpu.mem_write(0x1ff, 0b1111_1111);
     cpu.stack_pointer = 0xfe;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.status.bits(), 0b1110_1111);
 }
@@ -1363,7 +1384,7 @@ fn test_plp_pull_flags() {
 #[test]
 fn test_ror_accumulator() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0111,
         0x6A,
     ]);
@@ -1374,7 +1395,7 @@ fn test_ror_accumulator() {
 #[test]
 fn test_ror_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0111,
         0x8D, 0x38, 0x0D,
         // 0xA9, 0b0101_0111,
@@ -1387,7 +1408,7 @@ fn test_ror_absolute() {
 #[test]
 fn test_ror_carry_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0011,
         0x6A,
         0x6A,
@@ -1400,7 +1421,7 @@ fn test_ror_carry_flag() {
 #[test]
 fn test_ror_carry_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_0111,
         0x8D, 0x38, 0x0D,
         0x6E, 0x38, 0x0D,
@@ -1414,7 +1435,7 @@ fn test_ror_carry_absolute() {
 #[test]
 fn test_ror_zero_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0000_0001,
         0x6A,
     ]);
@@ -1425,7 +1446,7 @@ fn test_ror_zero_flag() {
 #[test]
 fn test_ror_negative_flag() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![0xA9, 0b0000_0001, 0x6A, 0x6A, 0x00]);
+    load_and_run(&mut cpu, vec![0xA9, 0b0000_0001, 0x6A, 0x6A, 0x00]);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -1433,146 +1454,146 @@ fn test_ror_negative_flag() {
 #[test]
 fn test_bcc_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x18,       // CLC
         0x90, 0x06, // BCC 0x10
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800A);
+    assert_eq!(cpu.program_counter, 0x8009);
 }
 
 #[test]
 fn test_bcc_dont_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x38,       // SEC
         0x90, 0x06, // BCC 0x10
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8004);
+    assert_eq!(cpu.program_counter, 0x8003);
 }
 
 #[test]
 fn test_bcs_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x38,       // SEC
         0xB0, 0x06, // BCC 0x10
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800A);
+    assert_eq!(cpu.program_counter, 0x8009);
 }
 
 #[test]
 fn test_bcs_dont_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x18,       // CLC
         0xB0, 0x06, // BCC 0x10
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8004);
+    assert_eq!(cpu.program_counter, 0x8003);
 }
 
 #[test]
 fn test_bmi_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1100_0000,
         0x30, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800B);
+    assert_eq!(cpu.program_counter, 0x800A);
 }
 
 #[test]
 fn test_bmi_dont_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0111_1111,
         0x30, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8005);
+    assert_eq!(cpu.program_counter, 0x8004);
 }
 
 #[test]
 fn test_beq_branch() {
     let mut cpu: CPU = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x00,
         0xF0, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800B);
+    assert_eq!(cpu.program_counter, 0x800A);
 }
 
 #[test]
 fn test_beq_dont_branch() {
     let mut cpu: CPU = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x69,
         0xF0, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8005);
+    assert_eq!(cpu.program_counter, 0x8004);
 }
 
 #[test]
 fn test_bne_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0x69,
         0xD0, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800B);
+    assert_eq!(cpu.program_counter, 0x800A);
 }
 
 #[test]
 fn test_bne_dont_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0,
         0xD0, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8005);
+    assert_eq!(cpu.program_counter, 0x8004);
 }
 
 #[test]
 fn test_bpl_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b0101_1010,
         0x10, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x800B);
+    assert_eq!(cpu.program_counter, 0x800A);
 }
 
 #[test]
 fn test_bpl_dont_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0xA9, 0b1101_1010,
         0x10, 0x06,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x8005);
+    assert_eq!(cpu.program_counter, 0x8004);
 }
 
 #[test]
 fn test_bvc_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x50, 0x06,
     ]);
     cpu.reset();
     cpu.status.set(StatusFlags::OVERFLOW, false);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x8009);
+    assert_eq!(cpu.program_counter, 0x8008);
 }
 
 #[test]
@@ -1583,22 +1604,22 @@ fn test_bvc_dont_branch() {
     ]);
     cpu.reset();
     cpu.status.set(StatusFlags::OVERFLOW, true);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x8003);
+    assert_eq!(cpu.program_counter, 0x8002);
 }
 
 #[test]
 fn test_bvs_branch() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x70, 0x06,
     ]);
     cpu.reset();
     cpu.status.set(StatusFlags::OVERFLOW, true);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x8009);
+    assert_eq!(cpu.program_counter, 0x8008);
 }
 
 #[test]
@@ -1609,22 +1630,20 @@ fn test_bvs_dont_branch() {
     ]);
     cpu.reset();
     cpu.status.set(StatusFlags::OVERFLOW, false);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x8003);
+    assert_eq!(cpu.program_counter, 0x8002);
 }
 
 #[test]
 fn test_jmp_absolute() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x4C, 0x69, 0x80,
         0x00,
     ]);
 
-    // PC is incremented once when 0x00 is read, so check
-    // is for 1 higher than expected from JMP
-    assert_eq!(cpu.program_counter, 0x806A);
+    assert_eq!(cpu.program_counter, 0x8069);
 }
 
 #[test]
@@ -1636,26 +1655,49 @@ fn test_jmp_indirect() {
     ]);
     cpu.reset();
     cpu.mem_write_u16(0x8100, 0x1234);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x1235);
+    assert_eq!(cpu.program_counter, 0x1234);
+}
+
+fn jmp_page_boundary(variant: CpuVariant) -> CPU {
+    let mut cpu = new_cpu();
+    cpu.variant = variant;
+    cpu.load(vec![0x6C, 0xFF, 0x81]);
+    cpu.reset();
+    cpu.mem_write(0x81FF, 0x34);
+    cpu.mem_write(0x8100, 0x12);
+    cpu.mem_write(0x8200, 0x56);
+    cpu
+}
+
+#[test]
+fn test_jmp_indirect_page_boundary() {
+    // the high byte comes from the start of the same page
+    let mut cpu = jmp_page_boundary(CpuVariant::NMOS6502);
+    assert_eq!(cpu.step().cycles, 5);
+    assert_eq!(cpu.program_counter, 0x1234);
+
+    let mut cpu = jmp_page_boundary(CpuVariant::WDC65C02);
+    assert_eq!(cpu.step().cycles, 6);
+    assert_eq!(cpu.program_counter, 0x5634);
 }
 
 #[test]
 fn test_jsr_pc() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
-        0x20, 0x02, 0x40,
+    load_and_run(&mut cpu, vec![
+        0x20, 0x00, 0x02,
         0x00,
     ]);
 
-    assert_eq!(cpu.program_counter, 0x4003);
+    assert_eq!(cpu.program_counter, 0x0200);
 }
 
 #[test]
 fn test_jsr_stack() {
     let mut cpu = new_cpu();
-    cpu.load_and_run(vec![
+    load_and_run(&mut cpu, vec![
         0x20, 0x02, 0x40,
         0x00,
     ]);
@@ -1672,9 +1714,24 @@ fn test_rts_pc() {
     ]);
     cpu.reset();
     cpu.mem_write(0x8102, 0x60);
-    cpu.run();
+    run(&mut cpu);
 
-    assert_eq!(cpu.program_counter, 0x8004);
+    assert_eq!(cpu.program_counter, 0x8003);
+}
+
+#[test]
+fn test_rts_to_ffff_wraps() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![0x60]); // RTS
+    cpu.reset();
+    cpu.mem_write(0x01FF, 0xFF);
+    cpu.mem_write(0x01FE, 0xFF);
+    cpu.stack_pointer = 0xFD;
+
+    let step = cpu.step();
+    assert_eq!(step.fault, None);
+    assert_eq!(step.cycles, 6);
+    assert_eq!(cpu.program_counter, 0x0000);
 }
 
 #[test]
@@ -1686,7 +1743,7 @@ fn test_adc_carry() {
     ]);
     cpu.reset();
     cpu.register_a = 0x01;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::CARRY));
 }
@@ -1698,7 +1755,7 @@ fn test_adc_zero() {
         0x69, 0x00,
     ]);
     cpu.reset();
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -1710,7 +1767,7 @@ fn test_adc_negative() {
         0x69, 0x80,
     ]);
     cpu.reset();
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::NEGATIVE));
 }
@@ -1723,7 +1780,7 @@ fn test_adc_overflow() {
     ]);
     cpu.reset();
     cpu.register_a = 0b0100_0000;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::OVERFLOW));
 
@@ -1732,7 +1789,7 @@ fn test_adc_overflow() {
     ]);
     cpu.reset();
     cpu.status.set(StatusFlags::CARRY, true);
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::OVERFLOW));
 }
@@ -1740,14 +1797,14 @@ fn test_adc_overflow() {
 #[test]
 fn test_adc_decimal_mode() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0xF8, // SED
         0x69, 0x19,
     ]);
     cpu.reset();
     cpu.register_a = 0x28;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x47);
 }
@@ -1755,7 +1812,7 @@ fn test_adc_decimal_mode() {
 #[test]
 fn test_adc_decimal_carry() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     // 0x58 + 0x46 + 0x01 (carry) = 105 (0x05 + carry)
     cpu.load(vec![
         0xF8, // SED
@@ -1764,7 +1821,7 @@ fn test_adc_decimal_carry() {
     ]);
     cpu.reset();
     cpu.register_a = 0x46;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x05);
     assert!(cpu.status.contains(StatusFlags::CARRY));
@@ -1773,14 +1830,14 @@ fn test_adc_decimal_carry() {
 #[test]
 fn test_adc_decimal_add_81_and_92() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0xF8,
         0x69, 0x92,
     ]);
     cpu.reset();
     cpu.register_a = 0x81;
-    cpu.run();
+    run(&mut cpu);
 
     println!("reg a: {}", cpu.register_a);
     assert_eq!(cpu.register_a, 0x73);
@@ -1791,14 +1848,14 @@ fn test_adc_decimal_add_81_and_92() {
 #[test]
 fn test_adc_decimal_add_zero() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0xF8,
         0x69, 0x00,
     ]);
     cpu.reset();
     cpu.register_a = 0x10;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x10);
 }
@@ -1806,14 +1863,14 @@ fn test_adc_decimal_add_zero() {
 #[test]
 fn test_adc_decimal_9_plus_11() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0xF8,
         0x69, 0x11,
     ]);
     cpu.reset();
     cpu.register_a = 0x09;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x20);
 }
@@ -1827,7 +1884,7 @@ fn test_sbc_zero() {
     ]);
     cpu.reset();
     cpu.register_a = 0x0A;
-    cpu.run();
+    run(&mut cpu);
 
     assert!(cpu.status.contains(StatusFlags::ZERO));
 }
@@ -1841,7 +1898,7 @@ fn test_sbc() {
     ]);
     cpu.reset();
     cpu.register_a = 0x29;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x02);
 }
@@ -1849,7 +1906,7 @@ fn test_sbc() {
 #[test]
 fn test_sbc_decimal_mode() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0x38, 0xB8,
         0xF8,
@@ -1857,7 +1914,7 @@ fn test_sbc_decimal_mode() {
     ]);
     cpu.reset();
     cpu.register_a = 0x99;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x96);
 }
@@ -1865,7 +1922,7 @@ fn test_sbc_decimal_mode() {
 #[test]
 fn test_sbc_decimal_mode_wrap() {
     let mut cpu = new_cpu();
-    cpu.enable_decimal = true;
+    cpu.variant = CpuVariant::NMOS6502;
     cpu.load(vec![
         0x38, 0xB8,
         0xF8,
@@ -1873,11 +1930,116 @@ fn test_sbc_decimal_mode_wrap() {
     ]);
     cpu.reset();
     cpu.register_a = 0x50;
-    cpu.run();
+    run(&mut cpu);
 
     assert_eq!(cpu.register_a, 0x26);
 }
 
+#[test]
+fn test_2a03_has_no_decimal_mode() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xF8,
+        0x69, 0x19,
+    ]);
+    cpu.reset();
+    cpu.register_a = 0x28;
+    run(&mut cpu);
+
+    assert!(cpu.status.contains(StatusFlags::DECIMAL_MODE));
+    assert_eq!(cpu.register_a, 0x41);
+}
+
+#[test]
+fn test_adc_decimal_high_nibble() {
+    let mut cpu = new_cpu();
+    cpu.variant = CpuVariant::NMOS6502;
+    cpu.load(vec![
+        0xF8,
+        0x69, 0x50,
+    ]);
+    cpu.reset();
+    cpu.register_a = 0x45;
+    run(&mut cpu);
+
+    assert_eq!(cpu.register_a, 0x95);
+    assert!(!cpu.status.contains(StatusFlags::CARRY));
+}
+
+fn decimal_99_plus_1(variant: CpuVariant) -> CPU {
+    let mut cpu = new_cpu();
+    cpu.variant = variant;
+    cpu.load(vec![
+        0xF8,
+        0x69, 0x01,
+    ]);
+    cpu.reset();
+    cpu.register_a = 0x99;
+    run(&mut cpu);
+    cpu
+}
+
+#[test]
+fn test_adc_decimal_flags() {
+    // N from the uncorrected $A0, Z from the binary $9A
+    let cpu = decimal_99_plus_1(CpuVariant::NMOS6502);
+    assert_eq!(cpu.register_a, 0x00);
+    assert!(cpu.status.contains(StatusFlags::CARRY));
+    assert!(cpu.status.contains(StatusFlags::NEGATIVE));
+    assert!(!cpu.status.contains(StatusFlags::ZERO));
+
+    let cpu = decimal_99_plus_1(CpuVariant::WDC65C02);
+    assert_eq!(cpu.register_a, 0x00);
+    assert!(cpu.status.contains(StatusFlags::CARRY));
+    assert!(!cpu.status.contains(StatusFlags::NEGATIVE));
+    assert!(cpu.status.contains(StatusFlags::ZERO));
+}
+
+#[test]
+fn test_65c02_decimal_cycle() {
+    for (variant, cycles) in [(CpuVariant::NMOS6502, 2), (CpuVariant::WDC65C02, 3)] {
+        let mut cpu = new_cpu();
+        cpu.variant = variant;
+        cpu.load(vec![
+            0xF8,
+            0x69, 0x01,
+            0xE9, 0x01,
+            0xD8,
+            0x69, 0x01,
+        ]);
+        cpu.reset();
+        cpu.step();
+
+        assert_eq!(cpu.step().cycles, cycles, "{:?}", variant);
+        assert_eq!(cpu.step().cycles, cycles, "{:?}", variant);
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 2, "{:?}", variant);
+    }
+}
+
+#[test]
+fn test_sbc_decimal_borrow() {
+    for variant in [CpuVariant::NMOS6502, CpuVariant::WDC65C02] {
+        let mut cpu = new_cpu();
+        cpu.variant = variant;
+        cpu.load(vec![
+            0x38, 0xF8,
+            0xE9, 0x03, // 99 - 3, no borrow
+            0x85, 0x10,
+            0xA9, 0x00,
+            0xE9, 0x01, // 0 - 1 wraps to 99
+        ]);
+        cpu.reset();
+        cpu.register_a = 0x99;
+        run(&mut cpu);
+
+        assert_eq!(cpu.mem_read(0x10), 0x96);
+        assert_eq!(cpu.register_a, 0x99);
+        assert!(!cpu.status.contains(StatusFlags::CARRY));
+        assert!(cpu.status.contains(StatusFlags::NEGATIVE));
+    }
+}
+
 #[test]
 fn test_rti() {
     let mut cpu = new_cpu();
@@ -1891,11 +2053,729 @@ fn test_rti() {
         0x40,       // RTI
     ]);
     cpu.reset();
-    cpu.run();
+    run(&mut cpu);
 
     // TODO: I'm not sure about the behavior of bits
     // 4 & 5 when pulled off the stack here.
     assert_eq!(cpu.status.bits(), 0b1110_1111);
-    assert_eq!(cpu.program_counter, u16::from_le_bytes([0xBB, 0xCC]) + 1);
+    assert_eq!(cpu.program_counter, u16::from_le_bytes([0xBB, 0xCC]));
+}
+
+
+#[test]
+fn test_vblank_nmi() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xA9, 0x80,         // LDA #$80
+        0x8D, 0x00, 0x20,   // STA $2000
+        0x4C, 0x05, 0x80,   // JMP $8005
+    ]);
+    cpu.mem_write(0x9000, 0x00); // BRK
+    cpu.mem_write_u16(0xFFFA, 0x9000);
+    cpu.reset();
+    run(&mut cpu);
+
+    assert_eq!(cpu.program_counter, 0x9000);
+    assert!(cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
+
+    // the interrupted JMP is on the stack, with B clear
+    assert_eq!(cpu.mem_read(0x01FD), 0x80);
+    assert_eq!(cpu.mem_read(0x01FC), 0x05);
+    assert_eq!(cpu.mem_read(0x01FB) & 0b0011_0000, 0b0010_0000);
+}
+
+#[test]
+fn test_mapper_irq() {
+    let mut rom = Rom::blank();
+    rom.mapper = 4;
+    rom.prg_rom = vec![0; 0x8000];
+    let mut bus = Bus::new(rom).unwrap();
+    bus.allow_rom_writes = true;
+    let mut cpu = CPU::new(bus);
+
+    cpu.load(vec![
+        0xA9, 0x01,         // LDA #$01
+        0x8D, 0x00, 0xC0,   // STA $C000
+        0x8D, 0x01, 0xC0,   // STA $C001
+        0x8D, 0x01, 0xE0,   // STA $E001
+        0xA9, 0x08,         // LDA #$08
+        0x8D, 0x00, 0x20,   // STA $2000
+        0xA9, 0x18,         // LDA #$18
+        0x8D, 0x01, 0x20,   // STA $2001
+        0x58,               // CLI
+        0x4C, 0x16, 0x80,   // JMP $8016
+    ]);
+    cpu.mem_write(0x9000, 0x00); // BRK
+    cpu.mem_write_u16(0xFFFE, 0x9000);
+    // from here on writes to $8000-$FFFF go to the MMC3 registers
+    cpu.bus.allow_rom_writes = false;
+    cpu.reset();
+    run(&mut cpu);
+
+    assert_eq!(cpu.program_counter, 0x9000);
+    assert!(cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
+    assert_eq!(cpu.mem_read(0x01FD), 0x80);
+    assert_eq!(cpu.mem_read(0x01FC), 0x16);
+    assert_eq!(cpu.mem_read(0x01FB) & 0b0011_0000, 0b0010_0000);
+}
+
+#[test]
+fn test_brk() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0x00, 0xFF, // BRK and its padding byte
+        0xE8,       // INX
+    ]);
+    cpu.mem_write(0x9000, 0x40); // RTI
+    cpu.mem_write_u16(0xFFFE, 0x9000);
+    cpu.reset();
+    cpu.status.remove(StatusFlags::INTERRUPT_DISABLE);
+
+    assert_eq!(cpu.step().cycles, 7);
+    assert_eq!(cpu.program_counter, 0x9000);
+    assert!(cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
+
+    // PC+2 goes on the stack, with B set
+    assert_eq!(cpu.mem_read(0x01FD), 0x80);
+    assert_eq!(cpu.mem_read(0x01FC), 0x02);
+    assert_eq!(cpu.mem_read(0x01FB) & 0b0011_0000, 0b0011_0000);
+
+    // and the handler returns past the padding
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.register_x, 1);
+    assert!(!cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
+}
+
+// JMP $8000 forever, with an IRQ handler at $9000 that counts in $10
+fn irq_cpu(handler: Vec<u8>) -> CPU {
+    let mut cpu = new_cpu();
+    cpu.load(vec![0x4C, 0x00, 0x80]);
+    cpu.load_at(handler, 0x9000);
+    cpu.mem_write_u16(0xFFFC, 0x8000);
+    cpu.mem_write_u16(0xFFFE, 0x9000);
+    cpu.reset();
+
+    while cpu.bus.irq_line().is_empty() {
+        cpu.step();
+    }
+    assert_eq!(cpu.bus.irq_line(), IrqSource::FRAME_COUNTER);
+    cpu
+}
+
+#[test]
+fn test_irq_respects_interrupt_disable() {
+    let mut cpu = irq_cpu(vec![
+        0xE6, 0x10,         // INC $10
+        0xAD, 0x15, 0x40,   // LDA $4015
+        0x40,               // RTI
+    ]);
+
+    // I is set out of reset
+    for _ in 0..10 {
+        cpu.step();
+    }
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.mem_read(0x10), 0);
+
+    // the line is polled during an instruction, so the JMP that sees I clear
+    // goes first
+    cpu.status.remove(StatusFlags::INTERRUPT_DISABLE);
+    assert_eq!(cpu.step().opcode, Some(0x4C));
+    let step = cpu.step();
+    assert_eq!(step.interrupt, Some(Interrupt::IRQ));
+    assert_eq!(step.opcode, None);
+    assert_eq!(step.cycles, 7);
+    assert_eq!(cpu.program_counter, 0x9000);
+
+    cpu.step();
+    assert_eq!(cpu.program_counter, 0x9002);
+    assert_eq!(cpu.mem_read(0x10), 1);
+    assert_eq!(cpu.mem_read(0x01FB) & 0b0011_0000, 0b0010_0000);
+
+    // reading $4015 acknowledges it, so it's only taken the once
+    for _ in 0..10 {
+        cpu.step();
+    }
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.mem_read(0x10), 1);
+}
+
+#[test]
+fn test_irq_is_level_triggered() {
+    let mut cpu = irq_cpu(vec![
+        0xE6, 0x10, // INC $10
+        0x40,       // RTI
+    ]);
+    cpu.status.remove(StatusFlags::INTERRUPT_DISABLE);
+
+    // nothing acknowledges it, so it's taken again as soon as RTI clears I,
+    // after the JMP that first sees it
+    for _ in 0..9 {
+        cpu.step();
+    }
+    assert_eq!(cpu.mem_read(0x10), 3);
+    assert!(cpu.status.contains(StatusFlags::INTERRUPT_DISABLE));
+}
+
+#[test]
+fn test_stop() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![0xE8, 0x4C, 0x00, 0x80]); // INX, JMP $8000
+    cpu.reset();
+
+    cpu.run_with_callback(|cpu| {
+        if cpu.register_x == 5 {
+            cpu.stop();
+        }
+    }).unwrap();
+    assert_eq!(cpu.register_x, 5);
+    assert_eq!(cpu.program_counter, 0x8001);
+
+    // it only stops the once
+    cpu.stop();
+    assert_eq!(cpu.run_frame(), Ok(false));
+    assert_eq!(cpu.run_frame(), Ok(true));
+}
+
+#[test]
+fn test_step() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xA2, 0x01,         // LDX #$01
+        0xBD, 0xFF, 0x00,   // LDA $00FF,X
+    ]);
+    cpu.reset();
+
+    let step = cpu.step();
+    assert_eq!(step, StepResult {
+        opcode: Some(0xA2),
+        cycles: 2,
+        interrupt: None,
+        fault: None,
+    });
+
+    // with the extra cycle for crossing a page
+    let step = cpu.step();
+    assert_eq!(step.opcode, Some(0xBD));
+    assert_eq!(step.cycles, 5);
+    assert_eq!(cpu.program_counter, 0x8005);
+}
+
+#[test]
+fn test_opcode_at_ffff_wraps() {
+    let mut cpu = new_cpu();
+    cpu.mem_write(0xFFFF, 0xE8); // INX
+    cpu.program_counter = 0xFFFF;
+
+    let step = cpu.step();
+    assert_eq!(step.fault, None);
+    assert_eq!(cpu.register_x, 1);
+    assert_eq!(cpu.program_counter, 0x0000);
+
+    // and an operand that wraps round to the zero page
+    cpu.mem_write(0xFFFF, 0xA9); // LDA #$42
+    cpu.mem_write(0x0000, 0x42);
+    cpu.program_counter = 0xFFFF;
+
+    let step = cpu.step();
+    assert_eq!(step.fault, None);
+    assert_eq!(cpu.register_a, 0x42);
+    assert_eq!(cpu.program_counter, 0x0001);
+}
+
+/*
+ * Each opcode on its own in RAM, with all its operands zero so nothing
+ * crosses a page. Since every cycle is a bus access, this checks the accesses
+ * each instruction makes add up to what the opcode table says.
+ */
+fn check_cycles(variant: CpuVariant) {
+    for (&code, opcode) in variant.opcodes() {
+        if opcode.mnemonic == "HLT" {
+            continue;
+        }
+
+        let mut cpu = new_cpu();
+        cpu.variant = variant;
+        cpu.reset();
+        cpu.program_counter = 0x0200;
+        cpu.mem_write(0x0200, code);
+
+        // with the flags as they come out of reset, and BBR finding bit
+        // clear in the zero page
+        let taken = matches!(code, 0x90 | 0xD0 | 0x10 | 0x50 | 0x80)
+            || (variant.is_cmos() && code & 0x8F == 0x0F);
+        let expected = match opcode.cycles {
+            CycleBehavior::Constant(i) | CycleBehavior::PageCross(i) => i,
+            CycleBehavior::Branch(i) => i + taken as u8,
+        };
+        assert_eq!(cpu.step().cycles, expected as usize, "{:?} {:02X} {}", variant, code, opcode.mnemonic);
+    }
+}
+
+#[test]
+fn test_cycles_match_opcode_table() {
+    check_cycles(CpuVariant::NMOS6502);
+    check_cycles(CpuVariant::WDC65C02);
+}
+
+#[test]
+fn test_branch_cycles() {
+    let mut cpu = new_cpu();
+    cpu.reset();
+    cpu.program_counter = 0x02FC;
+    cpu.mem_write(0x02FC, 0xD0); // BNE +$10, onto the next page
+    cpu.mem_write(0x02FD, 0x10);
+    cpu.mem_write(0x030E, 0xF0); // BEQ, not taken
+    assert_eq!(cpu.step().cycles, 4);
+    assert_eq!(cpu.program_counter, 0x030E);
+    assert_eq!(cpu.step().cycles, 2);
 }
 
+fn joypad_cpu(program: Vec<u8>) -> CPU {
+    let mut cpu = new_cpu();
+    cpu.bus.joypad1.set_button_pressed_status(JoypadButton::BUTTON_B, true);
+    cpu.mem_write(0x4016, 1);
+    cpu.mem_write(0x4016, 0);
+    load_and_run(&mut cpu, program);
+    cpu
+}
+
+#[test]
+fn test_page_cross_dummy_read() {
+    // $40FF,X first reads $4016 before the high byte is fixed, which shifts A
+    // out of the controller so the next read gets B
+    let cpu = joypad_cpu(vec![
+        0xA2, 0x17,         // LDX #$17
+        0xBD, 0xFF, 0x40,   // LDA $40FF,X
+        0xAD, 0x16, 0x40,   // LDA $4016
+        0x00,
+    ]);
+    assert_eq!(cpu.register_a & 1, 1);
+
+    // without crossing a page there's only the one read
+    let cpu = joypad_cpu(vec![
+        0xA2, 0x01,         // LDX #$01
+        0xBD, 0x15, 0x40,   // LDA $4015,X
+        0xAD, 0x16, 0x40,   // LDA $4016
+        0x00,
+    ]);
+    assert_eq!(cpu.register_a & 1, 1);
+
+    // and a store always makes it
+    let cpu = joypad_cpu(vec![
+        0xA2, 0x17,         // LDX #$17
+        0x9D, 0xFF, 0x40,   // STA $40FF,X
+        0xAD, 0x16, 0x40,   // LDA $4016
+        0x00,
+    ]);
+    assert_eq!(cpu.register_a & 1, 1);
+}
+
+#[test]
+fn test_read_modify_write_writes_twice() {
+    // both writes to $4014 start an OAM DMA
+    let mut cpu = new_cpu();
+    cpu.load(vec![0xEE, 0x14, 0x40]); // INC $4014
+    cpu.reset();
+    let step = cpu.step();
+    assert!(step.cycles >= 6 + 2 * 513, "{}", step.cycles);
+
+    // the 65C02 reads it again instead
+    let mut cpu = new_65c02();
+    cpu.load(vec![0xEE, 0x14, 0x40]);
+    cpu.reset();
+    let step = cpu.step();
+    assert!(step.cycles <= 6 + 514, "{}", step.cycles);
+}
+
+#[test]
+fn test_step_nmi() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xA9, 0x80,         // LDA #$80
+        0x8D, 0x00, 0x20,   // STA $2000
+        0x4C, 0x05, 0x80,   // JMP $8005
+    ]);
+    cpu.mem_write_u16(0xFFFA, 0x9000);
+    cpu.reset();
+
+    let mut step = cpu.step();
+    while step.interrupt.is_none() {
+        assert!(step.opcode.is_some());
+        step = cpu.step();
+    }
+
+    assert_eq!(step.interrupt, Some(Interrupt::NMI));
+    assert_eq!(step.opcode, None);
+    assert_eq!(step.cycles, 7);
+    assert_eq!(cpu.program_counter, 0x9000);
+}
+
+#[test]
+fn test_step_jammed() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![0xE8, 0x02]); // INX, HLT
+    cpu.reset();
+    cpu.step();
+
+    let jammed = EmulatorError::Jammed { opcode: 0x02, pc: 0x8001 };
+    let step = cpu.step();
+    assert_eq!(step.opcode, Some(0x02));
+    assert_eq!(step.fault, Some(jammed));
+    assert!(cpu.is_jammed());
+
+    // and it stays stuck on the HLT, doing nothing at all
+    let step = cpu.step();
+    assert_eq!(step.opcode, None);
+    assert_eq!(step.cycles, 0);
+    assert_eq!(step.fault, Some(jammed));
+    assert_eq!(cpu.run(), Err(jammed));
+    assert_eq!(cpu.run_frame(), Err(jammed));
+    assert_eq!(cpu.program_counter, 0x8001);
+    assert_eq!(cpu.register_x, 1);
+
+    // until it's reset
+    cpu.reset();
+    assert!(!cpu.is_jammed());
+    assert_eq!(cpu.step().fault, None);
+    assert_eq!(cpu.register_x, 1);
+}
+
+#[test]
+fn test_write_only_read() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![0xAD, 0x00, 0x20]); // LDA $2000
+    cpu.reset();
+
+    // the high byte of the address is still on the bus
+    let step = cpu.step();
+    assert_eq!(step.fault, Some(EmulatorError::WriteOnlyRead { addr: 0x2000 }));
+    assert_eq!(cpu.register_a, 0x20);
+    assert_eq!(cpu.program_counter, 0x8003);
+
+    // and it's only reported the once
+    assert_eq!(cpu.step().fault, None);
+}
+
+#[test]
+fn test_run_carries_on_past_diagnostics() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xAD, 0x00, 0x20,   // LDA $2000
+        0xAD, 0x01, 0x20,   // LDA $2001
+        0xE8,               // INX
+        0x00,
+    ]);
+    cpu.reset();
+
+    assert_eq!(cpu.run_with_callback(stop_on_brk), Ok(()));
+    assert_eq!(cpu.register_x, 1);
+    assert_eq!(cpu.take_diagnostic(), Some(EmulatorError::WriteOnlyRead { addr: 0x2000 }));
+    assert_eq!(cpu.take_diagnostic(), None);
+}
+
+#[test]
+fn test_dummy_read_is_not_a_fault() {
+    let mut cpu = new_cpu();
+    cpu.load(vec![
+        0xA2, 0x10,         // LDX #$10
+        0x9D, 0xF0, 0x20,   // STA $20F0,X, reads $2000 first
+    ]);
+    cpu.reset();
+
+    cpu.step();
+    assert_eq!(cpu.step().fault, None);
+}
+
+fn new_65c02() -> CPU {
+    let mut cpu = new_cpu();
+    cpu.variant = CpuVariant::WDC65C02;
+    cpu
+}
+
+#[test]
+fn test_65c02_bra() {
+    let mut cpu = new_65c02();
+    load_and_run(&mut cpu, vec![
+        0x80, 0x02, // BRA +2
+        0xE8, 0xE8,
+        0xC8,
+        0x00,
+    ]);
+
+    assert_eq!(cpu.register_x, 0);
+    assert_eq!(cpu.register_y, 1);
+}
+
+#[test]
+fn test_65c02_push_pull_index_registers() {
+    let mut cpu = new_65c02();
+    load_and_run(&mut cpu, vec![
+        0xA2, 0x42, // LDX #$42
+        0xDA,       // PHX
+        0xA0, 0x80, // LDY #$80
+        0x5A,       // PHY
+        0xFA,       // PLX
+        0x7A,       // PLY
+        0x00,
+    ]);
+
+    assert_eq!(cpu.register_x, 0x80);
+    assert_eq!(cpu.register_y, 0x42);
+    assert!(!cpu.status.contains(StatusFlags::NEGATIVE));
+    assert_eq!(cpu.stack_pointer, STACK_RESET);
+}
+
+#[test]
+fn test_65c02_stz() {
+    let mut cpu = new_65c02();
+    cpu.mem_write(0x10, 0xFF);
+    cpu.mem_write(0x15, 0xFF);
+    cpu.mem_write(0x0300, 0xFF);
+    load_and_run(&mut cpu, vec![
+        0xA2, 0x05,
+        0x64, 0x10,         // STZ $10
+        0x74, 0x10,         // STZ $10,X
+        0x9C, 0x00, 0x03,   // STZ $0300
+        0x00,
+    ]);
+
+    assert_eq!(cpu.mem_read(0x10), 0);
+    assert_eq!(cpu.mem_read(0x15), 0);
+    assert_eq!(cpu.mem_read(0x0300), 0);
+}
+
+#[test]
+fn test_65c02_trb_tsb() {
+    let mut cpu = new_65c02();
+    cpu.mem_write(0x10, 0x3C);
+    cpu.mem_write(0x11, 0xF0);
+    cpu.load(vec![
+        0xA9, 0x0F,
+        0x14, 0x10, // TRB $10
+    ]);
+    cpu.reset();
+    run(&mut cpu);
+
+    assert_eq!(cpu.mem_read(0x10), 0x30);
+    assert!(!cpu.status.contains(StatusFlags::ZERO));
+
+    load_and_run(&mut cpu, vec![
+        0xA9, 0x0F,
+        0x04, 0x11, // TSB $11
+    ]);
+
+    assert_eq!(cpu.mem_read(0x11), 0xFF);
+    assert!(cpu.status.contains(StatusFlags::ZERO));
+    assert_eq!(cpu.register_a, 0x0F);
+}
+
+#[test]
+fn test_65c02_zero_page_indirect() {
+    let mut cpu = new_65c02();
+    cpu.mem_write_u16(0x20, 0x0400);
+    cpu.mem_write_u16(0x22, 0x0500);
+    cpu.mem_write(0x0400, 0x99);
+    load_and_run(&mut cpu, vec![
+        0xA0, 0x10,
+        0xB2, 0x20, // LDA ($20)
+        0x92, 0x22, // STA ($22)
+        0x00,
+    ]);
+
+    assert_eq!(cpu.register_a, 0x99);
+    assert_eq!(cpu.mem_read(0x0500), 0x99);
+}
+
+#[test]
+fn test_65c02_bit_immediate() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![0x89, 0xC0]);
+    cpu.reset();
+    cpu.register_a = 0x01;
+    cpu.status.insert(StatusFlags::OVERFLOW);
+    run(&mut cpu);
+
+    assert!(cpu.status.contains(StatusFlags::ZERO));
+    assert!(cpu.status.contains(StatusFlags::OVERFLOW));
+    assert!(!cpu.status.contains(StatusFlags::NEGATIVE));
+}
+
+#[test]
+fn test_65c02_inc_dec_accumulator() {
+    let mut cpu = new_65c02();
+    load_and_run(&mut cpu, vec![
+        0x1A, 0x1A, 0x1A,   // INC A
+        0x3A,               // DEC A
+        0x00,
+    ]);
+
+    assert_eq!(cpu.register_a, 2);
+}
+
+#[test]
+fn test_65c02_jmp_indexed_indirect() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![0x7C, 0x00, 0x81]);
+    cpu.reset();
+    cpu.register_x = 4;
+    cpu.mem_write_u16(0x8104, 0x1234);
+
+    assert_eq!(cpu.step().cycles, 6);
+    assert_eq!(cpu.program_counter, 0x1234);
+}
+
+#[test]
+fn test_65c02_interrupt_clears_decimal() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![0x00, 0xFF]);
+    cpu.mem_write_u16(0xFFFE, 0x9000);
+    cpu.reset();
+    cpu.status.insert(StatusFlags::DECIMAL_MODE);
+    cpu.step();
+
+    assert!(!cpu.status.contains(StatusFlags::DECIMAL_MODE));
+    // the handler still gets to see where it was
+    assert_eq!(cpu.mem_read(0x01FB) & 0b0000_1000, 0b0000_1000);
+}
+
+#[test]
+fn test_65c02_has_no_undocumented_opcodes() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![
+        0xA3,               // LAX on the NMOS chip
+        0x02, 0x10,         // HLT
+        0x5C, 0x34, 0x12,
+        0xEA,
+    ]);
+    cpu.reset();
+
+    // they're all NOPs, some of them a single cycle
+    let step = cpu.step();
+    assert_eq!(step.fault, None);
+    assert_eq!(step.cycles, 1);
+    assert_eq!(cpu.program_counter, 0x8001);
+    assert_eq!(cpu.register_a, 0);
+
+    assert_eq!(cpu.step().cycles, 2);
+    assert!(!cpu.is_jammed());
+    assert_eq!(cpu.step().cycles, 8);
+    assert_eq!(cpu.program_counter, 0x8006);
+}
+
+#[test]
+fn test_65c02_rmb_smb() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![
+        0x77, 0x10, // RMB7 $10
+        0x87, 0x10, // SMB0 $10
+    ]);
+    cpu.reset();
+    cpu.mem_write(0x10, 0x80);
+
+    assert_eq!(cpu.step().cycles, 5);
+    assert_eq!(cpu.mem_read(0x10), 0x00);
+    assert_eq!(cpu.step().cycles, 5);
+    assert_eq!(cpu.mem_read(0x10), 0x01);
+    assert_eq!(cpu.program_counter, 0x8004);
+}
+
+#[test]
+fn test_65c02_bbr_bbs() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![
+        0x0F, 0x10, 0x05,   // BBR0 $10,+5
+        0x8F, 0x10, 0x05,   // BBS0 $10,+5
+    ]);
+    cpu.reset();
+    cpu.mem_write(0x10, 0x01);
+
+    // bit 0 is set, so only BBS branches
+    assert_eq!(cpu.step().cycles, 5);
+    assert_eq!(cpu.program_counter, 0x8003);
+    assert_eq!(cpu.step().cycles, 6);
+    assert_eq!(cpu.program_counter, 0x800B);
+}
+
+#[test]
+fn test_65c02_wai_takes_irq() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![
+        0x58,   // CLI
+        0xCB,   // WAI
+        0xE8,   // INX
+    ]);
+    cpu.load_at(vec![
+        0xAD, 0x15, 0x40,   // LDA $4015
+        0x40,               // RTI
+    ], 0x9000);
+    cpu.mem_write_u16(0xFFFC, 0x8000);
+    cpu.mem_write_u16(0xFFFE, 0x9000);
+    cpu.reset();
+
+    cpu.step();
+    assert_eq!(cpu.step().cycles, 3);
+
+    // idling a cycle at a time until the frame counter's IRQ
+    let mut step = cpu.step();
+    while step.interrupt.is_none() {
+        assert_eq!(step.opcode, None);
+        assert_eq!(step.cycles, 1);
+        assert_eq!(cpu.program_counter, 0x8002);
+        step = cpu.step();
+    }
+    assert_eq!(step.interrupt, Some(Interrupt::IRQ));
+    assert_eq!(cpu.program_counter, 0x9000);
+
+    // and back after the WAI once the handler is done
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.program_counter, 0x8002);
+    cpu.step();
+    assert_eq!(cpu.register_x, 1);
+}
+
+#[test]
+fn test_65c02_wai_with_interrupts_disabled() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![
+        0xCB,   // WAI
+        0xE8,   // INX
+    ]);
+    cpu.reset();
+    cpu.step();
+
+    while cpu.bus.irq_line().is_empty() {
+        assert_eq!(cpu.step().opcode, None);
+    }
+
+    // the IRQ ends the wait but isn't taken
+    let step = cpu.step();
+    assert_eq!(step.interrupt, None);
+    assert_eq!(step.opcode, Some(0xE8));
+    assert_eq!(cpu.register_x, 1);
+}
+
+#[test]
+fn test_65c02_stp() {
+    let mut cpu = new_65c02();
+    cpu.load(vec![0xDB, 0xE8]); // STP, INX
+    cpu.reset();
+
+    let stopped = EmulatorError::Jammed { opcode: 0xDB, pc: 0x8000 };
+    let step = cpu.step();
+    assert_eq!(step.cycles, 3);
+    assert_eq!(step.fault, Some(stopped));
+    assert_eq!(cpu.step().cycles, 0);
+    assert_eq!(cpu.register_x, 0);
+
+    // until it's reset
+    cpu.reset();
+    assert!(!cpu.is_jammed());
+}
+
+#[test]
+fn test_65c02_opcode_table_is_complete() {
+    assert_eq!(CpuVariant::WDC65C02.opcodes().len(), 256);
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+/*
+ * Something a program did that the emulator won't quietly carry on through.
+ * None of these bring the process down. An unknown opcode or a jammed CPU
+ * can't go any further, so those come back out of the run loops. The rest
+ * are defined behaviour, an ignored write or an open bus read, and are only
+ * diagnostics: the run loops keep going and leave them for
+ * `CPU::take_diagnostic`, for the caller to report or stop on.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmulatorError {
+    UnknownOpcode { opcode: u8, pc: u16 },
+    // one of the HLT opcodes, the CPU does nothing more until a reset
+    Jammed { opcode: u8, pc: u16 },
+    // a write to a cartridge that only has ROM there
+    RomWrite { addr: u16, data: u8 },
+    // a read from one of the registers that can only be written
+    WriteOnlyRead { addr: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmulatorError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:02X} at {:04X}", opcode, pc)
+            },
+            EmulatorError::Jammed { opcode, pc } => {
+                write!(f, "CPU jammed by opcode {:02X} at {:04X}", opcode, pc)
+            },
+            EmulatorError::RomWrite { addr, data } => {
+                write!(f, "write of {:02X} to ROM at {:04X}", data, addr)
+            },
+            EmulatorError::WriteOnlyRead { addr } => {
+                write!(f, "read from write-only register {:04X}", addr)
+            },
+        }
+    }
+}
+
+impl EmulatorError {
+    // whether the program can't carry on past it
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, EmulatorError::UnknownOpcode { .. } | EmulatorError::Jammed { .. })
+    }
+}
+
+impl std::error::Error for EmulatorError {}
@@ -0,0 +1,79 @@
+use rusticom::apu::resampler::MAX_RATE_ADJUST;
+use rusticom::Nes;
+
+use sdl2::AudioSubsystem;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+
+#[cfg(test)]
+mod tests;
+
+// how much audio to keep queued, in frames of video. Less crackles as soon as
+// the OS gets in the way, more and the sound lags behind the picture.
+const LATENCY_FRAMES: f64 = 4.0;
+
+/*
+ * Dynamic rate control. The emulator and the sound card each have their own
+ * idea of how long a second is, so left alone the queue slowly drains until
+ * it crackles or fills up until the sound lags. Instead of dropping or
+ * padding samples the resampler is nudged a fraction of a percent, making a
+ * few more samples when the queue is below its target and a few less when
+ * it's above, which nobody can hear.
+ */
+pub struct RateControl {
+    target: usize,
+}
+
+impl RateControl {
+    pub fn new(target: usize) -> Self {
+        RateControl { target }
+    }
+
+    pub fn adjust(&self, queued: usize) -> f64 {
+        let fill = queued as f64 / self.target as f64;
+        let adjust = 1.0 + (1.0 - fill) * MAX_RATE_ADJUST;
+        adjust.clamp(1.0 - MAX_RATE_ADJUST, 1.0 + MAX_RATE_ADJUST)
+    }
+}
+
+// Streams the APU's samples to an SDL audio queue, the frame pacer keeps time
+// and the rate control keeps the queue in step with it
+pub struct Audio {
+    queue: AudioQueue<f32>,
+    rate_control: RateControl,
+}
+
+impl Audio {
+    pub fn new(audio_subsystem: &AudioSubsystem, sample_rate: u32, frame_rate: f64) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(1),
+            samples: Some(1024),
+        };
+        let queue = audio_subsystem.open_queue::<f32, _>(None, &desired)?;
+        let target = (queue.spec().freq as f64 * LATENCY_FRAMES / frame_rate) as usize;
+        queue.resume();
+
+        Ok(Audio {
+            queue,
+            rate_control: RateControl::new(target),
+        })
+    }
+
+    // what the device actually opened with, which might not be what was asked for
+    pub fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn queued(&self) -> usize {
+        self.queue.size() as usize / std::mem::size_of::<f32>()
+    }
+
+    pub fn push(&mut self, nes: &mut Nes) {
+        nes.set_rate_adjust(self.rate_control.adjust(self.queued()));
+
+        let samples = nes.audio_samples();
+        if let Err(e) = self.queue.queue_audio(&samples) {
+            println!("Dropping {} audio samples: {}", samples.len(), e);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use super::*;
+
+#[test]
+fn test_rate_control_at_target() {
+    let rate_control = RateControl::new(2940);
+    assert_eq!(rate_control.adjust(2940), 1.0);
+}
+
+#[test]
+fn test_rate_control_direction() {
+    let rate_control = RateControl::new(2940);
+
+    // running low, make more samples
+    assert!(rate_control.adjust(1000) > 1.0);
+    // backing up, make fewer
+    assert!(rate_control.adjust(4000) < 1.0);
+}
+
+#[test]
+fn test_rate_control_limits() {
+    let rate_control = RateControl::new(2940);
+    assert_eq!(rate_control.adjust(0), 1.0 + MAX_RATE_ADJUST);
+    assert_eq!(rate_control.adjust(100_000), 1.0 - MAX_RATE_ADJUST);
+}
@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+
+use rusticom::joypad::JoypadButton;
+use rusticom::pacer::FramePacer;
+use rusticom::ppu::frame;
+use rusticom::rewind::Rewind;
+use rusticom::screenshot;
+use rusticom::trace::trace;
+use rusticom::{Nes, Player};
+
+use self::audio::Audio;
+use self::slots::SaveSlots;
+
+use sdl2::event::Event;
+use sdl2::EventPump;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+
+pub mod audio;
+pub mod slots;
+
+pub struct Options {
+    pub rom_path: PathBuf,
+    pub sample_rate: u32,
+    pub frame_rate: f64,
+    pub trace: bool,
+    pub rewind_interval: u32,
+    pub rewind_memory: usize,
+}
+
+// what the keyboard has to say about the next frame
+struct Controls {
+    buttons: JoypadButton,
+    slots: SaveSlots,
+    rewinding: bool,
+}
+
+fn key_to_button(keycode: Keycode) -> Option<JoypadButton> {
+    match keycode {
+        Keycode::Up     => Some(JoypadButton::UP),
+        Keycode::Down   => Some(JoypadButton::DOWN),
+        Keycode::Left   => Some(JoypadButton::LEFT),
+        Keycode::Right  => Some(JoypadButton::RIGHT),
+        Keycode::Space  => Some(JoypadButton::SELECT),
+        Keycode::Return => Some(JoypadButton::START),
+        Keycode::A      => Some(JoypadButton::BUTTON_A),
+        Keycode::S      => Some(JoypadButton::BUTTON_B),
+        _ => None,
+    }
+}
+
+fn key_to_slot(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::Num0 => Some(0),
+        Keycode::Num1 => Some(1),
+        Keycode::Num2 => Some(2),
+        Keycode::Num3 => Some(3),
+        Keycode::Num4 => Some(4),
+        Keycode::Num5 => Some(5),
+        Keycode::Num6 => Some(6),
+        Keycode::Num7 => Some(7),
+        Keycode::Num8 => Some(8),
+        Keycode::Num9 => Some(9),
+        _ => None,
+    }
+}
+
+/*
+ * P pauses and R resets, 0-9 pick a save state slot, F5 saves to it and F7
+ * loads it. Holding Backspace plays the game backwards.
+ */
+fn handle_user_input(nes: &mut Nes, controls: &mut Controls, event_pump: &mut EventPump) {
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                std::process::exit(0);
+            },
+
+            Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                nes.cpu_mut().toggle_pause();
+            },
+
+            Event::KeyDown { keycode: Some(Keycode::R), .. } => nes.reset(),
+
+            Event::KeyDown { keycode: Some(Keycode::F5), .. } => controls.slots.save(nes),
+            Event::KeyDown { keycode: Some(Keycode::F7), .. } => controls.slots.load(nes),
+
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => controls.rewinding = true,
+            Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => controls.rewinding = false,
+
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                if let Some(button) = key_to_button(keycode) {
+                    controls.buttons.insert(button);
+                }
+                if let Some(slot) = key_to_slot(keycode) {
+                    controls.slots.select(slot);
+                }
+            },
+
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                if let Some(button) = key_to_button(keycode) {
+                    controls.buttons.remove(button);
+                }
+            },
+
+            _ => { }
+        }
+    }
+
+    nes.set_buttons(Player::One, controls.buttons);
+}
+
+// Everything that needs SDL, a window with the picture, sound and the keyboard
+pub fn run(mut nes: Nes, options: &Options) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let audio_subsystem = sdl_context.audio().unwrap();
+    let window = video_subsystem
+        .window("Rusticom", (frame::WIDTH * 3) as u32, (frame::HEIGHT * 3) as u32)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    canvas.set_scale(3.0, 3.0).unwrap();
+
+    let creator = canvas.texture_creator();
+    let mut texture = creator
+        .create_texture_target(PixelFormatEnum::RGB24, frame::WIDTH as u32, frame::HEIGHT as u32)
+        .unwrap();
+
+    let mut audio = Audio::new(&audio_subsystem, options.sample_rate, options.frame_rate).unwrap();
+    nes.set_sample_rate(audio.sample_rate());
+
+    let mut pacer = FramePacer::new(options.frame_rate);
+    let mut rewind = Rewind::new(options.rewind_interval, options.rewind_memory);
+    let mut controls = Controls {
+        buttons: JoypadButton::empty(),
+        slots: SaveSlots::new(&options.rom_path),
+        rewinding: false,
+    };
+    let mut last_error = None;
+
+    loop {
+        handle_user_input(&mut nes, &mut controls, &mut event_pump);
+
+        // going backwards shows one snapshot a frame and stays quiet, there's
+        // no sound to go with it
+        let result = if controls.rewinding {
+            if let Err(e) = rewind.step_back(&mut nes) {
+                println!("Couldn't rewind: {}", e);
+            }
+            nes.audio_samples();
+            Ok(true)
+        } else if options.trace {
+            nes.cpu_mut().run_frame_with_callback(|cpu| println!("{}", trace(cpu)))
+        } else {
+            nes.run_frame()
+        };
+
+        // The game carries on after an error, and a jammed CPU stays that way
+        // until it's reset. The same error isn't reported twice in a row, so
+        // a game that keeps on doing it doesn't flood the console.
+        let running = match result {
+            Ok(running) => running,
+            Err(error) => {
+                if last_error != Some(error) {
+                    println!("Emulator error: {}", error);
+                }
+                last_error = Some(error);
+                true
+            },
+        };
+        if let Some(diagnostic) = nes.take_diagnostic() {
+            if last_error != Some(diagnostic) {
+                println!("Emulator warning: {}", diagnostic);
+            }
+            last_error = Some(diagnostic);
+        }
+
+        let cpu = nes.cpu();
+        if !controls.rewinding && !cpu.is_paused() && !cpu.is_jammed() {
+            rewind.frame(&nes);
+        }
+
+        let screen_state = screenshot::rgb(nes.frame_buffer());
+        texture.update(None, &screen_state, frame::WIDTH * 3).unwrap();
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+
+        audio.push(&mut nes);
+        pacer.wait();
+
+        if !running {
+            break;
+        }
+    }
+}
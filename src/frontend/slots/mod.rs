@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusticom::Nes;
+
+#[cfg(test)]
+mod tests;
+
+pub const SLOTS: u8 = 10;
+
+/*
+ * Numbered save state files next to the ROM, game.nes keeps slot 3 in
+ * game.state3. Problems are reported rather than returned, there's nothing
+ * else the frontend could do about them.
+ */
+pub struct SaveSlots {
+    rom_path: PathBuf,
+    slot: u8,
+}
+
+impl SaveSlots {
+    pub fn new(rom_path: &Path) -> Self {
+        SaveSlots {
+            rom_path: rom_path.to_path_buf(),
+            slot: 0,
+        }
+    }
+
+    pub fn select(&mut self, slot: u8) {
+        self.slot = slot % SLOTS;
+        println!("Save state slot {}", self.slot);
+    }
+
+    fn path(&self) -> PathBuf {
+        self.rom_path.with_extension(format!("state{}", self.slot))
+    }
+
+    pub fn save(&self, nes: &Nes) {
+        let path = self.path();
+        match fs::write(&path, nes.save_state()) {
+            Ok(()) => println!("Saved state to {}", path.display()),
+            Err(e) => println!("Couldn't save state to {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn load(&self, nes: &mut Nes) {
+        let path = self.path();
+        let result = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| nes.load_state(&data));
+
+        match result {
+            Ok(()) => println!("Loaded state from {}", path.display()),
+            Err(e) => println!("Couldn't load state from {}: {}", path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use super::*;
+
+#[test]
+fn test_slot_paths() {
+    let mut slots = SaveSlots::new(Path::new("roms/game.nes"));
+    assert_eq!(slots.path(), PathBuf::from("roms/game.state0"));
+
+    slots.select(3);
+    assert_eq!(slots.path(), PathBuf::from("roms/game.state3"));
+
+    slots.select(12);
+    assert_eq!(slots.path(), PathBuf::from("roms/game.state2"));
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::EmulatorError;
+use crate::nes::Nes;
+use crate::screenshot;
+
+#[cfg(test)]
+mod tests;
+
+pub struct Options {
+    pub frames: u32,
+    pub screenshot: PathBuf,
+    // frame numbers to save, counting from 1. Empty saves just the last one.
+    pub screenshot_at: Vec<u32>,
+    pub hash: bool,
+    // stop on diagnostics like ROM writes too, not just fatal errors
+    pub strict: bool,
+}
+
+pub struct Summary {
+    pub frames: u32,
+    // what stopped the program, if it went wrong
+    pub error: Option<EmulatorError>,
+}
+
+/*
+ * Run a ROM for a fixed number of frames without a window or sound, for
+ * testing in CI. Frames are saved as PNGs and their hashes written to `out`
+ * as they're produced. Returns how many frames were run, which is less than
+ * asked for if the program stopped early or an emulator error stopped it.
+ * Diagnostics go to stderr, the same one not twice in a row, unless they're
+ * strict about them.
+ */
+pub fn run<W: Write>(nes: &mut Nes, options: &Options, out: &mut W) -> io::Result<Summary> {
+    let mut frame = 0;
+    let mut error = None;
+    let mut last_diagnostic = None;
+    while frame < options.frames {
+        let mut running = match nes.run_frame() {
+            Ok(running) => running,
+            Err(e) => {
+                error = Some(e);
+                false
+            },
+        };
+        frame += 1;
+
+        if let Some(diagnostic) = nes.take_diagnostic() {
+            if options.strict {
+                error = error.or(Some(diagnostic));
+                running = false;
+            } else if last_diagnostic != Some(diagnostic) {
+                eprintln!("Emulator warning: {}", diagnostic);
+            }
+            last_diagnostic = Some(diagnostic);
+        }
+
+        if options.hash {
+            writeln!(out, "{} {:08x}", frame, screenshot::hash(nes.frame_buffer()))?;
+        }
+
+        if options.screenshot_at.contains(&frame) {
+            let path = numbered(&options.screenshot, frame);
+            fs::write(path, screenshot::png(nes.frame_buffer()))?;
+        }
+
+        if !running {
+            break;
+        }
+    }
+
+    if options.screenshot_at.is_empty() {
+        fs::write(&options.screenshot, screenshot::png(nes.frame_buffer()))?;
+    }
+
+    Ok(Summary { frames: frame, error })
+}
+
+// screenshot.png -> screenshot-120.png
+fn numbered(path: &Path, frame: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, frame, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, frame),
+    };
+    path.with_file_name(name)
+}
@@ -0,0 +1,138 @@
+use super::*;
+use crate::rom::tests::nop_rom;
+
+fn nop_nes(stop: bool) -> Nes {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    if stop {
+        nes.stop();
+    }
+    nes
+}
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusticom-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_numbered() {
+    assert_eq!(numbered(Path::new("out/shot.png"), 12), PathBuf::from("out/shot-12.png"));
+    assert_eq!(numbered(Path::new("shot"), 3), PathBuf::from("shot-3"));
+}
+
+#[test]
+fn test_final_screenshot_and_hashes() {
+    let dir = output_dir("final");
+    let options = Options {
+        frames: 3,
+        screenshot: dir.join("shot.png"),
+        screenshot_at: Vec::new(),
+        hash: true,
+        strict: false,
+    };
+
+    let mut out = Vec::new();
+    let summary = run(&mut nop_nes(false), &options, &mut out).unwrap();
+    assert_eq!(summary.frames, 3);
+    assert_eq!(summary.error, None);
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].starts_with("3 "));
+
+    let png = fs::read(dir.join("shot.png")).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_screenshots_at_frames() {
+    let dir = output_dir("at");
+    let options = Options {
+        frames: 4,
+        screenshot: dir.join("shot.png"),
+        screenshot_at: vec![2, 4],
+        hash: false,
+        strict: false,
+    };
+
+    let mut out = Vec::new();
+    run(&mut nop_nes(false), &options, &mut out).unwrap();
+    assert!(out.is_empty());
+
+    assert!(dir.join("shot-2.png").exists());
+    assert!(dir.join("shot-4.png").exists());
+    assert!(!dir.join("shot-1.png").exists());
+    assert!(!dir.join("shot.png").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_stops_early() {
+    let dir = output_dir("stop");
+    let options = Options {
+        frames: 10,
+        screenshot: dir.join("shot.png"),
+        screenshot_at: Vec::new(),
+        hash: false,
+        strict: false,
+    };
+
+    let summary = run(&mut nop_nes(true), &options, &mut Vec::new()).unwrap();
+    assert_eq!(summary.frames, 1);
+    assert_eq!(summary.error, None);
+    assert!(dir.join("shot.png").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_stops_on_error() {
+    let dir = output_dir("error");
+    let options = Options {
+        frames: 10,
+        screenshot: dir.join("shot.png"),
+        screenshot_at: Vec::new(),
+        hash: false,
+        strict: false,
+    };
+
+    let mut rom = nop_rom();
+    rom.prg_rom[0x10] = 0x02;
+    let summary = run(&mut Nes::new(rom).unwrap(), &options, &mut Vec::new()).unwrap();
+    assert_eq!(summary.frames, 1);
+    assert_eq!(summary.error, Some(EmulatorError::Jammed { opcode: 0x02, pc: 0x8010 }));
+    assert!(dir.join("shot.png").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_diagnostics() {
+    let dir = output_dir("diagnostic");
+    let mut options = Options {
+        frames: 3,
+        screenshot: dir.join("shot.png"),
+        screenshot_at: Vec::new(),
+        hash: false,
+        strict: false,
+    };
+
+    // a ROM write carries on unless it's asked to be strict
+    let mut rom = nop_rom();
+    rom.prg_rom[0x10..0x13].copy_from_slice(&[0x8D, 0x00, 0x80]);
+    let summary = run(&mut Nes::new(rom.clone()).unwrap(), &options, &mut Vec::new()).unwrap();
+    assert_eq!(summary.frames, 3);
+    assert_eq!(summary.error, None);
+
+    options.strict = true;
+    let summary = run(&mut Nes::new(rom).unwrap(), &options, &mut Vec::new()).unwrap();
+    assert_eq!(summary.frames, 1);
+    assert_eq!(summary.error, Some(EmulatorError::RomWrite { addr: 0x8000, data: 0x00 }));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,108 @@
+use bitflags::bitflags;
+
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+
+#[cfg(test)]
+mod tests;
+
+/*
+ * Buttons in the order the controller shifts them out, A first.
+ */
+
+bitflags! {
+    #[derive(Clone, Copy)]
+    pub struct JoypadButton : u8 {
+        const BUTTON_A  = 0b0000_0001;
+        const BUTTON_B  = 0b0000_0010;
+        const SELECT    = 0b0000_0100;
+        const START     = 0b0000_1000;
+        const UP        = 0b0001_0000;
+        const DOWN      = 0b0010_0000;
+        const LEFT      = 0b0100_0000;
+        const RIGHT     = 0b1000_0000;
+    }
+}
+
+/*
+ * Standard controller. While the strobe bit is high the shift register keeps
+ * reloading from the buttons, so every read returns A. Once it goes low each
+ * read shifts out the next button, and after all 8 an official controller
+ * returns 1s.
+ */
+
+pub struct Joypad {
+    strobe: bool,
+    shift_register: u8,
+    buttons: JoypadButton,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            strobe: false,
+            shift_register: 0,
+            buttons: JoypadButton::empty(),
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        let was_strobe = self.strobe;
+        self.strobe = data & 1 == 1;
+
+        // the register keeps reloading until the strobe goes low
+        if was_strobe || self.strobe {
+            self.shift_register = self.buttons.bits();
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 1;
+        }
+
+        let bit = self.shift_register & 1;
+        self.shift_register = (self.shift_register >> 1) | 0b1000_0000;
+        bit
+    }
+
+    // the bit the next read returns, without shifting
+    pub fn peek(&self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 1;
+        }
+        self.shift_register & 1
+    }
+
+    pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
+        self.buttons.set(button, pressed);
+    }
+
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.buttons = buttons;
+    }
+
+    pub fn buttons(&self) -> JoypadButton {
+        self.buttons
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Joypad {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.strobe);
+        w.write_u8(self.shift_register);
+        w.write_u8(self.buttons.bits());
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.strobe = r.read_bool()?;
+        self.shift_register = r.read_u8()?;
+        self.buttons = JoypadButton::from_bits_truncate(r.read_u8()?);
+        Ok(())
+    }
+}
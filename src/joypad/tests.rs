@@ -0,0 +1,55 @@
+use super::*;
+
+fn read_all(joypad: &mut Joypad) -> Vec<u8> {
+    (0..8).map(|_| joypad.read()).collect()
+}
+
+#[test]
+fn test_read_buttons_in_order() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    joypad.set_button_pressed_status(JoypadButton::START, true);
+    joypad.set_button_pressed_status(JoypadButton::RIGHT, true);
+
+    joypad.write(1);
+    joypad.write(0);
+
+    assert_eq!(read_all(&mut joypad), vec![1, 0, 0, 1, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_reads_one_after_all_buttons() {
+    let mut joypad = Joypad::new();
+    joypad.write(1);
+    joypad.write(0);
+    read_all(&mut joypad);
+
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 1);
+}
+
+#[test]
+fn test_strobe_high_always_reads_a() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    joypad.write(1);
+
+    assert_eq!(read_all(&mut joypad), vec![1; 8]);
+
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_A, false);
+    assert_eq!(joypad.read(), 0);
+}
+
+#[test]
+fn test_buttons_latched_on_strobe() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_B, true);
+    joypad.write(1);
+    joypad.write(0);
+
+    // changes after the strobe are not seen until the next one
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    joypad.set_button_pressed_status(JoypadButton::BUTTON_B, false);
+    assert_eq!(joypad.read(), 0);
+    assert_eq!(joypad.read(), 1);
+}
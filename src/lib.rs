@@ -0,0 +1,23 @@
+pub mod apu;
+pub mod bus;
+pub mod checksum;
+pub mod cpu;
+pub mod error;
+pub mod headless;
+pub mod joypad;
+pub mod mapper;
+pub mod mem;
+pub mod nes;
+pub mod opcode;
+pub mod pacer;
+pub mod ppu;
+pub mod rewind;
+pub mod rom;
+pub mod savestate;
+pub mod screenshot;
+pub mod trace;
+
+pub use crate::nes::{Nes, Player};
+
+#[macro_use]
+extern crate lazy_static;
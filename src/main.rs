@@ -1,25 +1,20 @@
-pub mod bus;
-pub mod cpu;
-pub mod mem;
-pub mod opcode;
-pub mod ppu;
-pub mod rom;
-pub mod trace;
-
-use crate::bus::Bus;
-use crate::cpu::CPU;
-use crate::mem::Mem;
-use crate::rom::Rom;
-use crate::trace::trace;
-
-use clap::Parser;
+mod frontend;
+
+use std::path::PathBuf;
+
+use rusticom::headless;
+use rusticom::pacer::{NTSC_FRAME_RATE, PAL_FRAME_RATE};
+use rusticom::rom::Rom;
+use rusticom::Nes;
+
+use clap::{Parser, ValueEnum};
 use clap_num::maybe_hex;
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::EventPump;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::pixels::PixelFormatEnum;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Region {
+    Ntsc,
+    Pal,
+}
 
 #[derive(Parser)]
 struct Cli {
@@ -28,120 +23,98 @@ struct Cli {
 
     #[arg(short, long, value_parser=maybe_hex::<u16>)]
     entry_point: Option<u16>,
-}
 
-#[macro_use]
-extern crate lazy_static;
+    #[arg(long, default_value_t = 44100)]
+    sample_rate: u32,
 
-fn handle_user_input(cpu: &mut CPU, event_pump: &mut EventPump) {
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                std::process::exit(0);
-            },
+    #[arg(long, value_enum, default_value_t = Region::Ntsc)]
+    region: Region,
 
-            Event::KeyDown { keycode: Some(Keycode::W), .. } => {
-                cpu.mem_write(0xFF, 0x77);
-            },
+    // print every instruction as it runs, much too slow for full speed
+    #[arg(long)]
+    trace: bool,
 
-            Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                cpu.mem_write(0xFF, 0x61);
-            },
+    // how often to take a snapshot to rewind to, in frames
+    #[arg(long, default_value_t = 2)]
+    rewind_interval: u32,
 
-            Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                cpu.mem_write(0xFF, 0x73);
-            },
+    // how much memory rewinding may use, in megabytes
+    #[arg(long, value_name = "MB", default_value_t = 64)]
+    rewind_memory: usize,
 
-            Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                cpu.mem_write(0xFF, 0x64);
-            },
+    // run this many frames without a window or sound, then exit
+    #[arg(long, value_name = "FRAMES")]
+    headless: Option<u32>,
 
-            Event::KeyDown { keycode: Some(Keycode::P), .. } => {
-                cpu.toggle_pause();
-            }
+    // where headless mode saves the picture
+    #[arg(long, default_value = "screenshot.png", requires = "headless")]
+    screenshot: PathBuf,
 
-            _ => { }
-        }
-    }
-}
+    // save these frames instead of just the last one, as screenshot-N.png
+    #[arg(long, value_delimiter = ',', requires = "headless")]
+    screenshot_at: Vec<u32>,
 
-fn map_color(byte: u8) -> Color {
-    match byte {
-        0       => sdl2::pixels::Color::BLACK,
-        1       => sdl2::pixels::Color::WHITE,
-        2 | 9   => sdl2::pixels::Color::GREY,
-        3 | 10  => sdl2::pixels::Color::RED,
-        4 | 11  => sdl2::pixels::Color::GREEN,
-        5 | 12  => sdl2::pixels::Color::BLUE,
-        6 | 13  => sdl2::pixels::Color::MAGENTA,
-        7 | 14  => sdl2::pixels::Color::YELLOW,
-        _       => sdl2::pixels::Color::CYAN,
-    }
-}
+    // print a hash of every frame in headless mode
+    #[arg(long, requires = "headless")]
+    hash: bool,
 
-fn read_screen_state(cpu: &mut CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
-    let mut frame_idx = 0;
-    let mut update = false;
-
-    for i in 0x0200..0x0600 {
-        let color_idx = cpu.mem_read(i as u16);
-        let (b1, b2, b3) = map_color(color_idx).rgb();
-        if frame[frame_idx] != b1 || frame[frame_idx + 1] != b2 || frame[frame_idx + 2] != b3 {
-            frame[frame_idx] = b1;
-            frame[frame_idx + 1] = b2;
-            frame[frame_idx + 2] = b3;
-            update = true;
-        }
-        frame_idx += 3;
-    }
-
-    update
+    // stop headless mode on ROM writes and reads of write-only registers too
+    #[arg(long, requires = "headless")]
+    strict: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("Snake Game", (32.0 * 10.0) as u32, (32.0 * 10.0) as u32)
-        .position_centered()
-        .build()
-        .unwrap();
-
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-
-    canvas.set_scale(10.0, 10.0).unwrap();
-
-    let creator = canvas.texture_creator();
-    let mut texture = creator.create_texture_target(PixelFormatEnum::RGB24, 32, 32).unwrap();
-
-    let bytes: Vec<u8> = std::fs::read(cli.rom).unwrap();
+    let bytes: Vec<u8> = std::fs::read(&cli.rom).unwrap();
     let rom = Rom::new(&bytes).unwrap();
 
-    let bus = Bus::new(rom);
-    let mut cpu = CPU::new(bus);
-    cpu.reset();
-    cpu.program_counter = cli.entry_point.unwrap_or(cpu.program_counter);
-
-    let mut screen_state = [0u8; 32 * 3 * 32];
-    let mut rng = rand::thread_rng();
-
-    cpu.run_with_callback(move |cpu| {
-        println!("{}", trace(cpu));
-
-        handle_user_input(cpu, &mut event_pump);
-        cpu.mem_write(0xFE, rng.gen_range(1u8..=16u8));
+    let mut nes = match Nes::new(rom) {
+        Ok(nes) => nes,
+        Err(e) => {
+            eprintln!("Can't run {}: {}", cli.rom, e);
+            std::process::exit(1);
+        },
+    };
+    if let Some(entry_point) = cli.entry_point {
+        nes.cpu_mut().program_counter = entry_point;
+    }
 
-        if read_screen_state(cpu, &mut screen_state) {
-            texture.update(None, &screen_state, 32 * 3).unwrap();
-            canvas.copy(&texture, None, None).unwrap();
-            canvas.present();
+    if let Some(frames) = cli.headless {
+        let options = headless::Options {
+            frames,
+            screenshot: cli.screenshot,
+            screenshot_at: cli.screenshot_at,
+            hash: cli.hash,
+            strict: cli.strict,
+        };
+        let summary = headless::run(&mut nes, &options, &mut std::io::stdout()).unwrap();
+        if let Some(error) = summary.error {
+            eprintln!("Emulator error: {}", error);
         }
+        if summary.frames < frames || summary.error.is_some() {
+            eprintln!("Program stopped after {} of {} frames", summary.frames, frames);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-        ::std::thread::sleep(std::time::Duration::new(0, 25_000));
-    });
+    // The PPU and APU only have NTSC timing so far. PAL paces the NTSC frames
+    // at 50Hz, which is slow, and leaves the sound short of samples.
+    if let Region::Pal = cli.region {
+        eprintln!("Warning: PAL timing isn't emulated, running NTSC frames at the PAL rate and the sound will crackle");
+    }
 
-    
+    let options = frontend::Options {
+        rom_path: PathBuf::from(&cli.rom),
+        sample_rate: cli.sample_rate,
+        frame_rate: match cli.region {
+            Region::Ntsc => NTSC_FRAME_RATE,
+            Region::Pal => PAL_FRAME_RATE,
+        },
+        trace: cli.trace,
+        rewind_interval: cli.rewind_interval,
+        rewind_memory: cli.rewind_memory * 1024 * 1024,
+    };
+    frontend::run(nes, &options);
 }
@@ -0,0 +1,83 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{bank_offset, chr_memory, Mapper};
+
+/*
+ * Mapper 7: writes to $8000-$FFFF pick a 32 KB PRG bank with bits 0-2 and
+ * which nametable fills all four slots with bit 4. CHR is RAM.
+ *
+ * Only the AMROM and AOROM boards have bus conflicts and the iNES header cannot
+ * tell them apart from ANROM, whose games rely on having none, so they are not
+ * emulated.
+ */
+pub struct AxROM {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    bank: u8,
+}
+
+impl AxROM {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        AxROM {
+            prg_rom: rom.prg_rom,
+            chr,
+            chr_is_ram,
+            bank: 0,
+        }
+    }
+}
+
+impl Mapper for AxROM {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => {
+                let bank = (self.bank & 0b111) as usize;
+                Some(self.prg_rom[bank_offset(self.prg_rom.len(), bank, 0x8000, addr)])
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            self.bank = data;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[addr as usize]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            self.chr[addr as usize] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.bank & 0b1_0000 {
+            0 => Mirroring::SingleScreenA,
+            _ => Mirroring::SingleScreenB,
+        }
+    }
+}
+
+impl Snapshot for AxROM {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.bank);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.bank = r.read_u8()?;
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
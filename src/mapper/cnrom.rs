@@ -0,0 +1,84 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{bank_offset, chr_memory, Mapper};
+
+/*
+ * Mapper 3: NROM sized PRG with 8 KB CHR ROM banks picked by writes to
+ * $8000-$FFFF. Has bus conflicts, the value written is ANDed with the ROM byte.
+ */
+pub struct CNROM {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: Mirroring,
+    chr_bank: u8,
+}
+
+impl CNROM {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        CNROM {
+            prg_rom: rom.prg_rom,
+            chr,
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+            chr_bank: 0,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        (addr - 0x8000) as usize % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        bank_offset(self.chr.len(), self.chr_bank as usize, 0x2000, addr)
+    }
+}
+
+impl Mapper for CNROM {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank = data & self.prg_rom[self.prg_index(addr)];
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let index = self.chr_index(addr);
+            self.chr[index] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for CNROM {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.chr_bank);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.chr_bank = r.read_u8()?;
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
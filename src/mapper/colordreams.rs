@@ -0,0 +1,85 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{bank_offset, chr_memory, Mapper};
+
+/*
+ * Mapper 11: writes to $8000-$FFFF pick a 32 KB PRG bank with bits 0-1 and an
+ * 8 KB CHR bank with bits 4-7. Has bus conflicts.
+ */
+pub struct ColorDreams {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: Mirroring,
+    bank: u8,
+}
+
+impl ColorDreams {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        ColorDreams {
+            prg_rom: rom.prg_rom,
+            chr,
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+            bank: 0,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = (self.bank & 0b11) as usize;
+        bank_offset(self.prg_rom.len(), bank, 0x8000, addr)
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        bank_offset(self.chr.len(), (self.bank >> 4) as usize, 0x2000, addr)
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            self.bank = data & self.prg_rom[self.prg_index(addr)];
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let index = self.chr_index(addr);
+            self.chr[index] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+impl Snapshot for ColorDreams {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.bank);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.bank = r.read_u8()?;
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{bank_offset, chr_memory, Mapper};
+
+const PRG_RAM_SIZE: usize = 0x2000;
+
+/*
+ * Mapper 1 (SxROM). Registers are loaded one bit at a time through a 5 bit
+ * shift register, by writing bit 0 to anywhere in $8000-$FFFF five times. The
+ * fifth write copies the value to the register picked by bits 13-14 of its
+ * address. Writing a value with bit 7 set resets the shift register.
+ *
+ * Control ($8000-$9FFF)
+ * 4bit0
+ * -----
+ * CPPMM
+ * |||||
+ * |||++- Mirroring (0: one-screen A; 1: one-screen B; 2: vertical; 3: horizontal)
+ * |++--- PRG mode (0, 1: 32 KB; 2: first bank fixed at $8000; 3: last bank fixed at $C000)
+ * +----- CHR mode (0: 8 KB; 1: two 4 KB banks)
+ *
+ * CHR bank 0 ($A000-$BFFF), CHR bank 1 ($C000-$DFFF), PRG bank ($E000-$FFFF,
+ * bit 4 disables PRG RAM).
+ */
+pub struct MMC1 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    prg_bank: u8,
+
+    // The MMC1 ignores a write on the cycle right after another one, so the
+    // second write of a read-modify-write instruction is lost.
+    cycles_since_write: usize,
+}
+
+impl MMC1 {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        MMC1 {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr,
+            chr_is_ram,
+            shift: 0,
+            shift_count: 0,
+            control: 0b0_1100,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+            cycles_since_write: usize::MAX,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank0 = value,
+            0xC000..=0xDFFF => self.chr_bank1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b1_0000 == 0
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0b1111) as usize;
+        // 512 KB boards use bit 4 of the CHR register to pick the 256 KB half
+        let outer = (self.chr_bank0 & 0b1_0000) as usize;
+        let last = (self.prg_rom.len() / 0x4000).saturating_sub(1) & 0b1111;
+
+        let bank = match ((self.control >> 2) & 0b11, addr) {
+            (0 | 1, _) => return bank_offset(self.prg_rom.len(), (outer | bank) >> 1, 0x8000, addr),
+            (2, 0x8000..=0xBFFF) => outer,
+            (2, _) => outer | bank,
+            (_, 0x8000..=0xBFFF) => outer | bank,
+            (_, _) => outer | last,
+        };
+        bank_offset(self.prg_rom.len(), bank, 0x4000, addr)
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        if self.control & 0b1_0000 == 0 {
+            return bank_offset(self.chr.len(), (self.chr_bank0 >> 1) as usize, 0x2000, addr);
+        }
+
+        let bank = match addr {
+            0x0000..=0x0FFF => self.chr_bank0,
+            _ => self.chr_bank1,
+        };
+        bank_offset(self.chr.len(), bank as usize, 0x1000, addr)
+    }
+}
+
+impl Mapper for MMC1 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Some(self.prg_ram[(addr - 0x6000) as usize])
+            },
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram[(addr - 0x6000) as usize] = data;
+            },
+            0x8000..=0xFFFF => {
+                let consecutive = self.cycles_since_write < 2;
+                self.cycles_since_write = 0;
+                if consecutive {
+                    return;
+                }
+
+                if data & 0b1000_0000 != 0 {
+                    self.shift = 0;
+                    self.shift_count = 0;
+                    self.control |= 0b0_1100;
+                    return;
+                }
+
+                self.shift |= (data & 1) << self.shift_count;
+                self.shift_count += 1;
+
+                if self.shift_count == 5 {
+                    self.write_register(addr, self.shift);
+                    self.shift = 0;
+                    self.shift_count = 0;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let index = self.chr_index(addr);
+            self.chr[index] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenA,
+            1 => Mirroring::SingleScreenB,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn cpu_tick(&mut self, cycles: u8) {
+        self.cycles_since_write = self.cycles_since_write.saturating_add(cycles as usize);
+    }
+}
+
+impl Snapshot for MMC1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.shift);
+        w.write_u8(self.shift_count);
+        w.write_u8(self.control);
+        w.write_u8(self.chr_bank0);
+        w.write_u8(self.chr_bank1);
+        w.write_u8(self.prg_bank);
+        w.write_u64(self.cycles_since_write as u64);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        self.shift = r.read_u8()?;
+        self.shift_count = r.read_u8()?;
+        self.control = r.read_u8()?;
+        self.chr_bank0 = r.read_u8()?;
+        self.chr_bank1 = r.read_u8()?;
+        self.prg_bank = r.read_u8()?;
+        self.cycles_since_write = r.read_u64()? as usize;
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{bank_offset, chr_memory, Mapper};
+
+const PRG_RAM_SIZE: usize = 0x2000;
+
+// A12 has to stay low for this many CPU cycles before a rise clocks the
+// counter, which filters out the short dips between sprite fetches.
+const A12_FILTER_CYCLES: u64 = 3;
+
+/*
+ * Mapper 4 (TxROM). Registers sit in pairs at even and odd addresses:
+ *
+ * $8000 bank select   CP.. .RRR (C: CHR inversion, P: PRG mode, R: target register)
+ * $8001 bank data     value for the register picked by bank select
+ * $A000 mirroring     0: vertical, 1: horizontal
+ * $A001 PRG RAM       E W.. .... (E: enabled, W: write protected)
+ * $C000 IRQ latch     value the counter is reloaded with
+ * $C001 IRQ reload    reload the counter on the next clock
+ * $E000 IRQ disable   also acknowledges a pending IRQ
+ * $E001 IRQ enable
+ *
+ * R0 and R1 select 2 KB CHR banks, R2-R5 1 KB CHR banks and R6, R7 8 KB PRG
+ * banks. The scanline counter is clocked by rising edges of PPU A12, which
+ * happen once per line when the background and sprites use different tables.
+ */
+pub struct MMC3 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    bank_select: u8,
+    registers: [u8; 8],
+    mirroring: Mirroring,
+    four_screen: bool,
+    prg_ram_enabled: bool,
+    prg_ram_protected: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12: bool,
+    a12_low_since: u64,
+    cpu_cycles: u64,
+}
+
+impl MMC3 {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        MMC3 {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr,
+            chr_is_ram,
+            bank_select: 0,
+            registers: [0; 8],
+            mirroring: rom.screen_mirroring,
+            four_screen: rom.screen_mirroring == Mirroring::FourScreen,
+            prg_ram_enabled: true,
+            prg_ram_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_since: 0,
+            cpu_cycles: 0,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        // a cartridge with a single bank has it in both of the fixed slots
+        let banks = self.prg_rom.len() / 0x2000;
+        let second_last = banks.saturating_sub(2);
+        let last = banks.saturating_sub(1);
+        let swap = self.bank_select & 0b0100_0000 != 0;
+
+        let bank = match (addr, swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.registers[6] as usize,
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => second_last,
+            (0xA000..=0xBFFF, _) => self.registers[7] as usize,
+            _ => last,
+        };
+        bank_offset(self.prg_rom.len(), bank, 0x2000, addr)
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        // inversion swaps the 2 KB and 1 KB halves of the pattern tables
+        let addr = match self.bank_select & 0b1000_0000 != 0 {
+            true => addr ^ 0x1000,
+            false => addr,
+        };
+
+        let bank = match addr {
+            0x0000..=0x07FF => (self.registers[0] & 0xFE) | ((addr >> 10) & 1) as u8,
+            0x0800..=0x0FFF => (self.registers[1] & 0xFE) | ((addr >> 10) & 1) as u8,
+            _ => self.registers[2 + ((addr - 0x1000) >> 10) as usize],
+        };
+        bank_offset(self.chr.len(), bank as usize, 0x0400, addr)
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn watch_a12(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+
+        if a12 && !self.a12 && self.cpu_cycles - self.a12_low_since >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        } else if !a12 && self.a12 {
+            self.a12_low_since = self.cpu_cycles;
+        }
+
+        self.a12 = a12;
+    }
+}
+
+impl Mapper for MMC3 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => {
+                Some(self.prg_ram[(addr - 0x6000) as usize])
+            },
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        let even = addr & 1 == 0;
+
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled && !self.prg_ram_protected => {
+                self.prg_ram[(addr - 0x6000) as usize] = data;
+            },
+            0x8000..=0x9FFF if even => self.bank_select = data,
+            0x8000..=0x9FFF => self.registers[(self.bank_select & 0b111) as usize] = data,
+            0xA000..=0xBFFF if !even => {
+                self.prg_ram_enabled = data & 0b1000_0000 != 0;
+                self.prg_ram_protected = data & 0b0100_0000 != 0;
+            },
+            // four screen boards have their own nametable RAM and ignore this
+            0xA000..=0xBFFF if !self.four_screen => {
+                self.mirroring = match data & 1 {
+                    0 => Mirroring::Vertical,
+                    _ => Mirroring::Horizontal,
+                };
+            },
+            0xC000..=0xDFFF if even => self.irq_latch = data,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            },
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            },
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => {},
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.watch_a12(addr);
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.watch_a12(addr);
+        if self.chr_is_ram {
+            let index = self.chr_index(addr);
+            self.chr[index] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn cpu_tick(&mut self, cycles: u8) {
+        self.cpu_cycles += cycles as u64;
+    }
+
+    fn patch_prg_rom(&mut self, addr: u16, data: u8) -> bool {
+        let index = self.prg_index(addr);
+        self.prg_rom[index] = data;
+        true
+    }
+}
+
+impl Snapshot for MMC3 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.bank_select);
+        w.write_bytes(&self.registers);
+        w.write_bool(self.mirroring == Mirroring::Horizontal);
+        w.write_bool(self.prg_ram_enabled);
+        w.write_bool(self.prg_ram_protected);
+        w.write_u8(self.irq_latch);
+        w.write_u8(self.irq_counter);
+        w.write_bool(self.irq_reload);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.a12);
+        w.write_u64(self.a12_low_since);
+        w.write_u64(self.cpu_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        self.bank_select = r.read_u8()?;
+        r.read_into(&mut self.registers)?;
+        let horizontal = r.read_bool()?;
+        if !self.four_screen {
+            self.mirroring = match horizontal {
+                true => Mirroring::Horizontal,
+                false => Mirroring::Vertical,
+            };
+        }
+        self.prg_ram_enabled = r.read_bool()?;
+        self.prg_ram_protected = r.read_bool()?;
+        self.irq_latch = r.read_u8()?;
+        self.irq_counter = r.read_u8()?;
+        self.irq_reload = r.read_bool()?;
+        self.irq_enabled = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        self.a12 = r.read_bool()?;
+        self.a12_low_since = r.read_u64()?;
+        self.cpu_cycles = r.read_u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::Snapshot;
+use self::axrom::AxROM;
+use self::cnrom::CNROM;
+use self::colordreams::ColorDreams;
+use self::gxrom::GxROM;
+use self::mmc1::MMC1;
+use self::mmc3::MMC3;
+use self::nrom::NROM;
+use self::uxrom::UxROM;
+
+pub mod axrom;
+pub mod cnrom;
+pub mod colordreams;
+pub mod gxrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nrom;
+pub mod uxrom;
+
+#[cfg(test)]
+mod tests;
+
+const CHR_RAM_SIZE: usize = 0x2000;
+
+/*
+ * The cartridge side of the system. A mapper sees every CPU access from $4020
+ * to $FFFF and every PPU pattern table access from $0000 to $1FFF, decides how
+ * the nametables are mirrored and can pull the CPU's IRQ line. Its banking
+ * registers and RAM go into save states, the ROM doesn't.
+ */
+pub trait Mapper: Snapshot {
+    // None means the cartridge does not drive the data bus (open bus)
+    fn cpu_read(&mut self, addr: u16) -> Option<u8>;
+    fn cpu_write(&mut self, addr: u16, data: u8);
+
+    fn ppu_read(&mut self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, data: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // Called as the CPU runs, for boards that care about CPU timing
+    fn cpu_tick(&mut self, _cycles: u8) {}
+
+    // False for boards with nothing but ROM from $8000 up, where a write can
+    // only be a mistake
+    fn has_prg_registers(&self) -> bool {
+        true
+    }
+
+    // Write straight into whatever PRG ROM is mapped at addr, used to load
+    // test programs through `Bus::allow_rom_writes`. False if the board
+    // can't.
+    fn patch_prg_rom(&mut self, _addr: u16, _data: u8) -> bool {
+        false
+    }
+}
+
+pub type SharedMapper = Rc<RefCell<dyn Mapper>>;
+
+// The board the ROM's header asks for, or an error naming the mapper number
+// if it isn't one we have. Every board banks by taking offsets modulo the
+// PRG ROM's size, so a cartridge without any is turned away too.
+pub fn from_rom(rom: Rom) -> Result<SharedMapper, String> {
+    if rom.prg_rom.is_empty() {
+        return Err("Cartridge has no PRG ROM".to_string());
+    }
+
+    let mapper: SharedMapper = match rom.mapper {
+        0 => Rc::new(RefCell::new(NROM::new(rom))),
+        1 => Rc::new(RefCell::new(MMC1::new(rom))),
+        2 => Rc::new(RefCell::new(UxROM::new(rom))),
+        3 => Rc::new(RefCell::new(CNROM::new(rom))),
+        4 => Rc::new(RefCell::new(MMC3::new(rom))),
+        7 => Rc::new(RefCell::new(AxROM::new(rom))),
+        11 => Rc::new(RefCell::new(ColorDreams::new(rom))),
+        66 => Rc::new(RefCell::new(GxROM::new(rom))),
+        n => return Err(format!("Mapper {} is not supported", n)),
+    };
+    Ok(mapper)
+}
+
+// Boards without CHR ROM have 8 KB of CHR RAM in its place
+fn chr_memory(chr_rom: Vec<u8>) -> (Vec<u8>, bool) {
+    match chr_rom.is_empty() {
+        true => (vec![0; CHR_RAM_SIZE], true),
+        false => (chr_rom, false),
+    }
+}
+
+// Index into `len` bytes of ROM or RAM for addr, inside a window of `size`
+// bytes that shows `bank`. Banks past the end wrap around, as the board just
+// does not connect the upper address lines.
+fn bank_offset(len: usize, bank: usize, size: usize, addr: u16) -> usize {
+    (bank * size + (addr as usize & (size - 1))) % len
+}
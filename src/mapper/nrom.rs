@@ -0,0 +1,85 @@
+use crate::rom::{Mirroring, Rom};
+use crate::savestate::{Snapshot, StateReader, StateWriter};
+use super::{chr_memory, Mapper};
+
+/*
+ * Mapper 0: 16 or 32 KB of PRG ROM at $8000 (16 KB is mirrored into $C000)
+ * and 8 KB of CHR ROM or RAM, with mirroring fixed by the board.
+ */
+pub struct NROM {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: Mirroring,
+}
+
+impl NROM {
+    pub fn new(rom: Rom) -> Self {
+        let (chr, chr_is_ram) = chr_memory(rom.chr_rom);
+
+        NROM {
+            prg_rom: rom.prg_rom,
+            chr,
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let mut index = (addr - 0x8000) as usize;
+        if self.prg_rom.len() == 0x4000 {
+            index %= 0x4000;
+        }
+        index
+    }
+}
+
+impl Mapper for NROM {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, _addr: u16, _data: u8) {}
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            self.chr[addr as usize] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn has_prg_registers(&self) -> bool {
+        false
+    }
+
+    fn patch_prg_rom(&mut self, addr: u16, data: u8) -> bool {
+        let index = self.prg_index(addr);
+        self.prg_rom[index] = data;
+        true
+    }
+}
+
+impl Snapshot for NROM {
+    fn save_state(&self, w: &mut StateWriter) {
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        if self.chr_is_ram {
+            r.read_into(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
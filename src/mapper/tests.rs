@@ -0,0 +1,524 @@
+use super::*;
+use crate::rom::tests::test_rom;
+use crate::savestate::{StateReader, StateWriter};
+
+fn nrom(prg_pages: usize, chr_rom: Vec<u8>) -> NROM {
+    let mut prg_rom = vec![0; prg_pages * 0x4000];
+    for (i, byte) in prg_rom.iter_mut().enumerate() {
+        *byte = (i / 0x4000) as u8 + 1;
+    }
+
+    NROM::new(Rom {
+        prg_rom,
+        chr_rom,
+        mapper: 0,
+        screen_mirroring: Mirroring::Vertical,
+    })
+}
+
+#[test]
+fn test_nrom_16k_is_mirrored() {
+    let mut mapper = nrom(1, vec![0; 0x2000]);
+    assert_eq!(mapper.cpu_read(0x8000), Some(1));
+    assert_eq!(mapper.cpu_read(0xC000), Some(1));
+}
+
+#[test]
+fn test_nrom_32k() {
+    let mut mapper = nrom(2, vec![0; 0x2000]);
+    assert_eq!(mapper.cpu_read(0xBFFF), Some(1));
+    assert_eq!(mapper.cpu_read(0xC000), Some(2));
+}
+
+#[test]
+fn test_nrom_open_bus_below_rom() {
+    let mut mapper = nrom(1, vec![0; 0x2000]);
+    assert_eq!(mapper.cpu_read(0x6000), None);
+}
+
+#[test]
+fn test_nrom_chr_rom_is_read_only() {
+    let mut mapper = nrom(1, vec![7; 0x2000]);
+    mapper.ppu_write(0x0010, 0x42);
+    assert_eq!(mapper.ppu_read(0x0010), 7);
+}
+
+#[test]
+fn test_nrom_chr_ram() {
+    let mut mapper = nrom(1, Vec::new());
+    mapper.ppu_write(0x1FFF, 0x42);
+    assert_eq!(mapper.ppu_read(0x1FFF), 0x42);
+}
+
+#[test]
+fn test_from_rom_reports_mirroring() {
+    let mapper = from_rom(test_rom()).unwrap();
+    assert_eq!(mapper.borrow().mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn test_from_rom_empty_prg() {
+    for mapper in [0, 1, 2, 3, 4, 7, 11, 66] {
+        let mut rom = test_rom();
+        rom.mapper = mapper;
+        rom.prg_rom = Vec::new();
+        assert_eq!(from_rom(rom).err(), Some("Cartridge has no PRG ROM".to_string()));
+    }
+}
+
+#[test]
+fn test_from_rom_unknown_mapper() {
+    let mut rom = test_rom();
+    rom.mapper = 5;
+    assert_eq!(from_rom(rom).err(), Some("Mapper 5 is not supported".to_string()));
+}
+
+// 128 KB of PRG and CHR, each byte holding the number of its 16 KB PRG or
+// 4 KB CHR bank
+fn mmc1() -> MMC1 {
+    let prg_rom = (0..0x20000).map(|i| (i / 0x4000) as u8).collect();
+    let chr_rom = (0..0x20000).map(|i| (i / 0x1000) as u8).collect();
+
+    MMC1::new(Rom {
+        prg_rom,
+        chr_rom,
+        mapper: 1,
+        screen_mirroring: Mirroring::Vertical,
+    })
+}
+
+fn mmc1_write(mapper: &mut MMC1, addr: u16, value: u8) {
+    for bit in 0..5 {
+        mapper.cpu_write(addr, (value >> bit) & 1);
+        mapper.cpu_tick(2);
+    }
+}
+
+#[test]
+fn test_mmc1_power_on_fixes_last_bank() {
+    let mut mapper = mmc1();
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+    assert_eq!(mapper.cpu_read(0xC000), Some(7));
+
+    mmc1_write(&mut mapper, 0xE000, 2);
+    assert_eq!(mapper.cpu_read(0x8000), Some(2));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(7));
+}
+
+#[test]
+fn test_mmc1_small_prg() {
+    let mut mapper = MMC1::new(Rom {
+        prg_rom: vec![0x42; 0x2000],
+        chr_rom: Vec::new(),
+        mapper: 1,
+        screen_mirroring: Mirroring::Vertical,
+    });
+    assert_eq!(mapper.cpu_read(0x8000), Some(0x42));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(0x42));
+}
+
+#[test]
+fn test_mmc1_prg_fix_first() {
+    let mut mapper = mmc1();
+    mmc1_write(&mut mapper, 0x8000, 0b0_1000);
+    mmc1_write(&mut mapper, 0xE000, 5);
+
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+    assert_eq!(mapper.cpu_read(0xC000), Some(5));
+}
+
+#[test]
+fn test_mmc1_prg_32k() {
+    let mut mapper = mmc1();
+    mmc1_write(&mut mapper, 0x8000, 0b0_0000);
+    mmc1_write(&mut mapper, 0xE000, 3);
+
+    // the low bit of the bank number is ignored
+    assert_eq!(mapper.cpu_read(0x8000), Some(2));
+    assert_eq!(mapper.cpu_read(0xC000), Some(3));
+}
+
+#[test]
+fn test_mmc1_chr_banks() {
+    let mut mapper = mmc1();
+    mmc1_write(&mut mapper, 0xA000, 5);
+    mmc1_write(&mut mapper, 0xC000, 9);
+
+    // 8 KB mode ignores the low bit and the second register
+    assert_eq!(mapper.ppu_read(0x0000), 4);
+    assert_eq!(mapper.ppu_read(0x1000), 5);
+
+    mmc1_write(&mut mapper, 0x8000, 0b1_1100);
+    assert_eq!(mapper.ppu_read(0x0000), 5);
+    assert_eq!(mapper.ppu_read(0x1FFF), 9);
+}
+
+#[test]
+fn test_mmc1_mirroring() {
+    let mut mapper = mmc1();
+    let expected = [
+        Mirroring::SingleScreenA,
+        Mirroring::SingleScreenB,
+        Mirroring::Vertical,
+        Mirroring::Horizontal,
+    ];
+
+    for (value, mirroring) in expected.iter().enumerate() {
+        mmc1_write(&mut mapper, 0x8000, 0b0_1100 | value as u8);
+        assert_eq!(mapper.mirroring(), *mirroring);
+    }
+}
+
+#[test]
+fn test_mmc1_reset_clears_shift_register() {
+    let mut mapper = mmc1();
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_tick(2);
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_tick(2);
+    mapper.cpu_write(0x8000, 0x80);
+    mapper.cpu_tick(2);
+
+    mmc1_write(&mut mapper, 0xE000, 4);
+    assert_eq!(mapper.cpu_read(0x8000), Some(4));
+}
+
+#[test]
+fn test_mmc1_ignores_consecutive_writes() {
+    let mut mapper = mmc1();
+
+    // the second write of a read-modify-write lands on the next cycle
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_tick(1);
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_tick(2);
+
+    for _ in 0..4 {
+        mapper.cpu_write(0xE000, 0);
+        mapper.cpu_tick(2);
+    }
+
+    assert_eq!(mapper.cpu_read(0x8000), Some(1));
+}
+
+#[test]
+fn test_mmc1_prg_ram() {
+    let mut mapper = mmc1();
+    mapper.cpu_write(0x6000, 0x42);
+    mapper.cpu_write(0x7FFF, 0x24);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+    assert_eq!(mapper.cpu_read(0x7FFF), Some(0x24));
+
+    mmc1_write(&mut mapper, 0xE000, 0b1_0000);
+    assert_eq!(mapper.cpu_read(0x6000), None);
+}
+
+// 128 KB of PRG and CHR, each byte holding the number of its 8 KB PRG or
+// 1 KB CHR bank
+fn mmc3() -> MMC3 {
+    let prg_rom = (0..0x20000).map(|i| (i / 0x2000) as u8).collect();
+    let chr_rom = (0..0x20000).map(|i| (i / 0x0400) as u8).collect();
+
+    MMC3::new(Rom {
+        prg_rom,
+        chr_rom,
+        mapper: 4,
+        screen_mirroring: Mirroring::Vertical,
+    })
+}
+
+fn mmc3_banks(mapper: &mut MMC3, mode: u8, banks: [u8; 8]) {
+    for (register, bank) in banks.iter().enumerate() {
+        mapper.cpu_write(0x8000, mode | register as u8);
+        mapper.cpu_write(0x8001, *bank);
+    }
+}
+
+// Rising edge of A12 after it has been low long enough, like the PPU going
+// from background to sprite fetches
+fn mmc3_scanline(mapper: &mut MMC3) {
+    mapper.ppu_read(0x0000);
+    mapper.cpu_tick(100);
+    mapper.ppu_read(0x1000);
+}
+
+#[test]
+fn test_mmc3_prg_modes() {
+    let mut mapper = mmc3();
+    mmc3_banks(&mut mapper, 0, [0, 0, 0, 0, 0, 0, 3, 5]);
+
+    assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    assert_eq!(mapper.cpu_read(0xA000), Some(5));
+    assert_eq!(mapper.cpu_read(0xC000), Some(14));
+    assert_eq!(mapper.cpu_read(0xE000), Some(15));
+
+    mapper.cpu_write(0x8000, 0b0100_0000);
+    assert_eq!(mapper.cpu_read(0x8000), Some(14));
+    assert_eq!(mapper.cpu_read(0xA000), Some(5));
+    assert_eq!(mapper.cpu_read(0xC000), Some(3));
+    assert_eq!(mapper.cpu_read(0xE000), Some(15));
+}
+
+#[test]
+fn test_mmc3_single_prg_bank() {
+    let mut mapper = MMC3::new(rom(4, vec![0x42; 0x2000], Vec::new()));
+    assert_eq!(mapper.cpu_read(0x8000), Some(0x42));
+    assert_eq!(mapper.cpu_read(0xC000), Some(0x42));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(0x42));
+
+    mapper.cpu_write(0x8000, 0b0100_0000);
+    assert_eq!(mapper.cpu_read(0x8000), Some(0x42));
+}
+
+#[test]
+fn test_mmc3_chr_banks() {
+    let mut mapper = mmc3();
+    mmc3_banks(&mut mapper, 0, [9, 20, 30, 31, 32, 33, 0, 0]);
+
+    // 2 KB banks ignore the low bit
+    assert_eq!(mapper.ppu_read(0x0000), 8);
+    assert_eq!(mapper.ppu_read(0x0400), 9);
+    assert_eq!(mapper.ppu_read(0x0800), 20);
+    assert_eq!(mapper.ppu_read(0x0C00), 21);
+    assert_eq!(mapper.ppu_read(0x1000), 30);
+    assert_eq!(mapper.ppu_read(0x1FFF), 33);
+
+    mapper.cpu_write(0x8000, 0b1000_0000);
+    assert_eq!(mapper.ppu_read(0x0000), 30);
+    assert_eq!(mapper.ppu_read(0x0C00), 33);
+    assert_eq!(mapper.ppu_read(0x1000), 8);
+    assert_eq!(mapper.ppu_read(0x1C00), 21);
+}
+
+#[test]
+fn test_mmc3_mirroring() {
+    let mut mapper = mmc3();
+    mapper.cpu_write(0xA000, 1);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    mapper.cpu_write(0xA000, 0);
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn test_mmc3_prg_ram_protect() {
+    let mut mapper = mmc3();
+    mapper.cpu_write(0xA001, 0b1000_0000);
+    mapper.cpu_write(0x6000, 0x42);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+
+    mapper.cpu_write(0xA001, 0b1100_0000);
+    mapper.cpu_write(0x6000, 0x24);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+
+    mapper.cpu_write(0xA001, 0);
+    assert_eq!(mapper.cpu_read(0x6000), None);
+}
+
+#[test]
+fn test_mmc3_irq_counter() {
+    let mut mapper = mmc3();
+    mapper.cpu_write(0xC000, 2);
+    mapper.cpu_write(0xC001, 0);
+    mapper.cpu_write(0xE001, 0);
+
+    // reload, then count 2 down to 0
+    mmc3_scanline(&mut mapper);
+    mmc3_scanline(&mut mapper);
+    assert!(!mapper.irq_pending());
+    mmc3_scanline(&mut mapper);
+    assert!(mapper.irq_pending());
+
+    mapper.cpu_write(0xE000, 0);
+    assert!(!mapper.irq_pending());
+}
+
+#[test]
+fn test_mmc3_a12_filter() {
+    let mut mapper = mmc3();
+    mapper.cpu_write(0xC000, 0);
+    mapper.cpu_write(0xE001, 0);
+    mmc3_scanline(&mut mapper);
+    mapper.cpu_write(0xE000, 0);
+    mapper.cpu_write(0xE001, 0);
+
+    // A12 dipping low for a moment does not count
+    mapper.ppu_read(0x0000);
+    mapper.cpu_tick(1);
+    mapper.ppu_read(0x1000);
+    assert!(!mapper.irq_pending());
+
+    mmc3_scanline(&mut mapper);
+    assert!(mapper.irq_pending());
+}
+
+// Each byte holds the number of its bank
+fn labeled(size: usize, bank_size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i / bank_size) as u8).collect()
+}
+
+fn rom(mapper: u8, prg_rom: Vec<u8>, chr_rom: Vec<u8>) -> Rom {
+    Rom {
+        prg_rom,
+        chr_rom,
+        mapper,
+        screen_mirroring: Mirroring::Horizontal,
+    }
+}
+
+#[test]
+fn test_uxrom() {
+    let mut mapper = UxROM::new(rom(2, labeled(0x20000, 0x4000), Vec::new()));
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+    assert_eq!(mapper.cpu_read(0xC000), Some(7));
+
+    // bus conflict with the 7 stored at $C000
+    mapper.cpu_write(0xC000, 0b1101);
+    assert_eq!(mapper.cpu_read(0x8000), Some(5));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(7));
+
+    mapper.ppu_write(0x0123, 0x42);
+    assert_eq!(mapper.ppu_read(0x0123), 0x42);
+}
+
+#[test]
+fn test_uxrom_small_prg() {
+    let mut mapper = UxROM::new(rom(2, vec![0x42; 0x2000], Vec::new()));
+    assert_eq!(mapper.cpu_read(0x8000), Some(0x42));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(0x42));
+}
+
+#[test]
+fn test_cnrom() {
+    let mut mapper = CNROM::new(rom(3, vec![0xFF; 0x4000], labeled(0x8000, 0x2000)));
+    mapper.cpu_write(0x8000, 2);
+    assert_eq!(mapper.ppu_read(0x0000), 2);
+    assert_eq!(mapper.ppu_read(0x1FFF), 2);
+    assert_eq!(mapper.cpu_read(0xC000), Some(0xFF));
+
+    let mut mapper = CNROM::new(rom(3, vec![0x01; 0x4000], labeled(0x8000, 0x2000)));
+    mapper.cpu_write(0x8000, 3);
+    assert_eq!(mapper.ppu_read(0x0000), 1);
+}
+
+#[test]
+fn test_axrom() {
+    let mut mapper = AxROM::new(rom(7, labeled(0x20000, 0x8000), Vec::new()));
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenA);
+
+    mapper.cpu_write(0x8000, 0b1_0011);
+    assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(3));
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenB);
+}
+
+#[test]
+fn test_gxrom() {
+    let mut prg_rom = labeled(0x20000, 0x8000);
+    prg_rom[0] = 0xFF;
+    let mut mapper = GxROM::new(rom(66, prg_rom, labeled(0x8000, 0x2000)));
+
+    mapper.cpu_write(0x8000, 0b0010_0011);
+    assert_eq!(mapper.cpu_read(0x8001), Some(2));
+    assert_eq!(mapper.ppu_read(0x0000), 3);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+    // bus conflict with the 2 stored at $8001 in bank 2
+    mapper.cpu_write(0x8001, 0b0011_0001);
+    assert_eq!(mapper.cpu_read(0x8001), Some(0));
+    assert_eq!(mapper.ppu_read(0x0000), 0);
+}
+
+#[test]
+fn test_color_dreams() {
+    let mut prg_rom = labeled(0x20000, 0x8000);
+    prg_rom[0] = 0xFF;
+    let mut mapper = ColorDreams::new(rom(11, prg_rom, labeled(0x20000, 0x2000)));
+
+    mapper.cpu_write(0x8000, 0b0101_0010);
+    assert_eq!(mapper.cpu_read(0x8001), Some(2));
+    assert_eq!(mapper.ppu_read(0x1000), 5);
+
+    // bus conflict with the 2 stored at $8001 in bank 2
+    mapper.cpu_write(0x8001, 0b0111_0011);
+    assert_eq!(mapper.cpu_read(0x8001), Some(2));
+    assert_eq!(mapper.ppu_read(0x1000), 0);
+}
+
+#[test]
+fn test_chr_banked_boards_without_chr_rom() {
+    let mut mappers: Vec<Box<dyn Mapper>> = vec![
+        Box::new(CNROM::new(rom(3, vec![0xFF; 0x8000], Vec::new()))),
+        Box::new(GxROM::new(rom(66, vec![0xFF; 0x8000], Vec::new()))),
+        Box::new(ColorDreams::new(rom(11, vec![0xFF; 0x8000], Vec::new()))),
+    ];
+
+    // 8 KB of CHR RAM, whichever bank is picked
+    for mapper in mappers.iter_mut() {
+        mapper.ppu_write(0x1234, 0x42);
+        mapper.cpu_write(0x8000, 0xFF);
+        assert_eq!(mapper.ppu_read(0x1234), 0x42);
+    }
+}
+
+fn round_trip(from: &dyn Mapper, to: &mut dyn Mapper) {
+    let mut w = StateWriter::new();
+    from.save_state(&mut w);
+    let data = w.into_bytes();
+
+    let mut r = StateReader::new(&data);
+    to.load_state(&mut r).unwrap();
+    assert!(r.is_empty());
+}
+
+#[test]
+fn test_mmc1_save_state() {
+    let new = || MMC1::new(rom(1, labeled(0x20000, 0x4000), Vec::new()));
+    let mut mapper = new();
+    mmc1_write(&mut mapper, 0x8000, 0b0_1110);
+    mmc1_write(&mut mapper, 0xE000, 0b0101);
+    mapper.cpu_write(0x6000, 0x42);
+    mapper.ppu_write(0x0010, 0x24);
+
+    let mut loaded = new();
+    round_trip(&mapper, &mut loaded);
+
+    assert_eq!(loaded.cpu_read(0x8000), Some(5));
+    assert_eq!(loaded.cpu_read(0x6000), Some(0x42));
+    assert_eq!(loaded.ppu_read(0x0010), 0x24);
+    assert_eq!(loaded.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn test_mmc3_save_state() {
+    let mut mapper = mmc3();
+    mmc3_banks(&mut mapper, 0, [0, 2, 4, 5, 6, 7, 3, 1]);
+    mapper.cpu_write(0xA000, 1);
+    mapper.cpu_write(0xC000, 2);
+    mapper.cpu_write(0xC001, 0);
+    mapper.cpu_write(0xE001, 0);
+    mmc3_scanline(&mut mapper);
+
+    let mut loaded = mmc3();
+    round_trip(&mapper, &mut loaded);
+
+    assert_eq!(loaded.cpu_read(0x8000), Some(3));
+    assert_eq!(loaded.mirroring(), Mirroring::Horizontal);
+
+    // the counter picks up where it was, two more lines to the IRQ
+    mmc3_scanline(&mut mapper);
+    mmc3_scanline(&mut loaded);
+    mmc3_scanline(&mut mapper);
+    mmc3_scanline(&mut loaded);
+    assert!(mapper.irq_pending());
+    assert!(loaded.irq_pending());
+}
+
+#[test]
+fn test_chr_rom_not_saved() {
+    let mut mapper = CNROM::new(rom(3, vec![0xFF; 0x8000], labeled(0x8000, 0x2000)));
+    mapper.cpu_write(0x8000, 2);
+
+    let mut w = StateWriter::new();
+    mapper.save_state(&mut w);
+    assert_eq!(w.into_bytes(), vec![2]);
+}
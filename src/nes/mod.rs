@@ -0,0 +1,178 @@
+use crate::bus::Bus;
+use crate::checksum::crc32;
+use crate::cpu::{StepResult, CPU};
+use crate::error::EmulatorError;
+use crate::joypad::JoypadButton;
+use crate::ppu::frame::Frame;
+use crate::rom::Rom;
+use crate::savestate::{self, Snapshot, StateReader, StateWriter};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+/*
+ * The whole console, for anything that wants to run games without caring how
+ * the CPU, bus, PPU and APU are wired together. The cartridge is kept around
+ * so the console can be power cycled, which throws away all of the machine's
+ * state and builds it again from the ROM.
+ */
+pub struct Nes {
+    cpu: CPU,
+    rom: Rom,
+    rom_crc: u32,
+    sample_rate: Option<u32>,
+}
+
+impl Nes {
+    // fails if the cartridge's board isn't one we have
+    pub fn new(rom: Rom) -> Result<Self, String> {
+        let mut nes = Nes {
+            cpu: CPU::new(Bus::new(rom.clone())?),
+            rom_crc: crc32(&rom.prg_rom),
+            rom,
+            sample_rate: None,
+        };
+        nes.reset();
+        Ok(nes)
+    }
+
+    // A cartridge with a board we don't have is turned away, leaving the
+    // current game running
+    pub fn load_rom(&mut self, rom: Rom) -> Result<(), String> {
+        self.cpu = self.fresh_cpu(rom.clone())?;
+        self.rom_crc = crc32(&rom.prg_rom);
+        self.rom = rom;
+        self.reset();
+        Ok(())
+    }
+
+    // the reset button, the CPU starts again from the reset vector and the
+    // PPU and APU registers it's wired to are cleared
+    pub fn reset(&mut self) {
+        self.cpu.bus.reset();
+        self.cpu.reset();
+    }
+
+    pub fn power_cycle(&mut self) {
+        self.cpu = self.fresh_cpu(self.rom.clone())
+            .expect("the cartridge's board was checked when it was loaded");
+        self.reset();
+    }
+
+    // the same kind of CPU, anything else about the machine starts over
+    fn fresh_cpu(&self, rom: Rom) -> Result<CPU, String> {
+        let mut bus = Bus::new(rom)?;
+        if let Some(rate) = self.sample_rate {
+            bus.set_sample_rate(rate);
+        }
+        let mut cpu = CPU::new(bus);
+        cpu.variant = self.cpu.variant;
+        Ok(cpu)
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        for byte in savestate::MAGIC {
+            w.write_u8(byte);
+        }
+        w.write_u16(savestate::VERSION);
+        w.write_u8(self.rom.mapper);
+        w.write_u32(self.rom_crc);
+        self.cpu.save_state(&mut w);
+        w.into_bytes()
+    }
+
+    // The state goes into a new machine which only replaces this one once it
+    // has loaded, so a bad state leaves the running game alone.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut r = StateReader::new(data);
+
+        let mut magic = [0; 4];
+        for byte in magic.iter_mut() {
+            *byte = r.read_u8()?;
+        }
+        if magic != savestate::MAGIC {
+            return Err("Not a save state".to_string());
+        }
+
+        let version = r.read_u16()?;
+        if version != savestate::VERSION {
+            return Err(format!(
+                "Save state is version {}, only version {} is supported", version, savestate::VERSION
+            ));
+        }
+
+        let mapper = r.read_u8()?;
+        let rom_crc = r.read_u32()?;
+        if mapper != self.rom.mapper || rom_crc != self.rom_crc {
+            return Err("Save state is for a different game".to_string());
+        }
+
+        let mut cpu = self.fresh_cpu(self.rom.clone())?;
+        cpu.load_state(&mut r)?;
+        if !r.is_empty() {
+            return Err("Save state has trailing data".to_string());
+        }
+
+        self.cpu = cpu;
+        Ok(())
+    }
+
+    pub fn step_instruction(&mut self) -> StepResult {
+        self.cpu.step()
+    }
+
+    // false if `stop` was called before the frame was done
+    pub fn run_frame(&mut self) -> Result<bool, EmulatorError> {
+        self.cpu.run_frame()
+    }
+
+    pub fn stop(&mut self) {
+        self.cpu.stop();
+    }
+
+    pub fn take_diagnostic(&mut self) -> Option<EmulatorError> {
+        self.cpu.take_diagnostic()
+    }
+
+    pub fn frame_buffer(&self) -> &Frame {
+        self.cpu.bus.frame()
+    }
+
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = Some(rate);
+        self.cpu.bus.set_sample_rate(rate);
+    }
+
+    pub fn set_rate_adjust(&mut self, adjust: f64) {
+        self.cpu.bus.set_rate_adjust(adjust);
+    }
+
+    // everything the APU has produced since the last call, nothing until a
+    // sample rate is set
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.cpu.bus.take_samples()
+    }
+
+    pub fn set_buttons(&mut self, player: Player, buttons: JoypadButton) {
+        match player {
+            Player::One => self.cpu.bus.joypad1.set_buttons(buttons),
+            Player::Two => self.cpu.bus.joypad2.set_buttons(buttons),
+        }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    // for debuggers and tracing
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+}
@@ -0,0 +1,213 @@
+use super::*;
+use crate::cpu::CpuVariant;
+use crate::mem::Mem;
+use crate::rom::tests::{busy_rom, nop_rom, test_rom};
+
+#[test]
+fn test_starts_at_reset_vector() {
+    let nes = Nes::new(nop_rom()).unwrap();
+    assert_eq!(nes.cpu().program_counter, 0x8000);
+}
+
+#[test]
+fn test_step_instruction() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    let step = nes.step_instruction();
+    assert_eq!(step.opcode, Some(0xEA));
+    assert_eq!(step.cycles, 2);
+    assert_eq!(nes.step_instruction(), step);
+    assert_eq!(nes.cpu().program_counter, 0x8002);
+}
+
+#[test]
+fn test_run_frame() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.cpu_mut().mem_write(0x2001, 0b0000_1000);
+    assert_eq!(nes.run_frame(), Ok(true));
+
+    // a frame is 29780.5 CPU cycles, NOPs take 2
+    let pc = nes.cpu().program_counter;
+    assert!((0x8000..0xF003).contains(&pc));
+
+    // the backdrop, palette entry 0
+    assert!(nes.frame_buffer().data.iter().all(|&color| color == 0));
+}
+
+#[test]
+fn test_reset_reaches_the_apu() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.run_frame().unwrap();
+    nes.run_frame().unwrap();
+    assert!(!nes.cpu().bus.irq_line().is_empty());
+
+    nes.reset();
+    assert!(nes.cpu().bus.irq_line().is_empty());
+}
+
+#[test]
+fn test_power_cycle_clears_state() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.cpu_mut().mem_write(0x0010, 0x55);
+    nes.step_instruction();
+
+    nes.reset();
+    assert_eq!(nes.cpu_mut().mem_read(0x0010), 0x55);
+
+    nes.power_cycle();
+    assert_eq!(nes.cpu_mut().mem_read(0x0010), 0x00);
+    assert_eq!(nes.cpu().program_counter, 0x8000);
+}
+
+#[test]
+fn test_load_rom() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.load_rom(test_rom()).unwrap();
+
+    // test_rom() is filled with 1s, including the reset vector
+    assert_eq!(nes.cpu().program_counter, 0x0101);
+}
+
+#[test]
+fn test_unknown_mapper() {
+    let mut rom = test_rom();
+    rom.mapper = 5;
+    assert_eq!(Nes::new(rom.clone()).err(), Some("Mapper 5 is not supported".to_string()));
+
+    // and the game that was running carries on
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.step_instruction();
+    assert!(nes.load_rom(rom).is_err());
+    assert_eq!(nes.cpu().program_counter, 0x8001);
+}
+
+#[test]
+fn test_audio_samples() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.run_frame().unwrap();
+    assert!(nes.audio_samples().is_empty());
+
+    nes.set_sample_rate(48000);
+    nes.run_frame().unwrap();
+    // 48000 / 60.0988, give or take where the frame ended
+    assert!((798..=800).contains(&nes.audio_samples().len()));
+
+    // the rate survives a power cycle
+    nes.power_cycle();
+    nes.run_frame().unwrap();
+    assert!(!nes.audio_samples().is_empty());
+}
+
+#[test]
+fn test_set_buttons() {
+    let mut nes = Nes::new(nop_rom()).unwrap();
+    nes.set_buttons(Player::One, JoypadButton::START);
+    nes.set_buttons(Player::Two, JoypadButton::BUTTON_A | JoypadButton::UP);
+
+    assert_eq!(nes.cpu().bus.joypad1.buttons().bits(), JoypadButton::START.bits());
+    assert_eq!(nes.cpu().bus.joypad2.buttons().bits(), 0b0001_0001);
+}
+
+// The resampler's filters aren't part of the state, so the samples themselves
+// come out a little different after a load but there are as many of them
+fn fingerprint(nes: &mut Nes) -> (u16, u8, u8, Vec<u8>, usize) {
+    let pc = nes.cpu().program_counter;
+    let a = nes.cpu().register_a;
+    let count = nes.cpu_mut().mem_read(0x0000);
+    let frame = nes.frame_buffer().data.clone();
+    (pc, a, count, frame, nes.audio_samples().len())
+}
+
+#[test]
+fn test_save_state_resumes_exactly() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    nes.set_sample_rate(44100);
+    nes.cpu_mut().mem_write(0x2001, 0b0001_1110);
+    for _ in 0..10 {
+        nes.run_frame().unwrap();
+    }
+    nes.audio_samples();
+    let state = nes.save_state();
+
+    for _ in 0..20 {
+        nes.run_frame().unwrap();
+    }
+    let expected = fingerprint(&mut nes);
+
+    nes.load_state(&state).unwrap();
+    for _ in 0..20 {
+        nes.run_frame().unwrap();
+    }
+    assert!(fingerprint(&mut nes) == expected);
+
+    // and saving again gives the same bytes
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.save_state(), state);
+}
+
+#[test]
+fn test_load_state_into_new_console() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    for _ in 0..5 {
+        nes.run_frame().unwrap();
+    }
+    let state = nes.save_state();
+
+    let mut other = Nes::new(busy_rom()).unwrap();
+    other.load_state(&state).unwrap();
+    assert_eq!(other.save_state(), state);
+    assert_eq!(other.cpu_mut().mem_read(0x0000), nes.cpu_mut().mem_read(0x0000));
+}
+
+#[test]
+fn test_cpu_variant_is_kept() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    nes.cpu_mut().variant = CpuVariant::WDC65C02;
+    nes.run_frame().unwrap();
+    let state = nes.save_state();
+
+    nes.power_cycle();
+    assert_eq!(nes.cpu().variant, CpuVariant::WDC65C02);
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.cpu().variant, CpuVariant::WDC65C02);
+
+    // a state brings its own
+    let mut other = Nes::new(busy_rom()).unwrap();
+    other.load_state(&state).unwrap();
+    assert_eq!(other.cpu().variant, CpuVariant::WDC65C02);
+}
+
+// states from before the CPU variant was saved would load a byte out of step
+#[test]
+fn test_load_state_rejects_version_4() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    let mut state = nes.save_state();
+    state[4..6].copy_from_slice(&4u16.to_le_bytes());
+
+    let error = format!("Save state is version 4, only version {} is supported", savestate::VERSION);
+    assert_eq!(nes.load_state(&state), Err(error));
+}
+
+#[test]
+fn test_load_state_rejects_bad_states() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    nes.run_frame().unwrap();
+    let state = nes.save_state();
+    let before = nes.save_state();
+
+    assert!(nes.load_state(b"not a state").is_err());
+    assert!(nes.load_state(&state[..state.len() - 1]).is_err());
+
+    let mut version = state.clone();
+    version[4] = 0xFF;
+    assert!(nes.load_state(&version).is_err());
+
+    let mut trailing = state.clone();
+    trailing.push(0);
+    assert!(nes.load_state(&trailing).is_err());
+
+    let mut other_game = Nes::new(nop_rom()).unwrap();
+    assert!(other_game.load_state(&state).is_err());
+
+    // none of which touched the running machine
+    assert_eq!(nes.save_state(), before);
+}
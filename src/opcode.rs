@@ -1,6 +1,8 @@
 use crate::cpu::AddressingMode;
 use std::collections::HashMap;
 
+// How long an instruction takes. The CPU doesn't count these, its timing
+// comes from making one bus access a cycle, but they're what that adds up to.
 pub enum CycleBehavior {
     Constant(u8),
     PageCross(u8),  // +1 if page crossed
@@ -273,9 +275,9 @@ lazy_static! {
         OpCode::new_undoc(0xA3, "LAX", 2, 6, AddressingMode::Indirect_X),
         OpCode::new_undoc(0xA7, "LAX", 2, 3, AddressingMode::ZeroPage),
         OpCode::new_undoc(0xAF, "LAX", 3, 4, AddressingMode::Absolute),
-        OpCode::new_undoc(0xB3, "LAX", 2, 5, AddressingMode::Indirect_Y),
+        OpCode::new_undoc(0xB3, "LAX", 2, Cycles::PageCross(5), AddressingMode::Indirect_Y),
         OpCode::new_undoc(0xB7, "LAX", 2, 4, AddressingMode::ZeroPage_Y),
-        OpCode::new_undoc(0xBF, "LAX", 3, 4, AddressingMode::Absolute_Y),
+        OpCode::new_undoc(0xBF, "LAX", 3, Cycles::PageCross(4), AddressingMode::Absolute_Y),
 
         // Unofficial SAX
         // Store AND of A and X (no flags affected)
@@ -343,7 +345,7 @@ lazy_static! {
         OpCode::new_undoc(0x67, "RRA", 2, 5, AddressingMode::ZeroPage),
         OpCode::new_undoc(0x6F, "RRA", 3, 6, AddressingMode::Absolute),
         OpCode::new_undoc(0x73, "RRA", 2, 8, AddressingMode::Indirect_Y),
-        OpCode::new_undoc(0x77, "RRA", 2, 8, AddressingMode::ZeroPage_X),
+        OpCode::new_undoc(0x77, "RRA", 2, 6, AddressingMode::ZeroPage_X),
         OpCode::new_undoc(0x7B, "RRA", 3, 7, AddressingMode::Absolute_Y),
         OpCode::new_undoc(0x7F, "RRA", 3, 7, AddressingMode::Absolute_X),
 
@@ -396,7 +398,7 @@ lazy_static! {
 
         // Unofficial LAR
         // AND memory with SP, transfer result to acc, X, and SP
-        OpCode::new_undoc(0xBB, "LAR", 3, 4, AddressingMode::Absolute_Y),
+        OpCode::new_undoc(0xBB, "LAR", 3, Cycles::PageCross(4), AddressingMode::Absolute_Y),
 
         // SXA
         // Similar to AXA, AND X with hi byte of target address + 1
@@ -425,5 +427,153 @@ lazy_static! {
         }
         map
     };
+
+    /*
+     * What the WDC 65C02 adds to the documented NMOS set, or changes in it.
+     * Most of the new instructions took over opcodes that are undocumented on
+     * the NMOS chip. The 65C02 has none of those, its other unused opcodes
+     * are NOPs of various lengths, the last of them below.
+     */
+    pub static ref CPU_65C02_OP_CODES: Vec<OpCode> = vec![
+        OpCode::new(0x80, "BRA", 2, Cycles::Branch(2), AddressingMode::None),
+
+        OpCode::new(0x89, "BIT", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x34, "BIT", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x3C, "BIT", 3, Cycles::PageCross(4), AddressingMode::Absolute_X),
+
+        OpCode::new(0x1A, "INC", 1, 2, AddressingMode::None),
+        OpCode::new(0x3A, "DEC", 1, 2, AddressingMode::None),
+
+        // the indirect JMP no longer wraps within the page, which costs a cycle
+        OpCode::new(0x6C, "JMP", 3, 6, AddressingMode::None),
+        OpCode::new(0x7C, "JMP", 3, 6, AddressingMode::None),
+
+        OpCode::new(0xDA, "PHX", 1, 3, AddressingMode::None),
+        OpCode::new(0x5A, "PHY", 1, 3, AddressingMode::None),
+        OpCode::new(0xFA, "PLX", 1, 4, AddressingMode::None),
+        OpCode::new(0x7A, "PLY", 1, 4, AddressingMode::None),
+
+        OpCode::new(0x64, "STZ", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x74, "STZ", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x9C, "STZ", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x9E, "STZ", 3, 5, AddressingMode::Absolute_X),
+
+        OpCode::new(0x14, "TRB", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x1C, "TRB", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x04, "TSB", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x0C, "TSB", 3, 6, AddressingMode::Absolute),
+
+        // (zp), the pointer in the zero page without any indexing
+        OpCode::new(0x12, "ORA", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0x32, "AND", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0x52, "EOR", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0x72, "ADC", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0x92, "STA", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0xB2, "LDA", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0xD2, "CMP", 2, 5, AddressingMode::ZeroPage_Indirect),
+        OpCode::new(0xF2, "SBC", 2, 5, AddressingMode::ZeroPage_Indirect),
+
+        // WDC's bit instructions: RMB and SMB clear or set one bit of a zero
+        // page byte, BBR and BBS branch on one
+        OpCode::new(0x07, "RMB0", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x17, "RMB1", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x27, "RMB2", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x37, "RMB3", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x47, "RMB4", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x57, "RMB5", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x67, "RMB6", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x77, "RMB7", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x87, "SMB0", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x97, "SMB1", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xA7, "SMB2", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xB7, "SMB3", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xC7, "SMB4", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xD7, "SMB5", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xE7, "SMB6", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xF7, "SMB7", 2, 5, AddressingMode::ZeroPage),
+
+        OpCode::new(0x0F, "BBR0", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x1F, "BBR1", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x2F, "BBR2", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x3F, "BBR3", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x4F, "BBR4", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x5F, "BBR5", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x6F, "BBR6", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x7F, "BBR7", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x8F, "BBS0", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0x9F, "BBS1", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xAF, "BBS2", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xBF, "BBS3", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xCF, "BBS4", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xDF, "BBS5", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xEF, "BBS6", 3, Cycles::Branch(5), AddressingMode::None),
+        OpCode::new(0xFF, "BBS7", 3, Cycles::Branch(5), AddressingMode::None),
+
+        // wait for an interrupt, and stop the clock until a reset
+        OpCode::new(0xCB, "WAI", 1, 3, AddressingMode::None),
+        OpCode::new(0xDB, "STP", 1, 3, AddressingMode::None),
+
+        // The unused opcodes. The $x3 and $xB columns take a single cycle,
+        // the rest read an operand and throw it away.
+        OpCode::new_undoc(0x03, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x13, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x23, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x33, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x43, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x53, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x63, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x73, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x83, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x93, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xA3, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xB3, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xC3, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xD3, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xE3, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xF3, "NOP", 1, 1, AddressingMode::None),
+
+        OpCode::new_undoc(0x0B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x1B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x2B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x3B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x4B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x5B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x6B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x7B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x8B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0x9B, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xAB, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xBB, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xEB, "NOP", 1, 1, AddressingMode::None),
+        OpCode::new_undoc(0xFB, "NOP", 1, 1, AddressingMode::None),
+
+        OpCode::new_undoc(0x02, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0x22, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0x42, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0x62, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0x82, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0xC2, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0xE2, "NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new_undoc(0x44, "NOP", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new_undoc(0x54, "NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new_undoc(0xD4, "NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new_undoc(0xF4, "NOP", 2, 4, AddressingMode::ZeroPage_X),
+
+        OpCode::new_undoc(0xDC, "NOP", 3, 4, AddressingMode::Absolute),
+        OpCode::new_undoc(0xFC, "NOP", 3, 4, AddressingMode::Absolute),
+        // reads the operand then spends five more cycles on $FFxx
+        OpCode::new_undoc(0x5C, "NOP", 3, 8, AddressingMode::Absolute),
+    ];
+
+    pub static ref OPCODES_65C02_MAP: HashMap<u8, &'static OpCode> = {
+        let mut map = HashMap::new();
+        for cpuop in CPU_OP_CODES.iter().filter(|op| !op.undocumented) {
+            map.insert(cpuop.code, cpuop);
+        }
+        for cpuop in &*CPU_65C02_OP_CODES {
+            map.insert(cpuop.code, cpuop);
+        }
+        map
+    };
 }
 
@@ -0,0 +1,55 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests;
+
+pub const NTSC_FRAME_RATE: f64 = 60.0988;
+pub const PAL_FRAME_RATE: f64 = 50.007;
+
+// further behind than this and we give up on catching up, which would only
+// mean a burst of frames as fast as the emulator can make them
+const MAX_LAG_FRAMES: u32 = 3;
+
+/*
+ * Keeps the frontend at the console's frame rate. Every deadline is worked out
+ * from when pacing started rather than from when the last sleep returned, so
+ * oversleeping on one frame is made up on the next instead of slowly drifting
+ * away from the real rate.
+ */
+pub struct FramePacer {
+    rate: f64,
+    start: Instant,
+    frames: u64,
+}
+
+impl FramePacer {
+    pub fn new(rate: f64) -> Self {
+        FramePacer {
+            rate,
+            start: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    pub fn wait(&mut self) {
+        if let Some(delay) = self.delay(Instant::now()) {
+            thread::sleep(delay);
+        }
+    }
+
+    // how long from `now` until the next frame is due
+    fn delay(&mut self, now: Instant) -> Option<Duration> {
+        self.frames += 1;
+        let deadline = self.start + Duration::from_secs_f64(self.frames as f64 / self.rate);
+
+        let max_lag = Duration::from_secs_f64(MAX_LAG_FRAMES as f64 / self.rate);
+        if now > deadline + max_lag {
+            self.start = now;
+            self.frames = 0;
+            return None;
+        }
+
+        deadline.checked_duration_since(now)
+    }
+}
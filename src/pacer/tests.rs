@@ -0,0 +1,47 @@
+use super::*;
+
+fn frames(n: u64, rate: f64) -> Duration {
+    Duration::from_secs_f64(n as f64 / rate)
+}
+
+#[test]
+fn test_delay_until_next_frame() {
+    let mut pacer = FramePacer::new(NTSC_FRAME_RATE);
+    let start = pacer.start;
+
+    assert_eq!(pacer.delay(start), Some(frames(1, NTSC_FRAME_RATE)));
+    // the second frame took a whole frame to make, no time to sleep
+    assert_eq!(pacer.delay(start + frames(2, NTSC_FRAME_RATE)), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_oversleep_does_not_drift() {
+    let mut pacer = FramePacer::new(PAL_FRAME_RATE);
+    let start = pacer.start;
+    let late = Duration::from_millis(2);
+
+    // woke up 2ms late, the next frame is 2ms shorter to make up for it
+    pacer.delay(start);
+    let delay = pacer.delay(start + frames(1, PAL_FRAME_RATE) + late).unwrap();
+    assert_eq!(delay, frames(2, PAL_FRAME_RATE) - frames(1, PAL_FRAME_RATE) - late);
+
+    // and after a thousand frames we're still on the real rate
+    for n in 3..1000 {
+        pacer.delay(start + frames(n - 1, PAL_FRAME_RATE) + late);
+    }
+    assert_eq!(pacer.start, start);
+    assert_eq!(pacer.frames, 999);
+}
+
+#[test]
+fn test_resync_when_far_behind() {
+    let mut pacer = FramePacer::new(NTSC_FRAME_RATE);
+    let start = pacer.start;
+
+    let stalled = start + Duration::from_secs(1);
+    assert_eq!(pacer.delay(stalled), None);
+    assert_eq!(pacer.start, stalled);
+
+    // back to one frame at a time from there
+    assert_eq!(pacer.delay(stalled), Some(frames(1, NTSC_FRAME_RATE)));
+}
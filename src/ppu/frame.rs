@@ -0,0 +1,36 @@
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+
+/*
+ * A finished (or in progress) picture as produced by the PPU. Each byte is an
+ * index into the 64 color system palette rather than an RGB value, so the
+ * frontend decides how the final colors look.
+ */
+
+pub struct Frame {
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new() -> Self {
+        Frame {
+            data: vec![0; WIDTH * HEIGHT],
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
+        if x < WIDTH && y < HEIGHT {
+            self.data[y * WIDTH + x] = color;
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.data[y * WIDTH + x]
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Internal PPU scrolling registers, shared by PPUCTRL, PPUSCROLL and PPUADDR.
+ * See https://www.nesdev.org/wiki/PPU_scrolling
+ *
+ * v (current VRAM address) and t (temporary VRAM address) are 15 bits:
+ *
+ * yyy NN YYYYY XXXXX
+ * ||| || ||||| +++++- coarse X scroll
+ * ||| || +++++------- coarse Y scroll
+ * ||| ++------------- nametable select
+ * +++---------------- fine Y scroll
+ *
+ * x is the 3 bit fine X scroll and w the shared first/second write toggle.
+ */
+
+const COARSE_X: u16     = 0x001F;
+const COARSE_Y: u16     = 0x03E0;
+const NAMETABLE_X: u16  = 0x0400;
+const NAMETABLE_Y: u16  = 0x0800;
+const NAMETABLE: u16    = NAMETABLE_X | NAMETABLE_Y;
+const FINE_Y: u16       = 0x7000;
+
+const HORIZONTAL: u16 = COARSE_X | NAMETABLE_X;
+const VERTICAL: u16 = COARSE_Y | NAMETABLE_Y | FINE_Y;
+
+const ADDR_MIRROR_MASK: u16 = 0b0011_1111_1111_1111;
+
+pub struct LoopyRegister {
+    pub v: u16,
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+}
+
+impl LoopyRegister {
+    pub fn new() -> Self {
+        LoopyRegister {
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+        }
+    }
+
+    pub fn write_ctrl(&mut self, value: u8) {
+        self.t = (self.t & !NAMETABLE) | (((value & 0b11) as u16) << 10);
+    }
+
+    pub fn reset_latch(&mut self) {
+        self.w = false;
+    }
+
+    pub fn write_scroll(&mut self, value: u8) {
+        if !self.w {
+            self.t = (self.t & !COARSE_X) | (value >> 3) as u16;
+            self.x = value & 0b111;
+        } else {
+            self.t = (self.t & !(COARSE_Y | FINE_Y))
+                | (((value >> 3) as u16) << 5)
+                | (((value & 0b111) as u16) << 12);
+        }
+        self.w = !self.w;
+    }
+
+    pub fn write_addr(&mut self, value: u8) {
+        if !self.w {
+            // bit 14 of t is cleared by the first write
+            self.t = (self.t & 0x00FF) | (((value & 0b0011_1111) as u16) << 8);
+        } else {
+            self.t = (self.t & 0xFF00) | value as u16;
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    pub fn addr(&self) -> u16 {
+        self.v & ADDR_MIRROR_MASK
+    }
+
+    pub fn increment(&mut self, inc: u8) {
+        self.v = self.v.wrapping_add(inc as u16) & 0x7FFF;
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.x
+    }
+
+    pub fn fine_y(&self) -> u16 {
+        (self.v & FINE_Y) >> 12
+    }
+
+    pub fn tile_addr(&self) -> u16 {
+        0x2000 | (self.v & 0x0FFF)
+    }
+
+    pub fn attribute_addr(&self) -> u16 {
+        0x23C0 | (self.v & NAMETABLE) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07)
+    }
+
+    // Which quadrant of the attribute byte the current tile uses, as a shift
+    pub fn attribute_shift(&self) -> u8 {
+        (((self.v >> 4) & 0b100) | (self.v & 0b10)) as u8
+    }
+
+    pub fn increment_x(&mut self) {
+        if self.v & COARSE_X == 31 {
+            self.v &= !COARSE_X;
+            self.v ^= NAMETABLE_X;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    pub fn increment_y(&mut self) {
+        if self.v & FINE_Y != FINE_Y {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !FINE_Y;
+        let mut coarse_y = (self.v & COARSE_Y) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= NAMETABLE_Y;
+        } else if coarse_y == 31 {
+            // out of range coarse y wraps without switching nametables
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !HORIZONTAL) | (self.t & HORIZONTAL);
+    }
+
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !VERTICAL) | (self.t & VERTICAL);
+    }
+}
+
+impl Default for LoopyRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
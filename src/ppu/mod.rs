@@ -1,77 +1,152 @@
-use bitflags::Flags;
-use scrollreg::ScrollRegister;
-
+use crate::mapper::SharedMapper;
 use crate::rom::Mirroring;
-use self::addrreg::AddressRegister;
+use crate::savestate::{Snapshot, StateReader, StateWriter};
 use self::ctrlreg::{ControlFlags, ControlRegister};
+use self::frame::Frame;
+use self::loopyreg::LoopyRegister;
 use self::maskreg::{MaskFlags, MaskRegister};
+use self::sprite::{Sprite, SpriteAttributes, MAX_SPRITES_PER_LINE};
 use self::statusreg::StatusRegister;
 
-pub mod addrreg;
 pub mod ctrlreg;
+pub mod frame;
+pub mod loopyreg;
 pub mod maskreg;
+pub mod palette;
+pub mod sprite;
 pub mod statusreg;
-pub mod scrollreg;
+
+#[cfg(test)]
+mod tests;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const POST_RENDER_SCANLINE: u16 = 240;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
 
 pub struct PPU {
-    pub chr_rom: Vec<u8>,
     pub palette_table: [u8; 32],
-    pub vram: [u8; 2048],
+    // the console's 2KB of nametables, then the 2KB more that a four-screen
+    // cartridge brings along
+    pub vram: [u8; 4096],
     pub oam_data: [u8; 256],
-    pub mirroring: Mirroring,
-    pub addr: AddressRegister,
+    pub loopy: LoopyRegister,
     pub ctrl: ControlRegister,
     pub mask: MaskRegister,
     pub status: StatusRegister,
-    pub scroll: ScrollRegister,
     pub oam_addr: u8,
-    cycles: usize,
+    pub frame: Frame,
+    mapper: SharedMapper,
+    dot: u16,
     scanline: u16,
+    odd_frame: bool,
     internal_data_buf: u8,
-    w: bool,
+
+    // The NMI line is asserted while both the vblank flag (NMI_occurred) and
+    // PPUCTRL bit 7 (NMI_output) are set, the CPU reacts to it going active.
+    nmi_line: bool,
     nmi_interrupt: bool,
+    suppress_vblank: bool,
+
+    // background fetch latches and shift registers
+    bg_next_tile: u8,
+    bg_next_palette: u8,
+    bg_next_lo: u8,
+    bg_next_hi: u8,
+    bg_shift_lo: u16,
+    bg_shift_hi: u16,
+    bg_attr_shift_lo: u16,
+    bg_attr_shift_hi: u16,
+
+    // sprites for the next line, evaluated and fetched during this one
+    sprites: Vec<Sprite>,
+    sprite_patterns: [(u8, u8); MAX_SPRITES_PER_LINE],
 }
 
 impl PPU {
-    pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+    pub fn new(mapper: SharedMapper) -> Self {
         PPU {
-            chr_rom,
-            mirroring,
-            vram: [0; 2048],
+            mapper,
+            vram: [0; 4096],
             oam_data: [0; 256],
             oam_addr: 0,
             palette_table: [0; 32],
-            cycles: 0,
-            scanline: 0,
+            frame: Frame::new(),
+            // start on the pre-render line so the first frame is set up like any other
+            dot: 0,
+            scanline: PRE_RENDER_SCANLINE,
+            odd_frame: false,
             internal_data_buf: 0,
-            addr: AddressRegister::new(),
+            loopy: LoopyRegister::new(),
             ctrl: ControlRegister::new(),
             mask: MaskRegister::new(),
             status: StatusRegister::new(),
-            scroll: ScrollRegister::new(),
-            w: true, // should this start true or false?
+            nmi_line: false,
             nmi_interrupt: false,
+            suppress_vblank: false,
+            bg_next_tile: 0,
+            bg_next_palette: 0,
+            bg_next_lo: 0,
+            bg_next_hi: 0,
+            bg_shift_lo: 0,
+            bg_shift_hi: 0,
+            bg_attr_shift_lo: 0,
+            bg_attr_shift_hi: 0,
+            sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+            sprite_patterns: [(0, 0); MAX_SPRITES_PER_LINE],
         }
     }
 
+    /*
+     * The reset button clears PPUCTRL, PPUMASK, the scroll, the write latch,
+     * the read buffer and the odd frame flag. The memories, OAMADDR, v and
+     * the status flags are left as they were, and the frame carries on from
+     * where it had got to.
+     */
+    pub fn reset(&mut self) {
+        self.write_to_ppu_ctrl(0);
+        self.mask.write(0);
+        self.loopy.t = 0;
+        self.loopy.x = 0;
+        self.loopy.reset_latch();
+        self.internal_data_buf = 0;
+        self.odd_frame = false;
+    }
+
     pub fn write_to_ppu_scroll(&mut self, value: u8) {
-        self.scroll.update(value, self.w);
-        self.w = !self.w;
+        self.loopy.write_scroll(value);
     }
 
     pub fn write_to_ppu_addr(&mut self, value: u8) {
-        self.addr.update(value, self.w);
-        self.w = !self.w;
+        self.loopy.write_addr(value);
     }
 
     pub fn write_to_ppu_ctrl(&mut self, value: u8) {
-        let before_nmi_status = self.ctrl.flags.contains(ControlFlags::GENERATE_NMI);
         self.ctrl.flags = ControlFlags::from_bits_truncate(value);
-        if !before_nmi_status
-            && self.ctrl.flags.contains(ControlFlags::GENERATE_NMI)
-            && self.status.contains(StatusRegister::VBLANK_FLAG) {
-                self.nmi_interrupt = true;
+        self.loopy.write_ctrl(value);
+
+        // setting GENERATE_NMI while in vblank triggers another NMI
+        self.update_nmi_line();
+    }
+
+    fn update_nmi_line(&mut self) {
+        let line = self.status.contains(StatusRegister::VBLANK_FLAG)
+            && self.ctrl.flags.contains(ControlFlags::GENERATE_NMI);
+
+        if line && !self.nmi_line {
+            self.nmi_interrupt = true;
         }
+        self.nmi_line = line;
+    }
+
+    pub fn nmi_pending(&self) -> bool {
+        self.nmi_interrupt
+    }
+
+    pub fn poll_nmi_interrupt(&mut self) -> bool {
+        let interrupt = self.nmi_interrupt;
+        self.nmi_interrupt = false;
+        interrupt
     }
 
     pub fn write_to_ppu_mask(&mut self, value: u8) {
@@ -79,67 +154,132 @@ impl PPU {
     }
 
     fn inc_vram_addr(&mut self) {
-        self.addr.increment(self.ctrl.vram_addr_inc());
+        // During rendering v is busy being used for fetches, so the access
+        // bumps coarse X and Y like the fetch logic does instead.
+        if self.is_rendering() {
+            self.loopy.increment_x();
+            self.loopy.increment_y();
+        } else {
+            self.loopy.increment(self.ctrl.vram_addr_inc());
+        }
     }
 
     pub fn read_data(&mut self) -> u8 {
-        let addr = self.addr.get_addr();
+        let addr = self.loopy.addr();
         self.inc_vram_addr();
 
         match addr {
             0x0000..=0x1FFF => {
                 let result = self.internal_data_buf;
-                self.internal_data_buf = self.chr_rom[addr as usize];
+                self.internal_data_buf = self.read_chr(addr);
                 result
             },
-            0x2000..=0x2FFF => {
+            // $3000-$3EFF mirrors the nametables
+            0x2000..=0x3EFF => {
                 let result = self.internal_data_buf;
-                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
+                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr & 0x2FFF) as usize];
                 result
             }
-            0x3000..=0x3EFF => panic!("addr space 0x3000..0x3EFF is not expected to be used by PPU, requested = {}", addr),
-            0x3F00..=0x3FFF => {
-                self.palette_table[(addr - 0x3F00) as usize]
+            // the address is only 14 bits, this is $3F00-$3FFF
+            _ => {
+                // palette reads are not buffered, but the buffer is still
+                // filled with the nametable byte "underneath" the palette
+                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr - 0x1000) as usize];
+                self.palette_table[mirror_palette_addr(addr)]
             },
-            _ => panic!("Unexpected PPU access to mirrored space {}", addr),
+        }
+    }
+
+    // What a $2007 read would return, without moving v or refilling the
+    // read buffer
+    pub fn peek_data(&self) -> u8 {
+        let addr = self.loopy.addr();
+        match addr {
+            0x3F00..=0x3FFF => self.palette_table[mirror_palette_addr(addr)],
+            _ => self.internal_data_buf,
         }
     }
 
     pub fn write_data(&mut self, value: u8) {
-        let addr = self.addr.get_addr();
+        let addr = self.loopy.addr();
         
         match addr {
-            0x0000..=0x1FFF => println!("attempt to write to chr rom space {}", addr),
-            0x2000..=0x2FFF => self.vram[self.mirror_vram_addr(addr) as usize] = value,
-            0x3000..=0x3EFF => unimplemented!("write to illegal PPU area {}", addr),
-            0x3F00..=0x3FFF => self.palette_table[(addr - 0x3F00) as usize] = value,
-            _ => panic!("Unexpected PPU access to mirrored space {}", addr),
+            0x0000..=0x1FFF => self.mapper.borrow_mut().ppu_write(addr, value),
+            0x2000..=0x3EFF => self.vram[self.mirror_vram_addr(addr & 0x2FFF) as usize] = value,
+            _ => self.palette_table[mirror_palette_addr(addr)] = value,
         }
 
         self.inc_vram_addr();
     }
 
     pub fn read_status(&mut self) -> u8 {
-        self.w = false;
+        self.loopy.reset_latch();
+
+        if self.scanline == VBLANK_SCANLINE {
+            match self.dot {
+                // Reading one dot before vblank starts returns it clear and
+                // stops it from being set at all this frame.
+                1 => self.suppress_vblank = true,
+
+                // Reading as it is set returns it set, but the NMI is lost.
+                2 | 3 => self.nmi_interrupt = false,
+
+                _ => {},
+            }
+        }
+
         let status = self.status.bits();
         self.status.reset_vblank_status();
+        self.update_nmi_line();
         status
     }
 
+    // What a $2002 read would return, leaving vblank and the write latch be
+    pub fn peek_status(&self) -> u8 {
+        self.status.bits()
+    }
+
     pub fn read_oam_data(&self) -> u8 {
         // Reading OAMDATA while the PPU is rendering will expose internal OAM accessesduring
         // during sprite evaluation and loading; Micro Machines does this.
         //
         // if self.status.contains(StatusRegister::VBLANK_FLAG) {
-            self.oam_data[self.oam_addr as usize]
+        let value = self.oam_data[self.oam_addr as usize];
         // }
+
+        // bits 2-4 of the attribute byte do not exist and read back as 0
+        match self.oam_addr & 0b11 {
+            2 => value & 0b1110_0011,
+            _ => value,
+        }
     }
 
     pub fn write_oam_data(&mut self, value: u8) {
-        todo!("implement caveats from https://www.nesdev.org/wiki/PPU_registers#OAMDATA_-_Sprite_RAM_data_($2004_read/write");
+        // Writes during rendering do not modify OAM, but still bump the high
+        // 6 bits of the address (i.e. move to the next sprite).
+        if self.is_rendering() {
+            self.oam_addr = self.oam_addr.wrapping_add(4);
+            return;
+        }
 
         self.oam_data[self.oam_addr as usize] = value;
-        self.oam_addr += 1;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.mask.flags.intersects(MaskFlags::SH_BACKGROUND | MaskFlags::SH_SPRITES)
+    }
+
+    fn is_rendering(&self) -> bool {
+        self.rendering_enabled()
+            && (self.scanline < POST_RENDER_SCANLINE || self.scanline == PRE_RENDER_SCANLINE)
+    }
+
+    fn sprite_height(&self) -> u8 {
+        match self.ctrl.flags.contains(ControlFlags::SPRITE_SIZE) {
+            true => 16,
+            false => 8,
+        }
     }
 
     fn mirror_vram_addr(&self, addr: u16) -> u16 {
@@ -147,35 +287,395 @@ impl PPU {
         let vram_index = mirrored_vram - 0x2000;
         let name_table = vram_index / 0x0400;
 
-        match (&self.mirroring, name_table) {
+        match (self.mapper.borrow().mirroring(), name_table) {
             (Mirroring::Vertical, 2) | (Mirroring::Vertical, 3) => vram_index - 0x0800,
             (Mirroring::Horizontal, 2) => vram_index - 0x0400,
             (Mirroring::Horizontal, 1) => vram_index - 0x0400,
             (Mirroring::Horizontal, 3) => vram_index - 0x0800,
+            (Mirroring::SingleScreenA, _) => vram_index & 0x03FF,
+            (Mirroring::SingleScreenB, _) => 0x0400 | (vram_index & 0x03FF),
             _ => vram_index,
         }
     }
 
-    pub fn tick(&mut self, cycles: u8) -> bool {
-        self.cycles += cycles as usize;
-        if self.cycles >= 341 {
-            self.cycles -= 341;
-            self.scanline += 1;
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mapper.borrow_mut().ppu_read(addr)
+    }
+
+    fn background_table(&self) -> u16 {
+        match self.ctrl.flags.contains(ControlFlags::BGRND_PTRN_ADDR) {
+            true => 0x1000,
+            false => 0,
+        }
+    }
+
+    fn sprite_table(&self) -> u16 {
+        match self.ctrl.flags.contains(ControlFlags::SPRITE_PTRN_ADDR) {
+            true => 0x1000,
+            false => 0,
+        }
+    }
+
+    fn load_background_shifters(&mut self) {
+        self.bg_shift_lo = (self.bg_shift_lo & 0xFF00) | self.bg_next_lo as u16;
+        self.bg_shift_hi = (self.bg_shift_hi & 0xFF00) | self.bg_next_hi as u16;
+
+        // attribute bits are the same for all 8 pixels of a tile
+        let attr_lo = if self.bg_next_palette & 0b01 != 0 { 0xFF } else { 0x00 };
+        let attr_hi = if self.bg_next_palette & 0b10 != 0 { 0xFF } else { 0x00 };
+        self.bg_attr_shift_lo = (self.bg_attr_shift_lo & 0xFF00) | attr_lo;
+        self.bg_attr_shift_hi = (self.bg_attr_shift_hi & 0xFF00) | attr_hi;
+    }
+
+    fn shift_background(&mut self) {
+        self.bg_shift_lo <<= 1;
+        self.bg_shift_hi <<= 1;
+        self.bg_attr_shift_lo <<= 1;
+        self.bg_attr_shift_hi <<= 1;
+    }
+
+    /*
+     * Each tile takes 8 dots to fetch: nametable byte, attribute byte, then
+     * the low and high pattern planes, two dots per memory access. The tile
+     * goes into the shift registers on the 8th dot, when v also moves on to
+     * the next tile.
+     */
+    fn fetch_background(&mut self) {
+        match self.dot % 8 {
+            1 => {
+                let addr = self.loopy.tile_addr();
+                self.bg_next_tile = self.vram[self.mirror_vram_addr(addr) as usize];
+            },
+            3 => {
+                let addr = self.loopy.attribute_addr();
+                let attr = self.vram[self.mirror_vram_addr(addr) as usize];
+                self.bg_next_palette = (attr >> self.loopy.attribute_shift()) & 0b11;
+            },
+            5 => {
+                let addr = self.background_table() + self.bg_next_tile as u16 * 16 + self.loopy.fine_y();
+                self.bg_next_lo = self.read_chr(addr);
+            },
+            7 => {
+                let addr = self.background_table() + self.bg_next_tile as u16 * 16 + self.loopy.fine_y() + 8;
+                self.bg_next_hi = self.read_chr(addr);
+            },
+            0 => {
+                self.load_background_shifters();
+                self.loopy.increment_x();
+            },
+            _ => {},
+        }
+    }
+
+    fn evaluate_sprites(&mut self) {
+        self.sprites.clear();
+
+        if self.scanline == PRE_RENDER_SCANLINE {
+            return;
+        }
+
+        let (sprites, overflow) = sprite::evaluate(&self.oam_data, self.scanline, self.sprite_height());
+        if overflow {
+            self.status.insert(StatusRegister::SPRITE_OVERFLOW);
+        }
+        self.sprites = sprites;
+    }
+
+    /*
+     * Dots 257-320 fetch the patterns of the sprites found for the next line,
+     * 8 dots per sprite. Unused slots still fetch tile $FF, which matters to
+     * mappers that watch the PPU address bus.
+     */
+    fn fetch_sprite(&mut self) {
+        let slot = ((self.dot - 257) / 8) as usize;
+        let height = self.sprite_height();
 
-            if self.scanline == 241 {
-                if self.ctrl.flags.contains(ControlFlags::GENERATE_NMI) {
-                    self.status.set_vblank_status(true);
-                    self.nmi_interrupt = true;
+        let (addr, flip) = match self.sprites.get(slot) {
+            Some(sprite) => {
+                let row = (self.scanline - sprite.y as u16) as u8;
+                (
+                    sprite.pattern_addr(row, height, self.sprite_table()),
+                    sprite.attributes.contains(SpriteAttributes::FLIP_HORIZONTAL),
+                )
+            },
+            None => {
+                let dummy = Sprite {
+                    index: 0xFF,
+                    y: 0xFF,
+                    tile: 0xFF,
+                    attributes: SpriteAttributes::empty(),
+                    x: 0xFF,
+                };
+                (dummy.pattern_addr(0, height, self.sprite_table()), false)
+            },
+        };
+
+        match (self.dot - 257) % 8 {
+            5 => {
+                let lo = self.read_chr(addr);
+                self.sprite_patterns[slot].0 = if flip { lo.reverse_bits() } else { lo };
+            },
+            7 => {
+                let hi = self.read_chr(addr + 8);
+                self.sprite_patterns[slot].1 = if flip { hi.reverse_bits() } else { hi };
+            },
+            _ => {},
+        }
+    }
+
+    fn background_pixel(&self) -> u8 {
+        let bit = 0x8000 >> self.loopy.fine_x();
+        let lo = (self.bg_shift_lo & bit != 0) as u8;
+        let hi = (self.bg_shift_hi & bit != 0) as u8;
+        let value = (hi << 1) | lo;
+
+        if value == 0 {
+            return 0;
+        }
+
+        let attr_lo = (self.bg_attr_shift_lo & bit != 0) as u8;
+        let attr_hi = (self.bg_attr_shift_hi & bit != 0) as u8;
+        ((attr_hi << 1) | attr_lo) * 4 + value
+    }
+
+    // Returns the palette index (0x10-0x1F), priority and sprite 0-ness of the
+    // front-most opaque sprite pixel at `x`, if any.
+    fn sprite_pixel(&self, x: usize) -> Option<(u8, bool, bool)> {
+        for (sprite, (lo, hi)) in self.sprites.iter().zip(self.sprite_patterns.iter()) {
+            let column = x as i32 - sprite.x as i32;
+            if !(0..8).contains(&column) {
+                continue;
+            }
+
+            let bit = 7 - column as u8;
+            let value = (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1);
+
+            if value != 0 {
+                return Some((
+                    0x10 + sprite.palette() * 4 + value,
+                    sprite.attributes.contains(SpriteAttributes::BEHIND_BACKGROUND),
+                    sprite.index == 0,
+                ));
+            }
+        }
+
+        None
+    }
+
+    fn render_pixel(&mut self) {
+        let x = (self.dot - 1) as usize;
+        let y = self.scanline as usize;
+
+        let show_background = self.mask.flags.contains(MaskFlags::SH_BACKGROUND);
+        let show_left = self.mask.flags.contains(MaskFlags::SH_BKGD_LEFT);
+        let show_sprites = self.mask.flags.contains(MaskFlags::SH_SPRITES);
+        let show_sprites_left = self.mask.flags.contains(MaskFlags::SH_SPR_LEFT);
+
+        let mut palette_idx = 0;
+
+        if show_background && (show_left || x >= 8) {
+            palette_idx = self.background_pixel();
+        }
+
+        if show_sprites && (show_sprites_left || x >= 8) {
+            if let Some((sprite_idx, behind, sprite_zero)) = self.sprite_pixel(x) {
+                if sprite_zero && palette_idx != 0 && x != 255 {
+                    self.status.insert(StatusRegister::SPRITE_ZERO_HIT);
                 }
+
+                if palette_idx == 0 || !behind {
+                    palette_idx = sprite_idx;
+                }
+            }
+        }
+
+        let mut color = self.palette_table[palette_idx as usize] & 0x3F;
+        if self.mask.flags.contains(MaskFlags::GREYSCALE) {
+            color &= 0x30;
+        }
+
+        self.frame.set_pixel(x, y, color);
+    }
+
+    fn step(&mut self) -> bool {
+        let rendering = self.rendering_enabled();
+        let visible = self.scanline < POST_RENDER_SCANLINE;
+        let pre_render = self.scanline == PRE_RENDER_SCANLINE;
+
+        if pre_render && self.dot == 1 {
+            self.status.reset_vblank_status();
+            self.status.remove(StatusRegister::SPRITE_ZERO_HIT | StatusRegister::SPRITE_OVERFLOW);
+            self.update_nmi_line();
+        }
+
+        if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+            if !self.suppress_vblank {
+                self.status.set_vblank_status(true);
+                self.update_nmi_line();
             }
+            self.suppress_vblank = false;
+        }
 
-            if self.scanline >= 262 {
+        if visible && (1..=256).contains(&self.dot) {
+            self.render_pixel();
+        }
+
+        if rendering && (visible || pre_render) {
+            if (1..=256).contains(&self.dot) || (321..=336).contains(&self.dot) {
+                self.shift_background();
+                self.fetch_background();
+            }
+
+            match self.dot {
+                256 => self.loopy.increment_y(),
+                257 => {
+                    self.loopy.copy_horizontal();
+                    self.evaluate_sprites();
+                },
+                280..=304 if pre_render => self.loopy.copy_vertical(),
+                _ => {},
+            }
+
+            if (257..=320).contains(&self.dot) {
+                self.oam_addr = 0;
+                self.fetch_sprite();
+            }
+        }
+
+        self.dot += 1;
+
+        // odd frames skip the last dot of the pre-render line when rendering
+        if pre_render && self.dot == DOTS_PER_SCANLINE - 1 && self.odd_frame && rendering {
+            self.dot += 1;
+        }
+
+        if self.dot >= DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+
+            if self.scanline > PRE_RENDER_SCANLINE {
                 self.scanline = 0;
-                self.status.reset_vblank_status();
-                return true;
+                self.odd_frame = !self.odd_frame;
             }
+
+            return self.scanline == POST_RENDER_SCANLINE;
         }
-        return false;
+
+        false
+    }
+
+    // Advance the PPU by `cycles` dots, returns true once the last visible
+    // line of a frame has been drawn.
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        let mut frame_complete = false;
+        for _ in 0..cycles {
+            frame_complete |= self.step();
+        }
+        frame_complete
     }
 }
 
+fn mirror_palette_addr(addr: u16) -> usize {
+    // $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C
+    let idx = (addr & 0x1F) as usize;
+    match idx {
+        0x10 | 0x14 | 0x18 | 0x1C => idx - 0x10,
+        _ => idx,
+    }
+}
+
+impl Snapshot for PPU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.palette_table);
+        w.write_bytes(&self.vram);
+        w.write_bytes(&self.oam_data);
+        w.write_u16(self.loopy.v);
+        w.write_u16(self.loopy.t);
+        w.write_u8(self.loopy.x);
+        w.write_bool(self.loopy.w);
+        w.write_u8(self.ctrl.flags.bits());
+        w.write_u8(self.mask.flags.bits());
+        w.write_u8(self.status.bits());
+        w.write_u8(self.oam_addr);
+        w.write_bytes(&self.frame.data);
+        w.write_u16(self.dot);
+        w.write_u16(self.scanline);
+        w.write_bool(self.odd_frame);
+        w.write_u8(self.internal_data_buf);
+        w.write_bool(self.nmi_line);
+        w.write_bool(self.nmi_interrupt);
+        w.write_bool(self.suppress_vblank);
+
+        w.write_u8(self.bg_next_tile);
+        w.write_u8(self.bg_next_palette);
+        w.write_u8(self.bg_next_lo);
+        w.write_u8(self.bg_next_hi);
+        w.write_u16(self.bg_shift_lo);
+        w.write_u16(self.bg_shift_hi);
+        w.write_u16(self.bg_attr_shift_lo);
+        w.write_u16(self.bg_attr_shift_hi);
+
+        w.write_u8(self.sprites.len() as u8);
+        for sprite in self.sprites.iter() {
+            w.write_u8(sprite.index);
+            w.write_u8(sprite.y);
+            w.write_u8(sprite.tile);
+            w.write_u8(sprite.attributes.bits());
+            w.write_u8(sprite.x);
+        }
+        for (lo, hi) in self.sprite_patterns.iter() {
+            w.write_u8(*lo);
+            w.write_u8(*hi);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.palette_table)?;
+        r.read_into(&mut self.vram)?;
+        r.read_into(&mut self.oam_data)?;
+        self.loopy.v = r.read_u16()?;
+        self.loopy.t = r.read_u16()?;
+        self.loopy.x = r.read_u8()?;
+        self.loopy.w = r.read_bool()?;
+        self.ctrl.flags = ControlFlags::from_bits_truncate(r.read_u8()?);
+        self.mask.flags = MaskFlags::from_bits_truncate(r.read_u8()?);
+        self.status = StatusRegister::from_bits_truncate(r.read_u8()?);
+        self.oam_addr = r.read_u8()?;
+        r.read_into(&mut self.frame.data)?;
+        self.dot = r.read_u16()?;
+        self.scanline = r.read_u16()?;
+        self.odd_frame = r.read_bool()?;
+        self.internal_data_buf = r.read_u8()?;
+        self.nmi_line = r.read_bool()?;
+        self.nmi_interrupt = r.read_bool()?;
+        self.suppress_vblank = r.read_bool()?;
+
+        self.bg_next_tile = r.read_u8()?;
+        self.bg_next_palette = r.read_u8()?;
+        self.bg_next_lo = r.read_u8()?;
+        self.bg_next_hi = r.read_u8()?;
+        self.bg_shift_lo = r.read_u16()?;
+        self.bg_shift_hi = r.read_u16()?;
+        self.bg_attr_shift_lo = r.read_u16()?;
+        self.bg_attr_shift_hi = r.read_u16()?;
+
+        let count = r.read_u8()? as usize;
+        if count > MAX_SPRITES_PER_LINE {
+            return Err(format!("Save state has {} sprites on a line", count));
+        }
+        self.sprites.clear();
+        for _ in 0..count {
+            self.sprites.push(Sprite {
+                index: r.read_u8()?,
+                y: r.read_u8()?,
+                tile: r.read_u8()?,
+                attributes: SpriteAttributes::from_bits_truncate(r.read_u8()?),
+                x: r.read_u8()?,
+            });
+        }
+        for pattern in self.sprite_patterns.iter_mut() {
+            *pattern = (r.read_u8()?, r.read_u8()?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use bitflags::bitflags;
+
+pub const MAX_SPRITES_PER_LINE: usize = 8;
+
+/*
+ * OAM byte 2
+ * 7  bit  0
+ * ---- ----
+ * VHP. ..PP
+ * |||   ||
+ * |||   ++- Palette (4 to 7) of sprite
+ * ||+------ Priority (0: in front of background; 1: behind background)
+ * |+------- Flip sprite horizontally
+ * +-------- Flip sprite vertically
+ */
+
+bitflags! {
+    #[derive(Clone, Copy)]
+    pub struct SpriteAttributes : u8 {
+        const PALETTE0          = 0b0000_0001;
+        const PALETTE1          = 0b0000_0010;
+        const BEHIND_BACKGROUND = 0b0010_0000;
+        const FLIP_HORIZONTAL   = 0b0100_0000;
+        const FLIP_VERTICAL     = 0b1000_0000;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sprite {
+    pub index: u8,
+    pub y: u8,
+    pub tile: u8,
+    pub attributes: SpriteAttributes,
+    pub x: u8,
+}
+
+impl Sprite {
+    pub fn from_oam(oam: &[u8; 256], index: u8) -> Self {
+        let base = index as usize * 4;
+        Sprite {
+            index,
+            y: oam[base],
+            tile: oam[base + 1],
+            attributes: SpriteAttributes::from_bits_truncate(oam[base + 2]),
+            x: oam[base + 3],
+        }
+    }
+
+    pub fn palette(&self) -> u8 {
+        self.attributes.bits() & 0b11
+    }
+
+    // Address of the pattern table row for the given row of the sprite,
+    // `row` is already relative to the top of the sprite.
+    pub fn pattern_addr(&self, row: u8, height: u8, table_8x8: u16) -> u16 {
+        let row = match self.attributes.contains(SpriteAttributes::FLIP_VERTICAL) {
+            true => height - 1 - row,
+            false => row,
+        };
+
+        if height == 16 {
+            // in 8x16 mode bit 0 of the tile index selects the pattern table
+            let bank = (self.tile as u16 & 1) * 0x1000;
+            let tile = (self.tile & 0xFE) as u16 + (row / 8) as u16;
+            bank + tile * 16 + (row % 8) as u16
+        } else {
+            table_8x8 + self.tile as u16 * 16 + row as u16
+        }
+    }
+}
+
+fn in_range(sprite_y: u8, line: u16, height: u8) -> bool {
+    let diff = line as i32 - sprite_y as i32;
+    diff >= 0 && diff < height as i32
+}
+
+/*
+ * Find the sprites which will be drawn on the line after `line`, in OAM order.
+ * Once eight sprites are found the PPU keeps scanning for the overflow flag,
+ * but a hardware bug increments both the sprite and byte index when a sprite
+ * is not in range, so it ends up comparing tile, attribute and x bytes as if
+ * they were y coordinates. Returns the sprites and the overflow flag.
+ */
+pub fn evaluate(oam: &[u8; 256], line: u16, height: u8) -> (Vec<Sprite>, bool) {
+    let mut found = Vec::with_capacity(MAX_SPRITES_PER_LINE);
+    let mut n = 0;
+
+    while n < 64 && found.len() < MAX_SPRITES_PER_LINE {
+        if in_range(oam[n * 4], line, height) {
+            found.push(Sprite::from_oam(oam, n as u8));
+        }
+        n += 1;
+    }
+
+    let mut m = 0;
+    while n < 64 {
+        if in_range(oam[n * 4 + m], line, height) {
+            return (found, true);
+        }
+        n += 1;
+        m = (m + 1) & 0b11;
+    }
+
+    (found, false)
+}
@@ -0,0 +1,702 @@
+use super::*;
+use crate::mapper;
+use crate::rom::Rom;
+
+// No CHR ROM, so the pattern tables are 8 KB of CHR RAM the tests can fill in
+fn new_ppu() -> PPU {
+    let mut rom = Rom::blank();
+    rom.screen_mirroring = Mirroring::Horizontal;
+    PPU::new(mapper::from_rom(rom).unwrap())
+}
+
+fn write_chr(ppu: &mut PPU, addr: usize, value: u8) {
+    ppu.mapper.borrow_mut().ppu_write(addr as u16, value);
+}
+
+fn run_frame(ppu: &mut PPU) {
+    while !ppu.tick(1) {}
+}
+
+fn tick_dots(ppu: &mut PPU, dots: usize) {
+    for _ in 0..dots {
+        ppu.tick(1);
+    }
+}
+
+// Pattern 1 in the background table is a solid tile of color 3, pattern 2 has
+// only its leftmost column set to color 1.
+fn with_tiles(ppu: &mut PPU, bank: usize) {
+    for row in 0..8 {
+        write_chr(ppu, bank + 16 + row, 0xFF);
+        write_chr(ppu, bank + 16 + row + 8, 0xFF);
+        write_chr(ppu, bank + 32 + row, 0x80);
+    }
+}
+
+#[test]
+fn test_palette_mirrors() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_addr(0x3F);
+    ppu.write_to_ppu_addr(0x10);
+    ppu.write_data(0x21);
+
+    assert_eq!(ppu.palette_table[0], 0x21);
+
+    ppu.write_to_ppu_addr(0x3F);
+    ppu.write_to_ppu_addr(0x00);
+    assert_eq!(ppu.read_data(), 0x21);
+}
+
+#[test]
+fn test_render_disabled_shows_backdrop() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[3] = 0x30;
+    ppu.vram[0] = 1;
+    with_tiles(&mut ppu, 0);
+
+    run_frame(&mut ppu);
+
+    assert!(ppu.frame.data.iter().all(|&c| c == 0x0F));
+}
+
+#[test]
+fn test_render_background_tile() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[3] = 0x30;
+    ppu.vram[1] = 1;
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1010);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(7, 0), 0x0F);
+    assert_eq!(ppu.frame.get_pixel(8, 0), 0x30);
+    assert_eq!(ppu.frame.get_pixel(15, 7), 0x30);
+    assert_eq!(ppu.frame.get_pixel(16, 0), 0x0F);
+    assert_eq!(ppu.frame.get_pixel(8, 8), 0x0F);
+}
+
+#[test]
+fn test_render_uses_background_pattern_table() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[3] = 0x30;
+    ppu.vram[0] = 1;
+    with_tiles(&mut ppu, 0x1000);
+    ppu.write_to_ppu_mask(0b0000_1010);
+
+    run_frame(&mut ppu);
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x00);
+
+    ppu.write_to_ppu_ctrl(0b0001_0000);
+    run_frame(&mut ppu);
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x30);
+}
+
+#[test]
+fn test_render_attribute_palette() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[3] = 0x30;
+    ppu.palette_table[15] = 0x16;
+
+    // tile (2, 0) lives in the top right quadrant of the first attribute byte
+    ppu.vram[2] = 1;
+    ppu.vram[0x3C0] = 0b0000_1100;
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1010);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(16, 0), 0x16);
+}
+
+#[test]
+fn test_render_left_column_clipping() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[3] = 0x30;
+    ppu.vram[0] = 1;
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1000);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x0F);
+}
+
+#[test]
+fn test_render_scrolled_into_mirrored_nametable() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[1] = 0x21;
+
+    // with horizontal mirroring $2400 is the same memory as $2000
+    ppu.vram[0] = 2;
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1010);
+    ppu.write_to_ppu_scroll(0);
+    ppu.write_to_ppu_scroll(0);
+    ppu.write_to_ppu_ctrl(0b0000_0001);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x21);
+    assert_eq!(ppu.frame.get_pixel(1, 0), 0x0F);
+}
+
+#[test]
+fn test_single_screen_mirroring() {
+    let mut rom = Rom::blank();
+    rom.screen_mirroring = Mirroring::SingleScreenB;
+    let mut ppu = PPU::new(mapper::from_rom(rom).unwrap());
+
+    ppu.write_to_ppu_addr(0x2C);
+    ppu.write_to_ppu_addr(0x05);
+    ppu.write_data(0x66);
+
+    assert_eq!(ppu.vram[0x0405], 0x66);
+
+    ppu.write_to_ppu_addr(0x20);
+    ppu.write_to_ppu_addr(0x05);
+    ppu.read_data();
+    assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_four_screen_mirroring() {
+    let mut rom = Rom::blank();
+    rom.screen_mirroring = Mirroring::FourScreen;
+    let mut ppu = PPU::new(mapper::from_rom(rom).unwrap());
+
+    for (i, table) in [0x20, 0x24, 0x28, 0x2C].into_iter().enumerate() {
+        ppu.write_to_ppu_addr(table);
+        ppu.write_to_ppu_addr(0x05);
+        ppu.write_data(i as u8 + 1);
+    }
+
+    assert_eq!(ppu.vram[0x0005], 1);
+    assert_eq!(ppu.vram[0x0405], 2);
+    assert_eq!(ppu.vram[0x0805], 3);
+    assert_eq!(ppu.vram[0x0C05], 4);
+
+    // and rendering out of the last of them is fine too
+    ppu.write_to_ppu_ctrl(0b0000_0011);
+    ppu.write_to_ppu_mask(0b0000_1000);
+    run_frame(&mut ppu);
+}
+
+fn set_sprite(ppu: &mut PPU, index: usize, y: u8, tile: u8, attributes: u8, x: u8) {
+    ppu.oam_data[index * 4] = y;
+    ppu.oam_data[index * 4 + 1] = tile;
+    ppu.oam_data[index * 4 + 2] = attributes;
+    ppu.oam_data[index * 4 + 3] = x;
+}
+
+// Hide every sprite below the visible area
+fn clear_oam(ppu: &mut PPU) {
+    ppu.oam_data = [0xFF; 256];
+}
+
+#[test]
+fn test_oam_data_write_increments_addr() {
+    let mut ppu = new_ppu();
+    ppu.oam_addr = 0xFF;
+    ppu.write_oam_data(0x12);
+    ppu.write_oam_data(0x34);
+
+    assert_eq!(ppu.oam_data[0xFF], 0x12);
+    assert_eq!(ppu.oam_data[0x00], 0x34);
+    assert_eq!(ppu.oam_addr, 1);
+}
+
+#[test]
+fn test_oam_data_attribute_unused_bits_read_zero() {
+    let mut ppu = new_ppu();
+    ppu.oam_data[2] = 0xFF;
+    ppu.oam_addr = 2;
+
+    assert_eq!(ppu.read_oam_data(), 0xE3);
+}
+
+#[test]
+fn test_render_sprite() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[0x17] = 0x27;
+    with_tiles(&mut ppu, 0);
+    set_sprite(&mut ppu, 0, 9, 1, 0b01, 20);
+    ppu.write_to_ppu_mask(0b0001_0100);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(20, 9), 0x0F);
+    assert_eq!(ppu.frame.get_pixel(20, 10), 0x27);
+    assert_eq!(ppu.frame.get_pixel(27, 17), 0x27);
+    assert_eq!(ppu.frame.get_pixel(28, 10), 0x0F);
+    assert_eq!(ppu.frame.get_pixel(20, 18), 0x0F);
+}
+
+#[test]
+fn test_render_sprite_horizontal_flip() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    ppu.palette_table[0x11] = 0x27;
+    with_tiles(&mut ppu, 0);
+    set_sprite(&mut ppu, 0, 9, 2, 0b0100_0000, 20);
+    ppu.write_to_ppu_mask(0b0001_0100);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(20, 10), 0x00);
+    assert_eq!(ppu.frame.get_pixel(27, 10), 0x27);
+}
+
+#[test]
+fn test_render_sprite_8x16_vertical_flip() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    ppu.palette_table[0x11] = 0x27;
+    ppu.palette_table[0x13] = 0x16;
+    with_tiles(&mut ppu, 0x1000);
+
+    // tile 0x03 in 8x16 mode is tiles 2 (top) and 3 (bottom) from $1000
+    for row in 0..8 {
+        write_chr(&mut ppu, 0x1000 + 32 + row + 8, 0x00);
+        write_chr(&mut ppu, 0x1000 + 48 + row, 0xFF);
+        write_chr(&mut ppu, 0x1000 + 48 + row + 8, 0xFF);
+    }
+    set_sprite(&mut ppu, 0, 9, 3, 0b1000_0000, 20);
+    ppu.write_to_ppu_ctrl(0b0010_0000);
+    ppu.write_to_ppu_mask(0b0001_0100);
+
+    run_frame(&mut ppu);
+
+    // flipped, so the solid bottom half is drawn first
+    assert_eq!(ppu.frame.get_pixel(21, 10), 0x16);
+    assert_eq!(ppu.frame.get_pixel(21, 17), 0x16);
+    assert_eq!(ppu.frame.get_pixel(20, 18), 0x27);
+    assert_eq!(ppu.frame.get_pixel(21, 18), 0x00);
+    assert_eq!(ppu.frame.get_pixel(20, 25), 0x27);
+}
+
+#[test]
+fn test_render_sprite_behind_background() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    ppu.palette_table[0x01] = 0x21;
+    ppu.palette_table[0x13] = 0x27;
+    with_tiles(&mut ppu, 0);
+    ppu.vram[2] = 2;
+    set_sprite(&mut ppu, 0, 0, 1, 0b0010_0000, 16);
+    ppu.write_to_ppu_mask(0b0001_1110);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(16, 1), 0x21);
+    assert_eq!(ppu.frame.get_pixel(17, 1), 0x27);
+}
+
+#[test]
+fn test_sprite_priority_by_oam_index() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    ppu.palette_table[0x13] = 0x27;
+    ppu.palette_table[0x17] = 0x16;
+    with_tiles(&mut ppu, 0);
+    set_sprite(&mut ppu, 3, 9, 1, 0b00, 20);
+    set_sprite(&mut ppu, 5, 9, 1, 0b01, 16);
+    ppu.write_to_ppu_mask(0b0001_0100);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(19, 10), 0x16);
+    assert_eq!(ppu.frame.get_pixel(20, 10), 0x27);
+}
+
+#[test]
+fn test_sprite_zero_hit() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    with_tiles(&mut ppu, 0);
+    ppu.vram[2] = 2;
+    set_sprite(&mut ppu, 0, 0, 1, 0, 12);
+    ppu.write_to_ppu_mask(0b0001_1110);
+
+    // the sprite's first line is line 1, at x = 16 where the background starts
+    tick_dots(&mut ppu, 341 + 341 + 17);
+    assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+
+    tick_dots(&mut ppu, 1);
+    assert!(ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+
+    // cleared at the start of the pre-render line
+    run_frame(&mut ppu);
+    tick_dots(&mut ppu, 341 * 21);
+    assert!(ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+    tick_dots(&mut ppu, 2);
+    assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+}
+
+#[test]
+fn test_sprite_zero_hit_not_on_transparent_background() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    with_tiles(&mut ppu, 0);
+    set_sprite(&mut ppu, 0, 0, 1, 0, 12);
+    ppu.write_to_ppu_mask(0b0001_1110);
+
+    for _ in 0..100 {
+        ppu.tick(255);
+    }
+    assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+}
+
+#[test]
+fn test_sprite_zero_hit_not_at_x_255() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    with_tiles(&mut ppu, 0);
+    ppu.vram[31] = 1;
+    set_sprite(&mut ppu, 0, 0, 2, 0, 255);
+    ppu.write_to_ppu_mask(0b0001_1110);
+
+    for _ in 0..100 {
+        ppu.tick(255);
+    }
+    assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+}
+
+#[test]
+fn test_sprite_overflow() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    for i in 0..9 {
+        set_sprite(&mut ppu, i, 50, 0, 0, 0);
+    }
+    ppu.write_to_ppu_mask(0b0001_0000);
+
+    for _ in 0..100 {
+        ppu.tick(255);
+    }
+    assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+}
+
+#[test]
+fn test_sprite_overflow_eight_sprites() {
+    let mut ppu = new_ppu();
+    clear_oam(&mut ppu);
+    for i in 0..8 {
+        set_sprite(&mut ppu, i, 50, 0, 0, 0);
+    }
+    ppu.write_to_ppu_mask(0b0001_0000);
+
+    run_frame(&mut ppu);
+    assert!(!ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+}
+
+#[test]
+fn test_sprite_overflow_hardware_bug() {
+    let mut oam = [0xFF; 256];
+    for i in 0..8 {
+        oam[i * 4] = 50;
+    }
+
+    // sprite 9 is in range, but after missing sprite 8 the evaluation reads
+    // its tile byte instead of its y coordinate
+    oam[9 * 4] = 50;
+    let (sprites, overflow) = sprite::evaluate(&oam, 50, 8);
+    assert_eq!(sprites.len(), 8);
+    assert!(!overflow);
+
+    // and a tile index that happens to look like an in range y sets the flag
+    oam[9 * 4 + 1] = 48;
+    let (_, overflow) = sprite::evaluate(&oam, 50, 8);
+    assert!(overflow);
+}
+
+#[test]
+fn test_loopy_scroll_and_addr_writes() {
+    // the example from https://www.nesdev.org/wiki/PPU_scrolling
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_ctrl(0b0000_0000);
+    ppu.read_status();
+
+    ppu.write_to_ppu_scroll(0b0111_1101);
+    assert_eq!(ppu.loopy.t, 0b0000_0000_0000_1111);
+    assert_eq!(ppu.loopy.x, 0b101);
+    assert!(ppu.loopy.w);
+
+    ppu.write_to_ppu_scroll(0b0101_1110);
+    assert_eq!(ppu.loopy.t, 0b0110_0001_0110_1111);
+    assert!(!ppu.loopy.w);
+
+    ppu.write_to_ppu_addr(0b0011_1101);
+    assert_eq!(ppu.loopy.t, 0b0011_1101_0110_1111);
+
+    ppu.write_to_ppu_addr(0b1111_0000);
+    assert_eq!(ppu.loopy.t, 0b0011_1101_1111_0000);
+    assert_eq!(ppu.loopy.v, ppu.loopy.t);
+}
+
+#[test]
+fn test_status_read_resets_write_toggle() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_addr(0x21);
+    ppu.read_status();
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+
+    assert_eq!(ppu.loopy.addr(), 0x2305);
+}
+
+#[test]
+fn test_data_read_is_buffered() {
+    let mut ppu = new_ppu();
+    ppu.vram[0x0305] = 0x66;
+    ppu.vram[0x0306] = 0x77;
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data();
+    assert_eq!(ppu.read_data(), 0x66);
+    assert_eq!(ppu.read_data(), 0x77);
+}
+
+#[test]
+fn test_nametable_mirror_at_3000() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_addr(0x33);
+    ppu.write_to_ppu_addr(0x05);
+    ppu.write_data(0x66);
+    assert_eq!(ppu.vram[0x0305], 0x66);
+
+    ppu.write_to_ppu_addr(0x33);
+    ppu.write_to_ppu_addr(0x05);
+    ppu.read_data();
+    assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_palette_read_fills_buffer_from_nametable() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[0] = 0x21;
+    ppu.vram[0x0700] = 0x55;
+    ppu.write_to_ppu_addr(0x3F);
+    ppu.write_to_ppu_addr(0x00);
+
+    assert_eq!(ppu.read_data(), 0x21);
+
+    ppu.write_to_ppu_addr(0x20);
+    ppu.write_to_ppu_addr(0x00);
+    assert_eq!(ppu.read_data(), 0x55);
+}
+
+#[test]
+fn test_data_access_during_rendering_increments_x_and_y() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_mask(0b0000_1000);
+
+    // line 10, past the point where v gets its horizontal bits back
+    tick_dots(&mut ppu, 341 + 341 * 10 + 260);
+
+    ppu.write_to_ppu_addr(0x20);
+    ppu.write_to_ppu_addr(0x00);
+    ppu.write_data(0x12);
+
+    // fine y was 2, coarse x was 0
+    assert_eq!(ppu.loopy.v, 0x3001);
+    assert_eq!(ppu.vram[0], 0x12);
+}
+
+#[test]
+fn test_fine_x_scroll() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[1] = 0x21;
+    ppu.vram[1] = 2;
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1010);
+    ppu.write_to_ppu_scroll(3);
+    ppu.write_to_ppu_scroll(0);
+
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(4, 0), 0x00);
+    assert_eq!(ppu.frame.get_pixel(5, 0), 0x21);
+    assert_eq!(ppu.frame.get_pixel(6, 0), 0x00);
+}
+
+#[test]
+fn test_mid_frame_scroll_split() {
+    let mut ppu = new_ppu();
+    ppu.palette_table[1] = 0x21;
+    ppu.palette_table[3] = 0x30;
+    for row in 0..30 {
+        ppu.vram[row * 32] = 1;
+        ppu.vram[row * 32 + 1] = 2;
+    }
+    with_tiles(&mut ppu, 0);
+    ppu.write_to_ppu_mask(0b0000_1010);
+
+    // scroll by a whole tile from line 100 down, the new horizontal position
+    // is picked up at dot 257 of the line before
+    tick_dots(&mut ppu, 341 + 341 * 99 + 200);
+    ppu.write_to_ppu_scroll(8);
+    ppu.write_to_ppu_scroll(0);
+    run_frame(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x30);
+    assert_eq!(ppu.frame.get_pixel(0, 99), 0x30);
+    assert_eq!(ppu.frame.get_pixel(0, 100), 0x21);
+}
+
+#[test]
+fn test_vblank_flag_set_and_cleared() {
+    let mut ppu = new_ppu();
+    run_frame(&mut ppu);
+    tick_dots(&mut ppu, 341 + 1);
+    assert!(!ppu.status.contains(StatusRegister::VBLANK_FLAG));
+
+    tick_dots(&mut ppu, 1);
+    assert!(ppu.status.contains(StatusRegister::VBLANK_FLAG));
+
+    tick_dots(&mut ppu, 341 * 20 - 1);
+    assert!(ppu.status.contains(StatusRegister::VBLANK_FLAG));
+    tick_dots(&mut ppu, 1);
+    assert!(!ppu.status.contains(StatusRegister::VBLANK_FLAG));
+}
+
+#[test]
+fn test_odd_frames_skip_a_dot_when_rendering() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_mask(0b0000_1000);
+    run_frame(&mut ppu);
+
+    let mut lengths = vec![];
+    for _ in 0..2 {
+        let mut dots = 1;
+        while !ppu.tick(1) {
+            dots += 1;
+        }
+        lengths.push(dots);
+    }
+
+    lengths.sort();
+    assert_eq!(lengths, vec![341 * 262 - 1, 341 * 262]);
+}
+
+// Runs until `dots` more dots would set the vblank flag, 0 means it was just set
+fn run_to_vblank(ppu: &mut PPU, dots: usize) {
+    run_frame(ppu);
+    tick_dots(ppu, 341 + 2 - dots);
+}
+
+#[test]
+fn test_nmi_at_vblank() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    run_to_vblank(&mut ppu, 1);
+    assert!(!ppu.poll_nmi_interrupt());
+
+    tick_dots(&mut ppu, 1);
+    assert!(ppu.poll_nmi_interrupt());
+    assert!(!ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_no_nmi_when_disabled() {
+    let mut ppu = new_ppu();
+    run_to_vblank(&mut ppu, 0);
+
+    assert!(ppu.status.contains(StatusRegister::VBLANK_FLAG));
+    assert!(!ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_enabling_nmi_during_vblank_triggers_nmi() {
+    let mut ppu = new_ppu();
+    run_to_vblank(&mut ppu, 0);
+    tick_dots(&mut ppu, 100);
+
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    assert!(ppu.poll_nmi_interrupt());
+
+    // toggling it off and on again gives another one
+    ppu.write_to_ppu_ctrl(0b0000_0000);
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    assert!(ppu.poll_nmi_interrupt());
+
+    // but writing it while already set does not
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    assert!(!ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_enabling_nmi_after_status_read_does_not_trigger_nmi() {
+    let mut ppu = new_ppu();
+    run_to_vblank(&mut ppu, 0);
+    tick_dots(&mut ppu, 100);
+
+    ppu.read_status();
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    assert!(!ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_status_read_before_vblank_suppresses_flag_and_nmi() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    run_to_vblank(&mut ppu, 1);
+
+    assert_eq!(ppu.read_status() & 0x80, 0);
+    tick_dots(&mut ppu, 10);
+
+    assert!(!ppu.status.contains(StatusRegister::VBLANK_FLAG));
+    assert!(!ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_status_read_at_vblank_suppresses_nmi() {
+    for late in 0..2 {
+        let mut ppu = new_ppu();
+        ppu.write_to_ppu_ctrl(0b1000_0000);
+        run_to_vblank(&mut ppu, 0);
+        tick_dots(&mut ppu, late);
+
+        assert_eq!(ppu.read_status() & 0x80, 0x80);
+        assert!(!ppu.poll_nmi_interrupt());
+    }
+}
+
+#[test]
+fn test_status_read_later_in_vblank_keeps_nmi() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_ctrl(0b1000_0000);
+    run_to_vblank(&mut ppu, 0);
+    tick_dots(&mut ppu, 2);
+
+    assert_eq!(ppu.read_status() & 0x80, 0x80);
+    assert!(ppu.poll_nmi_interrupt());
+}
+
+#[test]
+fn test_reset() {
+    let mut ppu = new_ppu();
+    ppu.write_to_ppu_ctrl(0b1000_0011);
+    ppu.write_to_ppu_mask(0b0001_1110);
+    ppu.write_to_ppu_scroll(0x7D);
+    ppu.oam_addr = 0x20;
+    ppu.status.set_vblank_status(true);
+
+    ppu.reset();
+    assert_eq!(ppu.ctrl.flags.bits(), 0);
+    assert_eq!(ppu.mask.flags.bits(), 0);
+    assert_eq!(ppu.loopy.t, 0);
+    assert_eq!(ppu.loopy.x, 0);
+    assert!(!ppu.loopy.w);
+
+    // the rest is left alone
+    assert_eq!(ppu.oam_addr, 0x20);
+    assert!(ppu.status.contains(StatusRegister::VBLANK_FLAG));
+}
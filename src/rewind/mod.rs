@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+use crate::nes::Nes;
+
+#[cfg(test)]
+mod tests;
+
+/*
+ * A history of save states to play a game backwards through. The newest
+ * state is kept whole and every older one is stored as the difference to the
+ * one after it: the two states XORed together, which is mostly zeros from one
+ * frame to the next, then run length encoded. Stepping back undoes the newest
+ * difference, and when the history goes over its memory budget the oldest
+ * differences are dropped off the other end without breaking the chain.
+ */
+pub struct Rewind {
+    interval: u32,
+    budget: usize,
+    frames: u32,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    deltas_size: usize,
+}
+
+impl Rewind {
+    // a snapshot every `interval` frames, keeping to about `budget` bytes
+    pub fn new(interval: u32, budget: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            frames: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+        }
+    }
+
+    // called once a frame, takes a snapshot when one is due
+    pub fn frame(&mut self, nes: &Nes) {
+        if self.frames == 0 {
+            self.push(nes.save_state());
+        }
+        self.frames = (self.frames + 1) % self.interval;
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = self.latest.take() {
+            let delta = encode(&state, &latest);
+            self.deltas_size += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+
+        while self.memory_used() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.deltas_size -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /*
+     * Put the console back to the snapshot before the newest one. That one
+     * becomes the newest, so holding this down walks back through the whole
+     * history and letting go carries on from wherever it stopped. Returns
+     * false once there's nothing older left.
+     */
+    pub fn step_back(&mut self, nes: &mut Nes) -> Result<bool, String> {
+        let (latest, delta) = match (&self.latest, self.deltas.pop_back()) {
+            (Some(latest), Some(delta)) => (latest, delta),
+            _ => return Ok(false),
+        };
+        self.deltas_size -= delta.len();
+
+        let state = decode(latest, &delta)?;
+        nes.load_state(&state)?;
+        self.latest = Some(state);
+        self.frames = 1 % self.interval;
+        Ok(true)
+    }
+
+    // how many snapshots there are to go back through
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.deltas_size
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).ok_or("Rewind snapshot is truncated")?;
+        *pos += 1;
+        if shift >= usize::BITS {
+            return Err("Rewind snapshot is corrupt".to_string());
+        }
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/*
+ * The target's length, then the XOR of the two states as pairs of a run of
+ * zeros and a run of literal bytes, each length a varint. The states don't
+ * have to be the same size, the base counts as zeros past its end.
+ */
+fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| target[i] ^ base.get(i).copied().unwrap_or(0);
+
+    let mut out = Vec::new();
+    write_varint(&mut out, target.len());
+
+    let mut i = 0;
+    while i < target.len() {
+        let zeros = i;
+        while i < target.len() && xor(i) == 0 {
+            i += 1;
+        }
+        write_varint(&mut out, i - zeros);
+
+        let literals = i;
+        while i < target.len() && xor(i) != 0 {
+            i += 1;
+        }
+        write_varint(&mut out, i - literals);
+        out.extend((literals..i).map(xor));
+    }
+    out
+}
+
+fn decode(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos)?;
+    let mut out = Vec::with_capacity(len);
+
+    while out.len() < len {
+        let zeros = read_varint(delta, &mut pos)?;
+        let literals = read_varint(delta, &mut pos)?;
+        let run = zeros.saturating_add(literals);
+        if run == 0 || run > len - out.len() || literals > delta.len() - pos {
+            return Err("Rewind snapshot is corrupt".to_string());
+        }
+
+        for _ in 0..zeros {
+            out.push(base.get(out.len()).copied().unwrap_or(0));
+        }
+        for &byte in &delta[pos..pos + literals] {
+            out.push(byte ^ base.get(out.len()).copied().unwrap_or(0));
+        }
+        pos += literals;
+    }
+
+    if pos != delta.len() {
+        return Err("Rewind snapshot is corrupt".to_string());
+    }
+    Ok(out)
+}
@@ -0,0 +1,147 @@
+use super::*;
+use crate::mem::Mem;
+use crate::rom::tests::busy_rom;
+
+fn round_trip(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let delta = encode(base, target);
+    decode(base, &delta).unwrap()
+}
+
+#[test]
+fn test_encode_round_trip() {
+    let base: Vec<u8> = (0..=255).collect();
+    let mut target = base.clone();
+    target[3] = 0;
+    target[100..110].fill(0xAA);
+    target[255] = 7;
+
+    assert_eq!(round_trip(&base, &target), target);
+    assert_eq!(round_trip(&base, &base), base);
+    assert_eq!(round_trip(&[], &target), target);
+    assert_eq!(round_trip(&base, &[]), Vec::<u8>::new());
+    assert_eq!(round_trip(&base, &target[..50]), &target[..50]);
+    assert_eq!(round_trip(&base[..50], &target), target);
+}
+
+#[test]
+fn test_encode_compresses_small_changes() {
+    let base = vec![0x55; 100_000];
+    let mut target = base.clone();
+    target[50_000] = 0;
+
+    // length, zeros, literals, the byte, zeros, no literals
+    assert_eq!(encode(&base, &base).len(), 3 + 3 + 1);
+    assert!(encode(&base, &target).len() < 16);
+}
+
+#[test]
+fn test_decode_rejects_corrupt_deltas() {
+    let base = vec![1, 2, 3, 4];
+    let delta = encode(&base, &[1, 2, 9, 4]);
+
+    assert!(decode(&base, &delta[..delta.len() - 1]).is_err());
+    assert!(decode(&base, &[delta.clone(), vec![0]].concat()).is_err());
+    // a zero length run would never get anywhere
+    assert!(decode(&base, &[4, 0, 0]).is_err());
+    // longer than the state it's meant to make
+    assert!(decode(&base, &[4, 5, 0]).is_err());
+}
+
+fn run_frames(nes: &mut Nes, rewind: &mut Rewind, frames: u32) {
+    for _ in 0..frames {
+        nes.run_frame().unwrap();
+        rewind.frame(nes);
+    }
+}
+
+#[test]
+fn test_step_back_walks_through_history() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    let mut rewind = Rewind::new(1, usize::MAX);
+    let mut states = Vec::new();
+    for _ in 0..10 {
+        nes.run_frame().unwrap();
+        rewind.frame(&nes);
+        states.push(nes.save_state());
+    }
+    assert_eq!(rewind.len(), 9);
+
+    for expected in states.iter().rev().skip(1) {
+        assert!(rewind.step_back(&mut nes).unwrap());
+        assert_eq!(&nes.save_state(), expected);
+    }
+    assert!(!rewind.step_back(&mut nes).unwrap());
+    assert_eq!(&nes.save_state(), &states[0]);
+}
+
+#[test]
+fn test_interval() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    let mut rewind = Rewind::new(4, usize::MAX);
+    let mut states = Vec::new();
+    for _ in 0..12 {
+        nes.run_frame().unwrap();
+        rewind.frame(&nes);
+        states.push(nes.save_state());
+    }
+
+    // frames 1, 5 and 9
+    assert_eq!(rewind.len(), 2);
+    rewind.step_back(&mut nes).unwrap();
+    assert_eq!(nes.save_state(), states[4]);
+
+    // the next snapshot is a full interval after the one rewound to
+    run_frames(&mut nes, &mut rewind, 3);
+    assert_eq!(rewind.len(), 1);
+    run_frames(&mut nes, &mut rewind, 1);
+    assert_eq!(rewind.len(), 2);
+}
+
+#[test]
+fn test_memory_budget() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    let mut rewind = Rewind::new(1, usize::MAX);
+    run_frames(&mut nes, &mut rewind, 1);
+    let state_size = rewind.memory_used();
+
+    let budget = state_size + 4096;
+    let mut rewind = Rewind::new(1, budget);
+    run_frames(&mut nes, &mut rewind, 200);
+
+    assert!(rewind.memory_used() <= budget);
+    assert!(rewind.len() > 1 && rewind.len() < 199);
+
+    // the oldest ones went, what's left still leads back from the newest
+    let len = rewind.len();
+    for _ in 0..len {
+        assert!(rewind.step_back(&mut nes).unwrap());
+    }
+    assert!(rewind.is_empty());
+
+    // not even room for the newest state, it's kept all the same
+    let mut rewind = Rewind::new(1, 0);
+    run_frames(&mut nes, &mut rewind, 5);
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.memory_used(), state_size);
+}
+
+#[test]
+fn test_resumes_deterministically() {
+    let mut nes = Nes::new(busy_rom()).unwrap();
+    nes.set_sample_rate(44100);
+    nes.cpu_mut().mem_write(0x2001, 0b0001_1110);
+    let mut rewind = Rewind::new(2, usize::MAX);
+
+    run_frames(&mut nes, &mut rewind, 11);
+    let state = nes.save_state();
+    run_frames(&mut nes, &mut rewind, 20);
+    let expected = nes.save_state();
+
+    for _ in 0..10 {
+        rewind.step_back(&mut nes).unwrap();
+    }
+    assert_eq!(nes.save_state(), state);
+
+    run_frames(&mut nes, &mut rewind, 20);
+    assert_eq!(nes.save_state(), expected);
+}
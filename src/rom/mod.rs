@@ -6,12 +6,15 @@ pub enum Mirroring {
     Vertical,
     Horizontal,
     FourScreen,
+    SingleScreenA,
+    SingleScreenB,
 }
 
 const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
 const PRG_ROM_PAGE_SIZE: usize = 16384;
 const CHR_ROM_PAGE_SIZE: usize = 8192;
 
+#[derive(Clone)]
 pub struct Rom {
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
@@ -23,7 +26,7 @@ impl Rom {
     pub fn blank() -> Self {
         Rom {
             prg_rom: vec![0x00; 0xFFFF],
-            chr_rom: vec![0x00],
+            chr_rom: Vec::new(),
             screen_mirroring: Mirroring::Vertical,
             mapper: 0b0000_0000,
         }
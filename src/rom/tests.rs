@@ -38,6 +38,45 @@ pub fn test_rom() -> Rom {
     Rom::new(&test_rom).unwrap()
 }
 
+// NROM running NOPs from $8000 forever, with a JMP back to the start at $F000
+pub fn nop_rom() -> Rom {
+    let mut prg_rom = vec![0xEA; 2 * PRG_ROM_PAGE_SIZE];
+    prg_rom[0x7000..0x7003].copy_from_slice(&[0x4C, 0x00, 0x80]);
+    prg_rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    Rom {
+        prg_rom,
+        chr_rom: Vec::new(),
+        mapper: 0,
+        screen_mirroring: Mirroring::Horizontal,
+    }
+}
+
+/*
+ * Counts in RAM, writes the count to the PPU and the APU and waits for vblank
+ * in between, so a save state has a bit of everything to get right.
+ *
+ * loop: INC $00
+ *       LDA $00
+ *       STA $2007
+ *       STA $4002
+ *       STA $4003
+ *       LDA #$0F
+ *       STA $4015
+ * wait: BIT $2002
+ *       BPL wait
+ *       JMP loop
+ */
+pub fn busy_rom() -> Rom {
+    let mut rom = nop_rom();
+    let program = [
+        0xE6, 0x00, 0xA5, 0x00, 0x8D, 0x07, 0x20, 0x8D, 0x02, 0x40, 0x8D, 0x03, 0x40,
+        0xA9, 0x0F, 0x8D, 0x15, 0x40, 0x2C, 0x02, 0x20, 0x10, 0xFB, 0x4C, 0x00, 0x80,
+    ];
+    rom.prg_rom[..program.len()].copy_from_slice(&program);
+    rom
+}
+
 #[test]
 fn test_test_rom() {
     let rom: Rom = test_rom();
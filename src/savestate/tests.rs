@@ -0,0 +1,52 @@
+use super::*;
+
+#[test]
+fn test_round_trip() {
+    let mut w = StateWriter::new();
+    w.write_u8(0x12);
+    w.write_bool(true);
+    w.write_u16(0x3456);
+    w.write_u32(0x789A_BCDE);
+    w.write_u64(u64::MAX - 1);
+    w.write_bytes(&[1, 2, 3]);
+    let data = w.into_bytes();
+
+    let mut r = StateReader::new(&data);
+    assert_eq!(r.read_u8(), Ok(0x12));
+    assert_eq!(r.read_bool(), Ok(true));
+    assert_eq!(r.read_u16(), Ok(0x3456));
+    assert_eq!(r.read_u32(), Ok(0x789A_BCDE));
+    assert_eq!(r.read_u64(), Ok(u64::MAX - 1));
+    let mut bytes = [0; 3];
+    r.read_into(&mut bytes).unwrap();
+    assert_eq!(bytes, [1, 2, 3]);
+    assert!(r.is_empty());
+}
+
+#[test]
+fn test_truncated() {
+    let mut r = StateReader::new(&[0x01]);
+    assert!(r.read_u16().is_err());
+
+    let mut w = StateWriter::new();
+    w.write_bytes(&[0; 8]);
+    let mut data = w.into_bytes();
+    data.pop();
+    assert!(StateReader::new(&data).read_bytes().is_err());
+}
+
+#[test]
+fn test_bad_flag() {
+    let mut r = StateReader::new(&[2]);
+    assert!(r.read_bool().is_err());
+}
+
+#[test]
+fn test_memory_size_mismatch() {
+    let mut w = StateWriter::new();
+    w.write_bytes(&[0; 4]);
+    let data = w.into_bytes();
+
+    let mut dest = [0; 8];
+    assert!(StateReader::new(&data).read_into(&mut dest).is_err());
+}
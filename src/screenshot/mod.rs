@@ -0,0 +1,81 @@
+use crate::checksum::{adler32, crc32};
+use crate::ppu::frame::{Frame, HEIGHT, WIDTH};
+use crate::ppu::palette::SYSTEM_PALETTE;
+
+#[cfg(test)]
+mod tests;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+// the most a stored deflate block can hold
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+pub fn rgb(frame: &Frame) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for color_idx in frame.data.iter() {
+        let (r, g, b) = SYSTEM_PALETTE[(*color_idx & 0x3F) as usize];
+        rgb.extend_from_slice(&[r, g, b]);
+    }
+    rgb
+}
+
+// a checksum of the colors on screen, stable between runs and platforms so
+// tests can compare it against a known good frame
+pub fn hash(frame: &Frame) -> u32 {
+    crc32(&rgb(frame))
+}
+
+/*
+ * Encode a frame as a 24 bit PNG. The image data isn't compressed, it goes in
+ * stored deflate blocks, which saves pulling in a zlib implementation for
+ * something that only has to be read back by other tools.
+ */
+pub fn png(frame: &Frame) -> Vec<u8> {
+    let rgb = rgb(frame);
+
+    // each row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity(HEIGHT * (WIDTH * 3 + 1));
+    for row in rgb.chunks(WIDTH * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no preset dictionary
+    let mut zlib = vec![0x78, 0x01];
+
+    let blocks = data.chunks(MAX_STORED_BLOCK).count();
+    for (i, block) in data.chunks(MAX_STORED_BLOCK).enumerate() {
+        let last = i + 1 == blocks;
+        zlib.push(last as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
@@ -0,0 +1,83 @@
+use super::*;
+
+// undo zlib_stored, checking the block headers on the way
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    assert_eq!(&zlib[0..2], &[0x78, 0x01]);
+
+    let mut data = Vec::new();
+    let mut pos = 2;
+    loop {
+        let last = zlib[pos] == 1;
+        let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]);
+        let nlen = u16::from_le_bytes([zlib[pos + 3], zlib[pos + 4]]);
+        assert_eq!(len, !nlen);
+
+        pos += 5;
+        data.extend_from_slice(&zlib[pos..pos + len as usize]);
+        pos += len as usize;
+        if last {
+            break;
+        }
+    }
+
+    assert_eq!(&zlib[pos..], &adler32(&data).to_be_bytes());
+    data
+}
+
+#[test]
+fn test_checksums() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+}
+
+#[test]
+fn test_rgb() {
+    let mut frame = Frame::new();
+    frame.set_pixel(1, 0, 0x20);
+
+    let rgb = rgb(&frame);
+    assert_eq!(rgb.len(), WIDTH * HEIGHT * 3);
+    assert_eq!(&rgb[0..6], &[0x80, 0x80, 0x80, 0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_hash() {
+    let mut frame = Frame::new();
+    let blank = hash(&frame);
+    assert_eq!(hash(&frame), blank);
+
+    frame.set_pixel(100, 100, 0x16);
+    assert_ne!(hash(&frame), blank);
+}
+
+#[test]
+fn test_png() {
+    let mut frame = Frame::new();
+    frame.set_pixel(0, 1, 0x20);
+    let png = png(&frame);
+
+    assert_eq!(&png[0..8], &PNG_SIGNATURE);
+
+    // walk the chunks, checking each one's CRC
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        let body = &png[pos + 4..pos + 8 + len];
+        let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+        assert_eq!(crc32(body), crc);
+        chunks.push((body[0..4].to_vec(), body[4..].to_vec()));
+        pos += 12 + len;
+    }
+
+    let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| kind.as_slice()).collect();
+    assert_eq!(kinds, vec![b"IHDR".as_slice(), b"IDAT", b"IEND"]);
+    assert_eq!(&chunks[0].1[0..8], &[0, 0, 1, 0, 0, 0, 0, 0xF0]);
+
+    // more than one stored block's worth
+    let raw = inflate_stored(&chunks[1].1);
+    let stride = WIDTH * 3 + 1;
+    assert_eq!(raw.len(), HEIGHT * stride);
+    assert_eq!(raw[stride], 0);
+    assert_eq!(&raw[stride + 1..stride + 4], &[0xFF, 0xFF, 0xFF]);
+}
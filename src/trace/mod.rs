@@ -1,22 +1,32 @@
-use std::collections::HashMap;
-
 use crate::cpu::{AddressingMode, CPU};
-use crate::mem::Mem;
-use crate::opcode;
 
 #[cfg(test)]
 mod tests;
 
-pub fn trace(cpu: &mut CPU) -> String {
-    let ref opcodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
+pub fn trace(cpu: &CPU) -> String {
+    let opcodes = cpu.variant.opcodes();
     
-    let instr_byte_one: u8 = cpu.mem_read(cpu.program_counter);
-    let instr_byte_two: u8 = cpu.mem_read(cpu.program_counter + 1);
-    let instr_byte_three: u8 = cpu.mem_read(cpu.program_counter + 2);
+    let instr_byte_one: u8 = cpu.bus.peek(cpu.program_counter);
+    let instr_byte_two: u8 = cpu.bus.peek(cpu.program_counter.wrapping_add(1));
+    let instr_byte_three: u8 = cpu.bus.peek(cpu.program_counter.wrapping_add(2));
     let u16_addr = u16::from_le_bytes([instr_byte_two, instr_byte_three]);
 
 
-    let opcode = opcodes.get(&instr_byte_one).expect(&format!("OpCode {:#02X} is not recognized", instr_byte_one));
+    // an opcode this CPU doesn't have, which stops it dead
+    let opcode = match opcodes.get(&instr_byte_one) {
+        Some(opcode) => opcode,
+        None => return format!(
+            "{:04X}  {:02X}        ??? ${:02X}                         A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            cpu.program_counter,
+            instr_byte_one,
+            instr_byte_one,
+            cpu.register_a,
+            cpu.register_x,
+            cpu.register_y,
+            cpu.status.bits(),
+            cpu.stack_pointer,
+        ),
+    };
 
     let opcode_hex = match opcode.len {
         1 => format!("{:02X}      ", instr_byte_one),
@@ -24,45 +34,54 @@ pub fn trace(cpu: &mut CPU) -> String {
         _ => format!("{:02X} {:02X} {:02X}", instr_byte_one, instr_byte_two, instr_byte_three),
     };
 
-    let (mem_addr, stored_value) = match opcode.mode {
-        AddressingMode::Immediate | AddressingMode::None => (0, 0),
-        _ => {
-            let (addr, _) = cpu.resolve_address(&opcode.mode, cpu.program_counter + 1);
-            (addr, cpu.mem_read(addr))
-        }
+    let (mem_addr, stored_value) = match cpu.resolve_address(&opcode.mode, cpu.program_counter.wrapping_add(1)) {
+        Some((addr, _)) => (addr, cpu.bus.peek(addr)),
+        None => (0, 0),
     };
     
-    let opcode_args = match opcode.mode {
+    let mut opcode_args = match opcode.mode {
         // secial cases for 'None' AddressingMode
         AddressingMode::None => {
             match opcode.len {
                 1 => {
                     match opcode.code {
                         0x0A | 0x4A | 0x2A | 0x6A => String::from("A                          "),
+                        // INC A and DEC A
+                        0x1A | 0x3A if cpu.variant.is_cmos() => String::from("A                          "),
                         _ => String::from("                           "),
                     }
                 },
                 2 => {
                     // Branches fall here
                     // We need some shenanigans here to make the arithmetic work
-                    let addr = (cpu.program_counter + 2)
+                    let addr = cpu.program_counter.wrapping_add(2)
                         .wrapping_add_signed((instr_byte_two as i8) as i16);
                     format!("${:04X}                      ", addr)
                 },
                 3 => { 
-                    let mem_addr = cpu.mem_read_u16(cpu.program_counter + 1);
+                    let mem_addr = cpu.bus.peek_u16(cpu.program_counter.wrapping_add(1));
                     
                     if opcode.code == 0x6C {
                         // JMP Indirect
-                        let indirect_ref = if mem_addr & 0x00FF == 0x00FF {
-                            let lo = cpu.mem_read(mem_addr);
-                            let hi = cpu.mem_read(mem_addr & 0xFF00);
+                        let indirect_ref = if mem_addr & 0x00FF == 0x00FF && !cpu.variant.is_cmos() {
+                            let lo = cpu.bus.peek(mem_addr);
+                            let hi = cpu.bus.peek(mem_addr & 0xFF00);
                             u16::from_le_bytes([lo, hi])
                         } else {
-                            cpu.mem_read_u16(mem_addr)
+                            cpu.bus.peek_u16(mem_addr)
                         };
 
                         format!("(${:04X}) = {:04X}             ", mem_addr, indirect_ref)
+                    } else if opcode.code & 0x0F == 0x0F && cpu.variant.is_cmos() {
+                        // BBR and BBS, the byte tested and where it branches
+                        let target = cpu.program_counter.wrapping_add(3)
+                            .wrapping_add_signed((instr_byte_three as i8) as i16);
+                        let value = cpu.bus.peek(instr_byte_two as u16);
+                        format!("{:<27}", format!("${:02X} = {:02X}, ${:04X}", instr_byte_two, value, target))
+                    } else if opcode.code == 0x7C && cpu.variant.is_cmos() {
+                        // JMP (abs,X)
+                        let target = cpu.bus.peek_u16(mem_addr.wrapping_add(cpu.register_x as u16));
+                        format!("(${:04X},X) = {:04X}           ", mem_addr, target)
                     } else {
                         format!("${:04X}                      ", mem_addr)
                     }
@@ -75,7 +94,7 @@ pub fn trace(cpu: &mut CPU) -> String {
         AddressingMode::Implied     => String::from("                           "),
 
         // length 2 modes
-        AddressingMode::Immediate   => format!("#${:02X}                       ", cpu.mem_read(cpu.program_counter + 1)),
+        AddressingMode::Immediate   => format!("#${:02X}                       ", cpu.bus.peek(cpu.program_counter.wrapping_add(1))),
         AddressingMode::ZeroPage    => format!("${:02X} = {:02X}                   ", mem_addr, stored_value),
         AddressingMode::ZeroPage_X  => format!("${:02X},X @ {:02X} = {:02X}            ", instr_byte_two, mem_addr, stored_value),
         AddressingMode::ZeroPage_Y  => format!("${:02X},Y @ {:02X} = {:02X}            ", instr_byte_two, mem_addr, stored_value),
@@ -88,17 +107,24 @@ pub fn trace(cpu: &mut CPU) -> String {
             let target = mem_addr.wrapping_sub(cpu.register_y as u16);
             format!("(${:02X}),Y = {:04X} @ {:04X} = {:02X} ", instr_byte_two, target, mem_addr, stored_value)
         },
+        AddressingMode::ZeroPage_Indirect => {
+            format!("(${:02X}) = {:04X} = {:02X}          ", instr_byte_two, mem_addr, stored_value)
+        },
 
         // length 3 modes
         AddressingMode::Absolute    => format!("${:04X} = {:02X}                 ", mem_addr, stored_value),
         AddressingMode::Absolute_X  => format!("${:04X},X @ {:04X} = {:02X}        ", u16_addr, mem_addr, stored_value),
         AddressingMode::Absolute_Y  => format!("${:04X},Y @ {:04X} = {:02X}        ", u16_addr, mem_addr, stored_value),
 
-        _ => {
-            panic!("mode {:?} is not supported", opcode.mode);
-        }
+        AddressingMode::Indirect    => format!("(${:04X}) = {:04X}             ", u16_addr, mem_addr),
     };
 
+    // the 65C02's bit instructions have a digit on the end, which the
+    // operand makes room for to keep the registers lined up
+    if opcode.mnemonic.len() > 3 && opcode_args.ends_with(' ') {
+        opcode_args.pop();
+    }
+
     format!(
         "{:04X}  {} {}{} {} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
         cpu.program_counter,
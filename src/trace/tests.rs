@@ -1,11 +1,13 @@
 use super::*;
 use crate::bus::Bus;
+use crate::cpu::CpuVariant;
+use crate::joypad::JoypadButton;
 use crate::mem::Mem;
 use crate::rom::tests::test_rom;
 
 #[test]
 fn test_trace_format() {
-    let mut bus = Bus::new(test_rom());
+    let mut bus = Bus::new(test_rom()).unwrap();
     bus.mem_write(100, 0xA2);
     bus.mem_write(101, 0x01);
     bus.mem_write(102, 0xCA);
@@ -21,7 +23,10 @@ fn test_trace_format() {
     let mut result: Vec<String> = vec![];
     cpu.run_with_callback(|cpu| {
         result.push(trace(cpu));
-    });
+        if result.len() == 3 {
+            cpu.stop();
+        }
+    }).unwrap();
     
     assert_eq!(
         "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD",
@@ -39,7 +44,7 @@ fn test_trace_format() {
 
 #[test]
 fn test_format_mem_access() {
-    let mut bus = Bus::new(test_rom());
+    let mut bus = Bus::new(test_rom()).unwrap();
 
     // ORA ($33),Y
     bus.mem_write(100, 0x11);
@@ -59,7 +64,8 @@ fn test_format_mem_access() {
     let mut result: Vec<String> = vec![];
     cpu.run_with_callback(|cpu| {
         result.push(trace(cpu));
-    });
+        cpu.stop();
+    }).unwrap();
 
     assert_eq!(
         "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD",
@@ -67,4 +73,112 @@ fn test_format_mem_access() {
     );
 }
 
+#[test]
+fn test_format_65c02() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+
+    // LDA ($33), INC A
+    bus.mem_write(100, 0xB2);
+    bus.mem_write(101, 0x33);
+    bus.mem_write(102, 0x1A);
+
+    bus.mem_write(0x33, 0x00);
+    bus.mem_write(0x34, 0x04);
+    bus.mem_write(0x0400, 0xAA);
+
+    let mut cpu = CPU::new(bus);
+    cpu.variant = CpuVariant::WDC65C02;
+    cpu.program_counter = 0x64;
+
+    let mut result: Vec<String> = vec![];
+    cpu.run_with_callback(|cpu| {
+        result.push(trace(cpu));
+        if result.len() == 2 {
+            cpu.stop();
+        }
+    }).unwrap();
+
+    assert_eq!(
+        "0064  B2 33     LDA ($33) = 0400 = AA           A:00 X:00 Y:00 P:24 SP:FD",
+        result[0],
+    );
+    assert_eq!(
+        "0066  1A        INC A                           A:AA X:00 Y:00 P:A4 SP:FD",
+        result[1],
+    );
+}
+
+#[test]
+fn test_format_65c02_unused_opcode() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+    bus.mem_write(100, 0xA3);
+
+    let mut cpu = CPU::new(bus);
+    cpu.variant = CpuVariant::WDC65C02;
+    cpu.program_counter = 0x64;
+
+    assert_eq!(
+        "0064  A3       *NOP                             A:00 X:00 Y:00 P:24 SP:FD",
+        trace(&cpu),
+    );
+}
 
+#[test]
+fn test_format_65c02_bit_instructions() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+
+    // SMB3 $10, BBS3 $10,-4
+    bus.mem_write(100, 0xB7);
+    bus.mem_write(101, 0x10);
+    bus.mem_write(102, 0xBF);
+    bus.mem_write(103, 0x10);
+    bus.mem_write(104, 0xFC);
+
+    let mut cpu = CPU::new(bus);
+    cpu.variant = CpuVariant::WDC65C02;
+    cpu.program_counter = 0x64;
+
+    let mut result: Vec<String> = vec![];
+    cpu.run_with_callback(|cpu| {
+        result.push(trace(cpu));
+        if result.len() == 2 {
+            cpu.stop();
+        }
+    }).unwrap();
+
+    assert_eq!(
+        "0064  B7 10     SMB3 $10 = 00                   A:00 X:00 Y:00 P:24 SP:FD",
+        result[0],
+    );
+    assert_eq!(
+        "0066  BF 10 FC  BBS3 $10 = 08, $0065            A:00 X:00 Y:00 P:24 SP:FD",
+        result[1],
+    );
+}
+
+#[test]
+fn test_trace_leaves_registers_alone() {
+    let mut bus = Bus::new(test_rom()).unwrap();
+
+    // LDA $4016
+    bus.mem_write(100, 0xAD);
+    bus.mem_write(101, 0x16);
+    bus.mem_write(102, 0x40);
+
+    bus.joypad1.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+
+    let mut cpu = CPU::new(bus);
+    cpu.program_counter = 0x64;
+
+    assert_eq!(
+        "0064  AD 16 40  LDA $4016 = 01                  A:00 X:00 Y:00 P:24 SP:FD",
+        trace(&cpu),
+    );
+
+    // the program still gets the A button
+    cpu.step();
+    assert_eq!(cpu.register_a & 1, 1);
+    assert_eq!(cpu.bus.take_error(), None);
+}